@@ -0,0 +1,234 @@
+//! Benchmarks of the cryptographic operations
+
+use canbench_rs::{bench, bench_fn, BenchResult};
+use ic_bls12_381::{G1Affine, G2Affine};
+use ic_vetkeys::{
+    verify_bls_signature, DerivedPublicKey, EncryptedVetKey, IbeCiphertext, IbeEncryptionContext,
+    IbeIdentity, IbeSeed, MasterPublicKey, TransportSecretKey, VetKey,
+};
+use ic_vetkeys_test_utils::{create_encrypted_key, random_scalar, DerivationContext};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::hint::black_box;
+
+const CANISTER_ID: &[u8] = b"canister-id";
+const CONTEXT: &[u8] = b"context";
+const INPUT: &[u8] = b"input";
+
+/// Everything needed to run the client side of VetKD
+struct Setup {
+    rng: ChaCha20Rng,
+    master_public_key: MasterPublicKey,
+    transport_secret_key: TransportSecretKey,
+    derived_public_key: DerivedPublicKey,
+    encrypted_vetkey: EncryptedVetKey,
+    vetkey: VetKey,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let derivation_context = DerivationContext::new(CANISTER_ID, CONTEXT);
+
+        let transport_secret_key = TransportSecretKey::random(&mut rng);
+        let transport_public_key =
+            G1Affine::from_compressed(&transport_secret_key.public_key().try_into().unwrap())
+                .unwrap();
+
+        let master_secret_key = random_scalar(&mut rng);
+        let master_public_key = G2Affine::from(G2Affine::generator() * master_secret_key);
+        let (derived_public_key, _delta) = derivation_context.derive_key(&master_public_key);
+        let derived_public_key =
+            DerivedPublicKey::deserialize(&derived_public_key.to_compressed()).unwrap();
+
+        let encrypted_vetkey = EncryptedVetKey::deserialize(&create_encrypted_key(
+            &mut rng,
+            &master_public_key,
+            &master_secret_key,
+            &transport_public_key,
+            &derivation_context,
+            INPUT,
+        ))
+        .unwrap();
+        let vetkey = encrypted_vetkey
+            .decrypt_and_verify(&transport_secret_key, &derived_public_key, INPUT)
+            .unwrap();
+
+        Self {
+            rng,
+            master_public_key: MasterPublicKey::deserialize(&master_public_key.to_compressed())
+                .unwrap(),
+            transport_secret_key,
+            derived_public_key,
+            encrypted_vetkey,
+            vetkey,
+        }
+    }
+
+    fn ibe_ciphertext(&mut self, msg_len: usize) -> IbeCiphertext {
+        let seed = IbeSeed::random(&mut self.rng);
+        IbeCiphertext::encrypt(
+            &self.derived_public_key,
+            &IbeIdentity::from_bytes(INPUT),
+            &vec![0x42; msg_len],
+            &seed,
+        )
+    }
+}
+
+#[bench(raw)]
+fn master_public_key_derive_canister_key() -> BenchResult {
+    let setup = Setup::new();
+    bench_fn(|| {
+        black_box(
+            black_box(&setup.master_public_key)
+                .derive_canister_key(CANISTER_ID)
+                .derive_sub_key(CONTEXT),
+        )
+    })
+}
+
+#[bench(raw)]
+fn derived_public_key_derive_sub_key() -> BenchResult {
+    let setup = Setup::new();
+    bench_fn(|| black_box(black_box(&setup.derived_public_key).derive_sub_key(CONTEXT)))
+}
+
+#[bench(raw)]
+fn derived_public_key_deserialize() -> BenchResult {
+    let bytes = Setup::new().derived_public_key.serialize();
+    bench_fn(|| black_box(DerivedPublicKey::deserialize(black_box(&bytes)).unwrap()))
+}
+
+#[bench(raw)]
+fn derived_public_key_deserialize_unchecked() -> BenchResult {
+    let bytes = Setup::new().derived_public_key.serialize_uncompressed();
+    bench_fn(|| black_box(DerivedPublicKey::deserialize_unchecked(black_box(&bytes)).unwrap()))
+}
+
+#[bench(raw)]
+fn derived_public_key_prepared() -> BenchResult {
+    let setup = Setup::new();
+    bench_fn(|| black_box(black_box(&setup.derived_public_key).prepared()))
+}
+
+#[bench(raw)]
+fn encrypted_vetkey_deserialize() -> BenchResult {
+    let bytes = Setup::new().encrypted_vetkey.serialize();
+    bench_fn(|| black_box(EncryptedVetKey::deserialize(black_box(&bytes)).unwrap()))
+}
+
+#[bench(raw)]
+fn encrypted_vetkey_decrypt_and_verify() -> BenchResult {
+    let setup = Setup::new();
+    bench_fn(|| {
+        black_box(
+            setup
+                .encrypted_vetkey
+                .decrypt_and_verify(
+                    &setup.transport_secret_key,
+                    &setup.derived_public_key,
+                    black_box(INPUT),
+                )
+                .unwrap(),
+        )
+    })
+}
+
+#[bench(raw)]
+fn encrypted_vetkey_decrypt_and_verify_prepared() -> BenchResult {
+    let setup = Setup::new();
+    let prepared = setup.derived_public_key.prepared();
+    bench_fn(|| {
+        black_box(
+            setup
+                .encrypted_vetkey
+                .decrypt_and_verify_prepared(
+                    &setup.transport_secret_key,
+                    &prepared,
+                    black_box(INPUT),
+                )
+                .unwrap(),
+        )
+    })
+}
+
+#[bench(raw)]
+fn bls_signature_verify() -> BenchResult {
+    let setup = Setup::new();
+    let signature = setup.vetkey.signature_bytes().to_vec();
+    bench_fn(|| {
+        assert!(verify_bls_signature(
+            &setup.derived_public_key,
+            black_box(INPUT),
+            black_box(&signature),
+        ))
+    })
+}
+
+#[bench(raw)]
+fn vetkey_deserialize() -> BenchResult {
+    let bytes = Setup::new().vetkey.signature_bytes().to_vec();
+    bench_fn(|| black_box(VetKey::deserialize(black_box(&bytes)).unwrap()))
+}
+
+#[bench(raw)]
+fn vetkey_deserialize_unchecked() -> BenchResult {
+    let bytes = Setup::new().vetkey.serialize_uncompressed();
+    bench_fn(|| black_box(VetKey::deserialize_unchecked(black_box(&bytes)).unwrap()))
+}
+
+#[bench(raw)]
+fn ibe_encrypt_32_bytes() -> BenchResult {
+    let mut setup = Setup::new();
+    let seed = IbeSeed::random(&mut setup.rng);
+    let identity = IbeIdentity::from_bytes(INPUT);
+    bench_fn(|| {
+        black_box(IbeCiphertext::encrypt(
+            &setup.derived_public_key,
+            &identity,
+            black_box(&[0x42; 32]),
+            &seed,
+        ))
+    })
+}
+
+#[bench(raw)]
+fn ibe_encrypt_32_bytes_with_context() -> BenchResult {
+    let mut setup = Setup::new();
+    let seed = IbeSeed::random(&mut setup.rng);
+    let context =
+        IbeEncryptionContext::new(&setup.derived_public_key, &IbeIdentity::from_bytes(INPUT));
+    bench_fn(|| black_box(context.encrypt(black_box(&[0x42; 32]), &seed)))
+}
+
+#[bench(raw)]
+fn ibe_decrypt_32_bytes() -> BenchResult {
+    let mut setup = Setup::new();
+    let ciphertext = setup.ibe_ciphertext(32);
+    bench_fn(|| black_box(ciphertext.decrypt(black_box(&setup.vetkey)).unwrap()))
+}
+
+#[bench(raw)]
+fn ibe_decrypt_4_kib() -> BenchResult {
+    let mut setup = Setup::new();
+    let ciphertext = setup.ibe_ciphertext(4096);
+    bench_fn(|| black_box(ciphertext.decrypt(black_box(&setup.vetkey)).unwrap()))
+}
+
+#[bench(raw)]
+fn ibe_decrypt_100_ciphertexts() -> BenchResult {
+    let mut setup = Setup::new();
+    let ciphertexts: Vec<_> = (0..100).map(|_| setup.ibe_ciphertext(32)).collect();
+    bench_fn(|| {
+        for ciphertext in &ciphertexts {
+            black_box(ciphertext.decrypt(black_box(&setup.vetkey)).unwrap());
+        }
+    })
+}
+
+#[bench(raw)]
+fn ibe_ciphertext_deserialize() -> BenchResult {
+    let bytes = Setup::new().ibe_ciphertext(32).serialize();
+    bench_fn(|| black_box(IbeCiphertext::deserialize(black_box(&bytes)).unwrap()))
+}
@@ -0,0 +1,12 @@
+//! Benchmarks of the instructions used by `ic-vetkeys` inside a canister
+//!
+//! The benchmarks are run with [canbench](https://github.com/dfinity/canbench)
+//! and measure the number of Wasm instructions, which determines the cycles
+//! cost of a call and whether it fits into the instruction limit of a single
+//! message. Run `canbench` in this directory to compare against the results
+//! in `canbench_results.yml`, and `canbench --persist` to update them.
+
+#[cfg(feature = "canbench-rs")]
+mod crypto;
+#[cfg(feature = "canbench-rs")]
+mod storage;
@@ -0,0 +1,207 @@
+//! Benchmarks of `KeyManager` and `EncryptedMaps` holding many entries
+
+use canbench_rs::{bench, bench_fn, BenchResult};
+use candid::Principal;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::DefaultMemoryImpl;
+use ic_vetkeys::encrypted_maps::EncryptedMaps;
+use ic_vetkeys::key_manager::KeyManager;
+use ic_vetkeys::types::{AccessRights, ByteBuf, KeyId, RemovedMapValues};
+use ic_vetkeys_test_utils::{random_key, random_name, random_self_authenticating_principal};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::hint::black_box;
+
+/// Number of entries in the benchmarked data structures
+const ENTRIES: usize = 1_000;
+
+/// Size of each encrypted value in the benchmarked maps
+const VALUE_BYTES: usize = 100;
+
+fn rng() -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(42)
+}
+
+fn key_manager() -> KeyManager<AccessRights> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    KeyManager::init(
+        "key_manager",
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+        memory_manager.get(MemoryId::new(2)),
+    )
+}
+
+fn encrypted_maps() -> EncryptedMaps<AccessRights> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    EncryptedMaps::init(
+        "encrypted_maps",
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+        memory_manager.get(MemoryId::new(2)),
+        memory_manager.get(MemoryId::new(3)),
+    )
+}
+
+/// Creates a key manager in which `user` has been granted access to
+/// `ENTRIES` keys of different owners
+fn key_manager_with_shared_keys(
+    rng: &mut ChaCha20Rng,
+    user: Principal,
+) -> KeyManager<AccessRights> {
+    let mut key_manager = key_manager();
+    for _ in 0..ENTRIES {
+        let owner = random_self_authenticating_principal(rng);
+        let key_id = (owner, random_name(rng));
+        key_manager
+            .set_user_rights(owner, key_id, user, AccessRights::ReadWrite)
+            .unwrap();
+    }
+    key_manager
+}
+
+/// Creates encrypted maps with a single map of `ENTRIES` values
+fn encrypted_maps_with_values(rng: &mut ChaCha20Rng) -> (EncryptedMaps<AccessRights>, KeyId) {
+    let mut encrypted_maps = encrypted_maps();
+    let owner = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    for _ in 0..ENTRIES {
+        encrypted_maps
+            .insert_encrypted_value(
+                owner,
+                map_id,
+                random_key(rng),
+                ByteBuf::from(vec![0x42; VALUE_BYTES]),
+            )
+            .unwrap();
+    }
+    (encrypted_maps, map_id)
+}
+
+#[bench(raw)]
+fn key_manager_set_user_rights() -> BenchResult {
+    let rng = &mut rng();
+    let user = random_self_authenticating_principal(rng);
+    let mut key_manager = key_manager_with_shared_keys(rng, user);
+    let owner = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    bench_fn(|| {
+        black_box(
+            key_manager
+                .set_user_rights(owner, key_id, user, AccessRights::Read)
+                .unwrap(),
+        )
+    })
+}
+
+#[bench(raw)]
+fn key_manager_get_accessible_shared_key_ids() -> BenchResult {
+    let rng = &mut rng();
+    let user = random_self_authenticating_principal(rng);
+    let key_manager = key_manager_with_shared_keys(rng, user);
+    bench_fn(|| {
+        let key_ids = key_manager.get_accessible_shared_key_ids(black_box(user));
+        assert_eq!(key_ids.len(), ENTRIES);
+    })
+}
+
+#[bench(raw)]
+fn key_manager_get_shared_user_access_for_key() -> BenchResult {
+    let rng = &mut rng();
+    let mut key_manager = key_manager();
+    let owner = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    for _ in 0..ENTRIES {
+        let user = random_self_authenticating_principal(rng);
+        key_manager
+            .set_user_rights(owner, key_id, user, AccessRights::Read)
+            .unwrap();
+    }
+    bench_fn(|| {
+        let users = key_manager
+            .get_shared_user_access_for_key(owner, black_box(key_id))
+            .unwrap();
+        assert_eq!(users.len(), ENTRIES);
+    })
+}
+
+#[bench(raw)]
+fn key_manager_ensure_user_can_read() -> BenchResult {
+    let rng = &mut rng();
+    let user = random_self_authenticating_principal(rng);
+    let key_manager = key_manager_with_shared_keys(rng, user);
+    let key_id = key_manager.get_accessible_shared_key_ids(user)[ENTRIES / 2];
+    bench_fn(|| {
+        black_box(
+            key_manager
+                .ensure_user_can_read(black_box(user), key_id)
+                .unwrap(),
+        )
+    })
+}
+
+#[bench(raw)]
+fn encrypted_maps_insert_encrypted_value() -> BenchResult {
+    let rng = &mut rng();
+    let (mut encrypted_maps, map_id) = encrypted_maps_with_values(rng);
+    let key = random_key(rng);
+    let value = ByteBuf::from(vec![0x42; VALUE_BYTES]);
+    bench_fn(|| {
+        black_box(
+            encrypted_maps
+                .insert_encrypted_value(map_id.0, map_id, key, value)
+                .unwrap(),
+        )
+    })
+}
+
+#[bench(raw)]
+fn encrypted_maps_get_encrypted_values_for_map() -> BenchResult {
+    let rng = &mut rng();
+    let (encrypted_maps, map_id) = encrypted_maps_with_values(rng);
+    bench_fn(|| {
+        let values = encrypted_maps
+            .get_encrypted_values_for_map(map_id.0, black_box(map_id))
+            .unwrap();
+        assert_eq!(values.len(), ENTRIES);
+    })
+}
+
+#[bench(raw)]
+fn encrypted_maps_get_all_accessible_encrypted_values() -> BenchResult {
+    let rng = &mut rng();
+    let mut encrypted_maps = encrypted_maps();
+    let user = random_self_authenticating_principal(rng);
+    const MAPS: usize = 10;
+    for _ in 0..MAPS {
+        let owner = random_self_authenticating_principal(rng);
+        let map_id = (owner, random_name(rng));
+        for _ in 0..ENTRIES / MAPS {
+            encrypted_maps
+                .insert_encrypted_value(
+                    owner,
+                    map_id,
+                    random_key(rng),
+                    ByteBuf::from(vec![0x42; VALUE_BYTES]),
+                )
+                .unwrap();
+        }
+        encrypted_maps
+            .set_user_rights(owner, map_id, user, AccessRights::Read)
+            .unwrap();
+    }
+    bench_fn(|| {
+        let maps = encrypted_maps.get_all_accessible_encrypted_values(black_box(user));
+        assert_eq!(maps.len(), MAPS);
+    })
+}
+
+#[bench(raw)]
+fn encrypted_maps_remove_map_values() -> BenchResult {
+    let rng = &mut rng();
+    let (mut encrypted_maps, map_id) = encrypted_maps_with_values(rng);
+    bench_fn(|| {
+        let removed = encrypted_maps.remove_map_values(map_id.0, map_id).unwrap();
+        assert!(matches!(removed, RemovedMapValues::Keys(keys) if keys.len() == ENTRIES));
+    })
+}
@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 
-use candid::Principal;
+use candid::{CandidType, Principal};
 use ic_cdk::{query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::storable::Blob;
@@ -11,6 +11,22 @@ use ic_vetkeys::types::{AccessRights, ByteBuf, EncryptedMapValue, TransportKey};
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 type MapId = (Principal, ByteBuf);
 
+/// A corrupt or inconsistent entry, see [`ic_vetkeys::types::CorruptEntry`].
+#[derive(CandidType, Clone, Debug)]
+enum CorruptEntry {
+    AccessGrant { user: Principal, key_id: MapId },
+    SharedKeyWithoutAccessGrant { key_id: MapId, user: Principal },
+    AccessGrantWithoutSharedKey { user: Principal, key_id: MapId },
+    EncryptedMapValue { map_id: MapId, map_key: ByteBuf },
+}
+
+/// The values removed by `remove_map_values`, see [`ic_vetkeys::types::RemovedMapValues`].
+#[derive(CandidType, Clone, Debug)]
+enum RemovedMapValues {
+    Keys(Vec<ByteBuf>),
+    Count(u64),
+}
+
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
@@ -58,6 +74,49 @@ fn get_encrypted_values_for_map(
     })
 }
 
+#[query]
+fn range_encrypted_values(
+    map_owner: Principal,
+    map_name: ByteBuf,
+    start: ByteBuf,
+    end: Option<ByteBuf>,
+    limit: Option<usize>,
+) -> Result<Vec<(ByteBuf, EncryptedMapValue)>, String> {
+    let map_name = bytebuf_to_blob(map_name)?;
+    let map_id = (map_owner, map_name);
+    let start = bytebuf_to_blob(start)?;
+    let end = end.map(bytebuf_to_blob).transpose()?;
+    let result = ENCRYPTED_MAPS.with_borrow(|encrypted_maps| {
+        encrypted_maps.range_encrypted_values(ic_cdk::caller(), map_id, start, end, limit)
+    });
+    result.map(|map_values| {
+        map_values
+            .into_iter()
+            .map(|(key, value)| (ByteBuf::from(key.as_slice().to_vec()), value))
+            .collect()
+    })
+}
+
+#[query]
+fn prefix_encrypted_values(
+    map_owner: Principal,
+    map_name: ByteBuf,
+    prefix: ByteBuf,
+    limit: Option<usize>,
+) -> Result<Vec<(ByteBuf, EncryptedMapValue)>, String> {
+    let map_name = bytebuf_to_blob(map_name)?;
+    let map_id = (map_owner, map_name);
+    let result = ENCRYPTED_MAPS.with_borrow(|encrypted_maps| {
+        encrypted_maps.prefix_encrypted_values(ic_cdk::caller(), map_id, prefix.as_ref(), limit)
+    });
+    result.map(|map_values| {
+        map_values
+            .into_iter()
+            .map(|(key, value)| (ByteBuf::from(key.as_slice().to_vec()), value))
+            .collect()
+    })
+}
+
 #[query]
 fn get_all_accessible_encrypted_values() -> Vec<(MapId, Vec<(ByteBuf, EncryptedMapValue)>)> {
     ENCRYPTED_MAPS
@@ -98,20 +157,19 @@ fn get_encrypted_value(
 }
 
 #[update]
-fn remove_map_values(
-    map_owner: Principal,
-    map_name: ByteBuf,
-) -> Result<Vec<EncryptedMapValue>, String> {
+fn remove_map_values(map_owner: Principal, map_name: ByteBuf) -> Result<RemovedMapValues, String> {
     let map_name = bytebuf_to_blob(map_name)?;
     let map_id = (map_owner, map_name);
     let result = ENCRYPTED_MAPS.with_borrow_mut(|encrypted_maps| {
         encrypted_maps.remove_map_values(ic_cdk::caller(), map_id)
     });
-    result.map(|removed| {
-        removed
-            .into_iter()
-            .map(|key| ByteBuf::from(key.as_ref().to_vec()))
-            .collect()
+    result.map(|removed| match removed {
+        ic_vetkeys::types::RemovedMapValues::Keys(keys) => RemovedMapValues::Keys(
+            keys.into_iter()
+                .map(|key| ByteBuf::from(key.as_ref().to_vec()))
+                .collect(),
+        ),
+        ic_vetkeys::types::RemovedMapValues::Count(count) => RemovedMapValues::Count(count),
     })
 }
 
@@ -207,6 +265,19 @@ fn set_user_rights(
     })
 }
 
+#[update]
+fn set_public_access(
+    map_owner: Principal,
+    map_name: ByteBuf,
+    access_rights: Option<AccessRights>,
+) -> Result<Option<AccessRights>, String> {
+    let map_name = bytebuf_to_blob(map_name)?;
+    let map_id = (map_owner, map_name);
+    ENCRYPTED_MAPS.with_borrow_mut(|encrypted_maps| {
+        encrypted_maps.set_public_access(ic_cdk::caller(), map_id, access_rights)
+    })
+}
+
 #[update]
 fn remove_user(
     map_owner: Principal,
@@ -220,6 +291,66 @@ fn remove_user(
     })
 }
 
+#[query]
+fn find_corrupt_entries() -> Vec<CorruptEntry> {
+    ensure_caller_is_controller();
+    ENCRYPTED_MAPS.with_borrow(|encrypted_maps| {
+        encrypted_maps
+            .find_corrupt_entries()
+            .into_iter()
+            .map(to_candid_corrupt_entry)
+            .collect()
+    })
+}
+
+#[update]
+fn remove_corrupt_entries() -> Vec<CorruptEntry> {
+    ensure_caller_is_controller();
+    ENCRYPTED_MAPS.with_borrow_mut(|encrypted_maps| {
+        encrypted_maps
+            .remove_corrupt_entries()
+            .into_iter()
+            .map(to_candid_corrupt_entry)
+            .collect()
+    })
+}
+
+fn ensure_caller_is_controller() {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        ic_cdk::trap("only controllers can access corrupt entries");
+    }
+}
+
+fn to_candid_corrupt_entry(entry: ic_vetkeys::types::CorruptEntry) -> CorruptEntry {
+    use ic_vetkeys::types::CorruptEntry as Entry;
+    match entry {
+        Entry::AccessGrant { user, key_id } => CorruptEntry::AccessGrant {
+            user,
+            key_id: to_candid_map_id(key_id),
+        },
+        Entry::SharedKeyWithoutAccessGrant { key_id, user } => {
+            CorruptEntry::SharedKeyWithoutAccessGrant {
+                key_id: to_candid_map_id(key_id),
+                user,
+            }
+        }
+        Entry::AccessGrantWithoutSharedKey { user, key_id } => {
+            CorruptEntry::AccessGrantWithoutSharedKey {
+                user,
+                key_id: to_candid_map_id(key_id),
+            }
+        }
+        Entry::EncryptedMapValue { map_id, map_key } => CorruptEntry::EncryptedMapValue {
+            map_id: to_candid_map_id(map_id),
+            map_key: ByteBuf::from(map_key.as_ref().to_vec()),
+        },
+    }
+}
+
+fn to_candid_map_id((owner, name): ic_vetkeys::types::MapId) -> MapId {
+    (owner, ByteBuf::from(name.as_ref().to_vec()))
+}
+
 fn bytebuf_to_blob(buf: ByteBuf) -> Result<Blob<32>, String> {
     Blob::try_from(buf.as_ref()).map_err(|_| "too large input".to_string())
 }
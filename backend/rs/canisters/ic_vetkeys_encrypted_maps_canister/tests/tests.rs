@@ -154,6 +154,27 @@ fn map_sharing_should_work() {
     assert_eq!(get_vetkey(env.principal_0), get_vetkey(env.principal_1));
 }
 
+#[test]
+fn only_controllers_should_find_or_remove_corrupt_entries() {
+    let rng = &mut reproducible_rng();
+    let env = TestEnvironment::new(rng);
+    let controller = env.pic.get_controllers(env.example_canister_id)[0];
+
+    for method_name in ["find_corrupt_entries", "remove_corrupt_entries"] {
+        let result = env.pic.update_call(
+            env.example_canister_id,
+            env.principal_0,
+            method_name,
+            encode_one(()).unwrap(),
+        );
+        assert!(result.is_err());
+
+        let corrupt_entries: Vec<candid::Reserved> =
+            env.update(controller, method_name, encode_one(()).unwrap());
+        assert!(corrupt_entries.is_empty());
+    }
+}
+
 struct TestEnvironment {
     pic: PocketIc,
     example_canister_id: Principal,
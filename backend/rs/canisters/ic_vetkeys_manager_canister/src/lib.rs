@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 
-use candid::Principal;
+use candid::{CandidType, Principal};
 use ic_cdk::{query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::storable::Blob;
@@ -9,6 +9,15 @@ use ic_vetkeys::key_manager::{KeyManager, VetKey, VetKeyVerificationKey};
 use ic_vetkeys::types::{AccessRights, ByteBuf, TransportKey};
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
+type KeyId = (Principal, ByteBuf);
+
+/// A corrupt or inconsistent entry, see [`ic_vetkeys::types::CorruptEntry`].
+#[derive(CandidType, Clone, Debug)]
+enum CorruptEntry {
+    AccessGrant { user: Principal, key_id: KeyId },
+    SharedKeyWithoutAccessGrant { key_id: KeyId, user: Principal },
+    AccessGrantWithoutSharedKey { user: Principal, key_id: KeyId },
+}
 
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -80,6 +89,17 @@ fn set_user_rights(
         .with_borrow_mut(|km| km.set_user_rights(ic_cdk::caller(), key_id, user, access_rights))
 }
 
+#[update]
+fn set_public_access(
+    key_owner: Principal,
+    key_name: ByteBuf,
+    access_rights: Option<AccessRights>,
+) -> Result<Option<AccessRights>, String> {
+    let key_name = bytebuf_to_blob(key_name)?;
+    let key_id = (key_owner, key_name);
+    KEY_MANAGER.with_borrow_mut(|km| km.set_public_access(ic_cdk::caller(), key_id, access_rights))
+}
+
 #[update]
 fn remove_user(
     key_owner: Principal,
@@ -91,6 +111,62 @@ fn remove_user(
     KEY_MANAGER.with_borrow_mut(|km| km.remove_user(ic_cdk::caller(), key_id, user))
 }
 
+#[query]
+fn find_corrupt_entries() -> Vec<CorruptEntry> {
+    ensure_caller_is_controller();
+    KEY_MANAGER.with_borrow(|km| {
+        km.find_corrupt_entries()
+            .into_iter()
+            .filter_map(to_candid_corrupt_entry)
+            .collect()
+    })
+}
+
+#[update]
+fn remove_corrupt_entries() -> Vec<CorruptEntry> {
+    ensure_caller_is_controller();
+    KEY_MANAGER.with_borrow_mut(|km| {
+        km.remove_corrupt_entries()
+            .into_iter()
+            .filter_map(to_candid_corrupt_entry)
+            .collect()
+    })
+}
+
+fn ensure_caller_is_controller() {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        ic_cdk::trap("only controllers can access corrupt entries");
+    }
+}
+
+fn to_candid_corrupt_entry(entry: ic_vetkeys::types::CorruptEntry) -> Option<CorruptEntry> {
+    use ic_vetkeys::types::CorruptEntry as Entry;
+    Some(match entry {
+        Entry::AccessGrant { user, key_id } => CorruptEntry::AccessGrant {
+            user,
+            key_id: to_candid_key_id(key_id),
+        },
+        Entry::SharedKeyWithoutAccessGrant { key_id, user } => {
+            CorruptEntry::SharedKeyWithoutAccessGrant {
+                key_id: to_candid_key_id(key_id),
+                user,
+            }
+        }
+        Entry::AccessGrantWithoutSharedKey { user, key_id } => {
+            CorruptEntry::AccessGrantWithoutSharedKey {
+                user,
+                key_id: to_candid_key_id(key_id),
+            }
+        }
+        // the key manager has no encrypted map values
+        Entry::EncryptedMapValue { .. } => return None,
+    })
+}
+
+fn to_candid_key_id((owner, name): ic_vetkeys::key_manager::KeyId) -> KeyId {
+    (owner, ByteBuf::from(name.as_ref().to_vec()))
+}
+
 fn bytebuf_to_blob(buf: ByteBuf) -> Result<Blob<32>, String> {
     Blob::try_from(buf.as_ref()).map_err(|_| "too large input".to_string())
 }
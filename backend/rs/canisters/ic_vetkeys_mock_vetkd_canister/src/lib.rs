@@ -0,0 +1,89 @@
+//! A test canister emulating the vetKD methods of the management canister
+//!
+//! The canister exposes `vetkd_public_key` and `vetkd_derive_key` with the
+//! same Candid interface as the management canister, backed by
+//! [`MockVetKd`] with a master key derived from the seed passed at
+//! installation. Controllers can inject faults into subsequent calls. The
+//! canister is intended for tests only: its master secret key is derived from
+//! a public seed and is therefore not secret.
+
+use std::cell::RefCell;
+
+use candid::{CandidType, Deserialize};
+use ic_cdk::api::call::{msg_cycles_accept128, msg_cycles_available128};
+use ic_cdk::{init, post_upgrade, query, update};
+use ic_vetkeys::vetkd_api_types::{
+    VetKDDeriveKeyReply, VetKDDeriveKeyRequest, VetKDPublicKeyReply, VetKDPublicKeyRequest,
+};
+use ic_vetkeys_test_utils::mock_vetkd::{Fault, MockVetKd, VetKdMethod};
+use serde_bytes::ByteBuf;
+
+#[derive(CandidType, Deserialize, Default)]
+struct InitArgs {
+    master_key_seed: Option<ByteBuf>,
+    key_name: Option<String>,
+}
+
+thread_local! {
+    static MOCK_VETKD: RefCell<MockVetKd> = RefCell::new(MockVetKd::new([0; 32]));
+}
+
+#[init]
+fn init(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+    let seed = match args.master_key_seed {
+        Some(seed) => <[u8; 32]>::try_from(seed.as_slice())
+            .unwrap_or_else(|_| ic_cdk::trap("master key seed must be 32 bytes long")),
+        None => [0; 32],
+    };
+    let mut mock = MockVetKd::new(seed);
+    if let Some(key_name) = args.key_name {
+        mock = mock.with_key_name(&key_name);
+    }
+    MOCK_VETKD.set(mock);
+}
+
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    init(args);
+}
+
+#[update]
+fn vetkd_public_key(request: VetKDPublicKeyRequest) -> VetKDPublicKeyReply {
+    MOCK_VETKD
+        .with_borrow_mut(|mock| mock.vetkd_public_key(ic_cdk::caller(), request))
+        .unwrap_or_else(|e| ic_cdk::trap(&e))
+}
+
+#[update]
+fn vetkd_derive_key(request: VetKDDeriveKeyRequest) -> VetKDDeriveKeyReply {
+    msg_cycles_accept128(msg_cycles_available128());
+    MOCK_VETKD
+        .with_borrow_mut(|mock| mock.vetkd_derive_key(ic_cdk::caller(), request))
+        .unwrap_or_else(|e| ic_cdk::trap(&e))
+}
+
+#[query]
+fn master_public_key() -> ByteBuf {
+    MOCK_VETKD.with_borrow(|mock| ByteBuf::from(mock.master_public_key()))
+}
+
+#[update]
+fn inject_fault(method: VetKdMethod, fault: Fault) {
+    ensure_caller_is_controller();
+    MOCK_VETKD.with_borrow_mut(|mock| mock.inject_fault(method, fault));
+}
+
+#[update]
+fn clear_faults() {
+    ensure_caller_is_controller();
+    MOCK_VETKD.with_borrow_mut(|mock| mock.clear_faults());
+}
+
+fn ensure_caller_is_controller() {
+    if !ic_cdk::api::is_controller(&ic_cdk::caller()) {
+        ic_cdk::trap("only controllers can inject faults");
+    }
+}
+
+ic_cdk::export_candid!();
@@ -0,0 +1,221 @@
+use candid::{decode_one, encode_args, encode_one, CandidType, Principal};
+use ic_vetkeys::vetkd_api_types::{
+    VetKDDeriveKeyReply, VetKDDeriveKeyRequest, VetKDPublicKeyReply, VetKDPublicKeyRequest,
+};
+use ic_vetkeys::{DerivedPublicKey, EncryptedVetKey, MasterPublicKey, TransportSecretKey};
+use ic_vetkeys_test_utils::mock_vetkd::{Fault, MockVetKd, VetKdMethod};
+use ic_vetkeys_test_utils::{git_root_dir, random_self_authenticating_principal, reproducible_rng};
+use pocket_ic::{PocketIc, PocketIcBuilder};
+use rand::{CryptoRng, Rng};
+use serde_bytes::ByteBuf;
+use std::path::Path;
+
+#[test]
+fn master_public_key_should_match_native_mock() {
+    let rng = &mut reproducible_rng();
+    let seed: [u8; 32] = rng.gen();
+    let env = TestEnvironment::new(Some(seed));
+
+    let master_public_key: ByteBuf =
+        env.query(env.controller, "master_public_key", encode_one(()).unwrap());
+
+    assert_eq!(
+        master_public_key.into_vec(),
+        MockVetKd::new(seed).master_public_key()
+    );
+}
+
+#[test]
+fn derived_key_should_verify_against_public_key() {
+    let rng = &mut reproducible_rng();
+    let env = TestEnvironment::new(None);
+    let caller = random_self_authenticating_principal(rng);
+    let transport_key = random_transport_key(rng);
+    let context = b"mock vetkd test".to_vec();
+    let input = rng.gen::<[u8; 32]>().to_vec();
+
+    let public_key = env
+        .update::<VetKDPublicKeyReply>(
+            caller,
+            "vetkd_public_key",
+            encode_one(env.public_key_request(context.clone())).unwrap(),
+        )
+        .public_key;
+
+    let master_public_key: ByteBuf =
+        env.query(caller, "master_public_key", encode_one(()).unwrap());
+    let expected_public_key = MasterPublicKey::deserialize(&master_public_key)
+        .unwrap()
+        .derive_canister_key(caller.as_slice())
+        .derive_sub_key(&context);
+    assert_eq!(public_key, expected_public_key.serialize());
+
+    let encrypted_key = env
+        .update::<VetKDDeriveKeyReply>(
+            caller,
+            "vetkd_derive_key",
+            encode_one(env.derive_key_request(context, input.clone(), &transport_key)).unwrap(),
+        )
+        .encrypted_key;
+
+    EncryptedVetKey::deserialize(&encrypted_key)
+        .unwrap()
+        .decrypt_and_verify(
+            &transport_key,
+            &DerivedPublicKey::deserialize(&public_key).unwrap(),
+            &input,
+        )
+        .expect("failed to decrypt and verify vetkey");
+}
+
+#[test]
+fn injected_fault_should_reject_next_call() {
+    let rng = &mut reproducible_rng();
+    let env = TestEnvironment::new(None);
+    let caller = random_self_authenticating_principal(rng);
+
+    env.update::<()>(
+        env.controller,
+        "inject_fault",
+        encode_args((
+            VetKdMethod::PublicKey,
+            Fault::Reject("key unavailable".to_string()),
+        ))
+        .unwrap(),
+    );
+
+    let request = encode_one(env.public_key_request(vec![])).unwrap();
+    let reject = env
+        .pic
+        .update_call(env.canister_id, caller, "vetkd_public_key", request.clone())
+        .unwrap_err();
+    assert!(reject.reject_message.contains("key unavailable"));
+
+    env.update::<VetKDPublicKeyReply>(caller, "vetkd_public_key", request);
+}
+
+#[test]
+fn only_controllers_should_inject_faults() {
+    let rng = &mut reproducible_rng();
+    let env = TestEnvironment::new(None);
+    let caller = random_self_authenticating_principal(rng);
+
+    let result = env.pic.update_call(
+        env.canister_id,
+        caller,
+        "inject_fault",
+        encode_args((VetKdMethod::DeriveKey, Fault::MalformedReply)).unwrap(),
+    );
+
+    assert!(result.is_err());
+}
+
+struct TestEnvironment {
+    pic: PocketIc,
+    canister_id: Principal,
+    controller: Principal,
+}
+
+impl TestEnvironment {
+    fn new(master_key_seed: Option<[u8; 32]>) -> Self {
+        let pic = PocketIcBuilder::new().with_application_subnet().build();
+
+        let controller = Principal::anonymous();
+        let canister_id = pic.create_canister_with_settings(Some(controller), None);
+        pic.add_cycles(canister_id, 2_000_000_000_000);
+
+        let init_args = master_key_seed.map(|seed| InitArgs {
+            master_key_seed: Some(ByteBuf::from(seed.to_vec())),
+            key_name: None,
+        });
+        pic.install_canister(
+            canister_id,
+            load_mock_vetkd_canister_wasm(),
+            encode_one(init_args).unwrap(),
+            Some(controller),
+        );
+
+        Self {
+            pic,
+            canister_id,
+            controller,
+        }
+    }
+
+    fn public_key_request(&self, context: Vec<u8>) -> VetKDPublicKeyRequest {
+        VetKDPublicKeyRequest {
+            canister_id: None,
+            context,
+            key_id: MockVetKd::new([0; 32]).key_id(),
+        }
+    }
+
+    fn derive_key_request(
+        &self,
+        context: Vec<u8>,
+        input: Vec<u8>,
+        transport_key: &TransportSecretKey,
+    ) -> VetKDDeriveKeyRequest {
+        VetKDDeriveKeyRequest {
+            input,
+            context,
+            transport_public_key: transport_key.public_key(),
+            key_id: MockVetKd::new([0; 32]).key_id(),
+        }
+    }
+
+    fn update<T: CandidType + for<'de> candid::Deserialize<'de>>(
+        &self,
+        caller: Principal,
+        method_name: &str,
+        args: Vec<u8>,
+    ) -> T {
+        let reply = self
+            .pic
+            .update_call(self.canister_id, caller, method_name, args);
+        match reply {
+            Ok(data) => decode_one(&data).expect("failed to decode reply"),
+            Err(user_error) => panic!("canister returned a user error: {user_error}"),
+        }
+    }
+
+    fn query<T: CandidType + for<'de> candid::Deserialize<'de>>(
+        &self,
+        caller: Principal,
+        method_name: &str,
+        args: Vec<u8>,
+    ) -> T {
+        let reply = self
+            .pic
+            .query_call(self.canister_id, caller, method_name, args);
+        match reply {
+            Ok(data) => decode_one(&data).expect("failed to decode reply"),
+            Err(user_error) => panic!("canister returned a user error: {user_error}"),
+        }
+    }
+}
+
+#[derive(CandidType)]
+struct InitArgs {
+    master_key_seed: Option<ByteBuf>,
+    key_name: Option<String>,
+}
+
+fn load_mock_vetkd_canister_wasm() -> Vec<u8> {
+    let wasm_path_string = match std::env::var("CUSTOM_WASM_PATH") {
+        Ok(path) if !path.is_empty() => path,
+        _ => format!(
+            "{}/target/wasm32-unknown-unknown/release/ic_vetkeys_mock_vetkd_canister.wasm",
+            git_root_dir()
+        ),
+    };
+    let wasm_path = Path::new(&wasm_path_string);
+    std::fs::read(wasm_path)
+        .expect("wasm does not exist - run `cargo build --release --target wasm32-unknown-unknown`")
+}
+
+fn random_transport_key<R: Rng + CryptoRng>(rng: &mut R) -> TransportSecretKey {
+    let mut seed = vec![0u8; 32];
+    rng.fill_bytes(&mut seed);
+    TransportSecretKey::from_seed(seed).unwrap()
+}
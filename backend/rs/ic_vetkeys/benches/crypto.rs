@@ -0,0 +1,151 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ic_bls12_381::{G1Affine, G2Affine};
+use ic_vetkeys::{
+    verify_bls_signature, DerivedPublicKey, EncryptedVetKey, IbeCiphertext, IbeEncryptionContext,
+    IbeIdentity, IbeSeed, MasterPublicKey, TransportSecretKey, VetKey,
+};
+use ic_vetkeys_test_utils::{create_encrypted_key, random_scalar, DerivationContext};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::hint::black_box;
+
+const CANISTER_ID: &[u8] = b"canister-id";
+const CONTEXT: &[u8] = b"context";
+const INPUT: &[u8] = b"input";
+
+struct Setup {
+    rng: ChaCha20Rng,
+    master_public_key: MasterPublicKey,
+    transport_secret_key: TransportSecretKey,
+    derived_public_key: DerivedPublicKey,
+    encrypted_vetkey: EncryptedVetKey,
+    vetkey: VetKey,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let derivation_context = DerivationContext::new(CANISTER_ID, CONTEXT);
+
+        let transport_secret_key = TransportSecretKey::random(&mut rng);
+        let transport_public_key =
+            G1Affine::from_compressed(&transport_secret_key.public_key().try_into().unwrap())
+                .unwrap();
+
+        let master_secret_key = random_scalar(&mut rng);
+        let master_public_key = G2Affine::from(G2Affine::generator() * master_secret_key);
+        let (derived_public_key, _delta) = derivation_context.derive_key(&master_public_key);
+        let derived_public_key =
+            DerivedPublicKey::deserialize(&derived_public_key.to_compressed()).unwrap();
+
+        let encrypted_vetkey = EncryptedVetKey::deserialize(&create_encrypted_key(
+            &mut rng,
+            &master_public_key,
+            &master_secret_key,
+            &transport_public_key,
+            &derivation_context,
+            INPUT,
+        ))
+        .unwrap();
+        let vetkey = encrypted_vetkey
+            .decrypt_and_verify(&transport_secret_key, &derived_public_key, INPUT)
+            .unwrap();
+
+        Self {
+            rng,
+            master_public_key: MasterPublicKey::deserialize(&master_public_key.to_compressed())
+                .unwrap(),
+            transport_secret_key,
+            derived_public_key,
+            encrypted_vetkey,
+            vetkey,
+        }
+    }
+}
+
+fn bench_key_derivation(c: &mut Criterion) {
+    let setup = Setup::new();
+
+    let mut group = c.benchmark_group("KeyDerivation");
+    group.bench_function("derive_canister_key", |b| {
+        b.iter(|| black_box(&setup.master_public_key).derive_canister_key(CANISTER_ID))
+    });
+    group.bench_function("derive_sub_key", |b| {
+        b.iter(|| black_box(&setup.derived_public_key).derive_sub_key(CONTEXT))
+    });
+    group.bench_function("prepared", |b| {
+        b.iter(|| black_box(&setup.derived_public_key).prepared())
+    });
+    group.finish();
+}
+
+fn bench_vetkey_verification(c: &mut Criterion) {
+    let setup = Setup::new();
+    let prepared = setup.derived_public_key.prepared();
+    let signature = setup.vetkey.signature_bytes().to_vec();
+
+    let mut group = c.benchmark_group("VetKeyVerification");
+    group.bench_function("decrypt_and_verify", |b| {
+        b.iter(|| {
+            setup.encrypted_vetkey.decrypt_and_verify(
+                &setup.transport_secret_key,
+                &setup.derived_public_key,
+                black_box(INPUT),
+            )
+        })
+    });
+    group.bench_function("decrypt_and_verify_prepared", |b| {
+        b.iter(|| {
+            setup.encrypted_vetkey.decrypt_and_verify_prepared(
+                &setup.transport_secret_key,
+                &prepared,
+                black_box(INPUT),
+            )
+        })
+    });
+    group.bench_function("verify_bls_signature", |b| {
+        b.iter(|| verify_bls_signature(&setup.derived_public_key, black_box(INPUT), &signature))
+    });
+    group.bench_function("verify_bls_signature_prepared", |b| {
+        b.iter(|| prepared.verify_bls_signature(black_box(INPUT), &signature))
+    });
+    group.finish();
+}
+
+fn bench_ibe(c: &mut Criterion) {
+    let mut setup = Setup::new();
+    let identity = IbeIdentity::from_bytes(INPUT);
+    let context = IbeEncryptionContext::new(&setup.derived_public_key, &identity);
+    let seed = IbeSeed::random(&mut setup.rng);
+
+    let mut group = c.benchmark_group("Ibe");
+    for msg_len in [32, 4096] {
+        let msg = vec![0x42; msg_len];
+        let ciphertext = IbeCiphertext::encrypt(&setup.derived_public_key, &identity, &msg, &seed);
+        group.throughput(Throughput::Bytes(msg_len as u64));
+        group.bench_with_input(BenchmarkId::new("encrypt", msg_len), &msg, |b, msg| {
+            b.iter(|| {
+                IbeCiphertext::encrypt(&setup.derived_public_key, &identity, black_box(msg), &seed)
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("encrypt_with_context", msg_len),
+            &msg,
+            |b, msg| b.iter(|| context.encrypt(black_box(msg), &seed)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("decrypt", msg_len),
+            &ciphertext,
+            |b, ciphertext| b.iter(|| ciphertext.decrypt(black_box(&setup.vetkey))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_key_derivation,
+    bench_vetkey_verification,
+    bench_ibe
+);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ic_bls12_381::{G1Affine, G2Affine, Scalar};
+use ic_vetkeys::{DerivedPublicKey, EncryptedVetKey, VetKey};
+use std::hint::black_box;
+
+fn g1(s: u64) -> G1Affine {
+    G1Affine::from(G1Affine::generator() * Scalar::from(s))
+}
+
+fn g2(s: u64) -> G2Affine {
+    G2Affine::from(G2Affine::generator() * Scalar::from(s))
+}
+
+fn bench_derived_public_key(c: &mut Criterion) {
+    let dpk = DerivedPublicKey::deserialize(&g2(42).to_compressed()).unwrap();
+    let compressed = dpk.serialize();
+    let uncompressed = dpk.serialize_uncompressed();
+
+    let mut group = c.benchmark_group("DerivedPublicKey");
+    group.bench_function("deserialize", |b| {
+        b.iter(|| DerivedPublicKey::deserialize(black_box(&compressed)))
+    });
+    for (name, bytes) in [("compressed", &compressed), ("uncompressed", &uncompressed)] {
+        group.bench_with_input(
+            BenchmarkId::new("deserialize_unchecked", name),
+            bytes,
+            |b, bytes| b.iter(|| DerivedPublicKey::deserialize_unchecked(black_box(bytes))),
+        );
+    }
+    group.finish();
+}
+
+fn bench_encrypted_vetkey(c: &mut Criterion) {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&g1(7).to_compressed());
+    bytes.extend_from_slice(&g2(7).to_compressed());
+    bytes.extend_from_slice(&g1(11).to_compressed());
+    let ek = EncryptedVetKey::deserialize(&bytes).unwrap();
+    let compressed = ek.serialize();
+    let uncompressed = ek.serialize_uncompressed();
+
+    let mut group = c.benchmark_group("EncryptedVetKey");
+    group.bench_function("deserialize", |b| {
+        b.iter(|| EncryptedVetKey::deserialize(black_box(&compressed)))
+    });
+    for (name, bytes) in [("compressed", &compressed), ("uncompressed", &uncompressed)] {
+        group.bench_with_input(
+            BenchmarkId::new("deserialize_unchecked", name),
+            bytes,
+            |b, bytes| b.iter(|| EncryptedVetKey::deserialize_unchecked(black_box(bytes))),
+        );
+    }
+    group.finish();
+}
+
+fn bench_vetkey(c: &mut Criterion) {
+    let vetkey = VetKey::deserialize(&g1(13).to_compressed()).unwrap();
+    let compressed = vetkey.signature_bytes().to_vec();
+    let uncompressed = vetkey.serialize_uncompressed().to_vec();
+
+    let mut group = c.benchmark_group("VetKey");
+    group.bench_function("deserialize", |b| {
+        b.iter(|| VetKey::deserialize(black_box(&compressed)))
+    });
+    for (name, bytes) in [("compressed", &compressed), ("uncompressed", &uncompressed)] {
+        group.bench_with_input(
+            BenchmarkId::new("deserialize_unchecked", name),
+            bytes,
+            |b, bytes| b.iter(|| VetKey::deserialize_unchecked(black_box(bytes))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_derived_public_key,
+    bench_encrypted_vetkey,
+    bench_vetkey
+);
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+use candid::Principal;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::DefaultMemoryImpl;
+use ic_vetkeys::encrypted_maps::EncryptedMaps;
+use ic_vetkeys::key_manager::KeyManager;
+use ic_vetkeys::types::{AccessRights, ByteBuf, KeyId};
+use ic_vetkeys_test_utils::{random_key, random_name, random_self_authenticating_principal};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::hint::black_box;
+
+/// Size of each encrypted value in the benchmarked maps
+const VALUE_BYTES: usize = 100;
+
+fn key_manager_with_shared_keys(
+    rng: &mut ChaCha20Rng,
+    user: Principal,
+    entries: usize,
+) -> KeyManager<AccessRights> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    let mut key_manager = KeyManager::init(
+        "key_manager",
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+        memory_manager.get(MemoryId::new(2)),
+    );
+    for _ in 0..entries {
+        let owner = random_self_authenticating_principal(rng);
+        let key_id = (owner, random_name(rng));
+        key_manager
+            .set_user_rights(owner, key_id, user, AccessRights::ReadWrite)
+            .unwrap();
+    }
+    key_manager
+}
+
+fn encrypted_maps_with_values(
+    rng: &mut ChaCha20Rng,
+    entries: usize,
+) -> (EncryptedMaps<AccessRights>, KeyId) {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    let mut encrypted_maps = EncryptedMaps::init(
+        "encrypted_maps",
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+        memory_manager.get(MemoryId::new(2)),
+        memory_manager.get(MemoryId::new(3)),
+    );
+    let owner = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    for _ in 0..entries {
+        encrypted_maps
+            .insert_encrypted_value(
+                owner,
+                map_id,
+                random_key(rng),
+                ByteBuf::from(vec![0x42; VALUE_BYTES]),
+            )
+            .unwrap();
+    }
+    (encrypted_maps, map_id)
+}
+
+fn bench_key_manager(c: &mut Criterion) {
+    let rng = &mut ChaCha20Rng::seed_from_u64(42);
+
+    let mut group = c.benchmark_group("KeyManager");
+    for entries in [100, 1_000, 10_000] {
+        let user = random_self_authenticating_principal(rng);
+        let mut key_manager = key_manager_with_shared_keys(rng, user, entries);
+        let key_id = key_manager.get_accessible_shared_key_ids(user)[entries / 2];
+        let other_user = random_self_authenticating_principal(rng);
+
+        group.bench_function(
+            BenchmarkId::new("get_accessible_shared_key_ids", entries),
+            |b| b.iter(|| key_manager.get_accessible_shared_key_ids(black_box(user))),
+        );
+        group.bench_function(BenchmarkId::new("ensure_user_can_read", entries), |b| {
+            b.iter(|| key_manager.ensure_user_can_read(black_box(user), key_id))
+        });
+        // benchmark writes last, since they add entries
+        group.bench_function(BenchmarkId::new("set_user_rights", entries), |b| {
+            b.iter_batched(
+                || {
+                    let owner = random_self_authenticating_principal(rng);
+                    (owner, random_name(rng))
+                },
+                |key_id| {
+                    key_manager.set_user_rights(key_id.0, key_id, other_user, AccessRights::Read)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_encrypted_maps(c: &mut Criterion) {
+    let rng = &mut ChaCha20Rng::seed_from_u64(42);
+
+    let mut group = c.benchmark_group("EncryptedMaps");
+    for entries in [100, 1_000, 10_000] {
+        let (mut encrypted_maps, map_id) = encrypted_maps_with_values(rng, entries);
+        let value = ByteBuf::from(vec![0x42; VALUE_BYTES]);
+
+        group.bench_function(
+            BenchmarkId::new("get_encrypted_values_for_map", entries),
+            |b| b.iter(|| encrypted_maps.get_encrypted_values_for_map(map_id.0, black_box(map_id))),
+        );
+        // benchmark writes last, since they add entries
+        group.bench_function(BenchmarkId::new("insert_encrypted_value", entries), |b| {
+            b.iter_batched(
+                || random_key(rng),
+                |key| encrypted_maps.insert_encrypted_value(map_id.0, map_id, key, value.clone()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_key_manager, bench_encrypted_maps);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+#![no_main]
+
+use ic_stable_structures::Storable;
+use ic_vetkeys::types::{AccessGrant, AccessRights, StoredValue, TryFromBytes};
+use libfuzzer_sys::fuzz_target;
+use std::borrow::Cow;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(access_rights) = AccessRights::try_from_bytes(data) {
+        assert_eq!(access_rights.to_bytes().as_ref(), data);
+        assert_eq!(AccessRights::from_bytes(Cow::Borrowed(data)), access_rights);
+    }
+
+    // access grants are stored in a `StoredValue`, which must not panic
+    match StoredValue::<AccessGrant<AccessRights>>::from_bytes(Cow::Borrowed(data)) {
+        StoredValue::Valid(grant) => {
+            assert_eq!(AccessGrant::from_bytes(grant.to_bytes()), grant);
+        }
+        corrupt @ StoredValue::Corrupt(_) => assert_eq!(corrupt.to_bytes().as_ref(), data),
+    }
+});
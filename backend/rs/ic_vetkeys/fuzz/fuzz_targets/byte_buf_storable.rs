@@ -0,0 +1,21 @@
+#![no_main]
+
+use ic_stable_structures::Storable;
+use ic_vetkeys::types::{ByteBuf, StoredValue, TryFromBytes};
+use libfuzzer_sys::fuzz_target;
+use std::borrow::Cow;
+
+fuzz_target!(|data: &[u8]| {
+    // Candid admits several encodings of the same value, so only the decoded
+    // value has to survive a round trip.
+    if let Ok(buf) = ByteBuf::try_from_bytes(data) {
+        assert_eq!(ByteBuf::from_bytes(Cow::Borrowed(data)), buf);
+        assert_eq!(ByteBuf::try_from_bytes(&buf.to_bytes()), Ok(buf));
+    }
+
+    // encrypted map values are stored in a `StoredValue`, which must not panic
+    let stored = StoredValue::<ByteBuf>::from_bytes(Cow::Borrowed(data));
+    if stored.is_corrupt() {
+        assert_eq!(stored.to_bytes().as_ref(), data);
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use ic_vetkeys::DerivedPublicKey;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(key) = DerivedPublicKey::deserialize(data) {
+        assert_eq!(key.serialize(), data);
+        assert_eq!(
+            DerivedPublicKey::deserialize_unchecked(&key.serialize_uncompressed()),
+            Ok(key)
+        );
+    }
+    if let Ok(key) = DerivedPublicKey::deserialize_unchecked(data) {
+        assert_eq!(
+            DerivedPublicKey::deserialize_unchecked(&key.serialize()),
+            Ok(key)
+        );
+    }
+});
@@ -0,0 +1,20 @@
+#![no_main]
+
+use ic_vetkeys::EncryptedVetKey;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(key) = EncryptedVetKey::deserialize(data) {
+        assert_eq!(key.serialize(), data);
+        assert_eq!(
+            EncryptedVetKey::deserialize_unchecked(&key.serialize_uncompressed()),
+            Ok(key)
+        );
+    }
+    if let Ok(key) = EncryptedVetKey::deserialize_unchecked(data) {
+        assert_eq!(
+            EncryptedVetKey::deserialize_unchecked(&key.serialize()),
+            Ok(key)
+        );
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use ic_vetkeys::IbeCiphertext;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(ctext) = IbeCiphertext::deserialize(data) {
+        assert_eq!(ctext.serialize(), data);
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+
+use ic_vetkeys::TransportSecretKey;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(key) = TransportSecretKey::deserialize(data) {
+        assert_eq!(key.serialize(), data);
+    }
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use ic_vetkeys::VetKey;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(key) = VetKey::deserialize(data) {
+        assert_eq!(key.signature_bytes().as_slice(), data);
+        let stored = VetKey::deserialize_unchecked(&key.serialize_uncompressed())
+            .expect("failed to deserialize uncompressed VetKey");
+        assert_eq!(stored, key);
+    }
+    if let Ok(key) = VetKey::deserialize_unchecked(data) {
+        let stored = VetKey::deserialize_unchecked(key.signature_bytes())
+            .expect("failed to deserialize compressed VetKey");
+        assert_eq!(stored, key);
+    }
+});
@@ -8,8 +8,10 @@ use std::future::Future;
 
 use crate::key_manager::KeyId;
 use crate::types::{
-    AccessControl, ByteBuf, EncryptedMapValue, MapId, MapKey, MapName, TransportKey,
+    AccessControl, ByteBuf, CorruptEntry, EncryptedMapValue, MapId, MapKey, MapName,
+    RemovedMapValues, StoredValue, TransportKey,
 };
+use crate::vetkd_api::{ManagementCanisterVetKdApi, VetKdApi};
 
 pub type VetKeyVerificationKey = ByteBuf;
 pub type VetKey = ByteBuf;
@@ -17,7 +19,7 @@ pub type VetKey = ByteBuf;
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 /// The **EncryptedMaps** backend is a support library built on top of [`crate::key_manager::KeyManager`].
-/// 
+///
 /// **EncryptedMaps** is designed to facilitate secure, encrypted data sharing between users on the Internet Computer (ICP) using the **vetKeys** feature. It allows developers to store encrypted key-value pairs (**maps**) securely and to manage fine-grained user access.
 ///
 /// For an introduction to **vetKeys**, refer to the [vetKeys Overview](https://internetcomputer.org/docs/building-apps/network-features/encryption/vetKeys).
@@ -42,6 +44,9 @@ type Memory = VirtualMemory<DefaultMemoryImpl>;
 /// - **Encrypted Values Storage:** Maps `(KeyId, MapKey)` to `EncryptedMapValue`, securely storing encrypted data.
 /// - **KeyManager Integration:** Uses **KeyManager** to handle user permissions, ensuring authorized access to maps.
 ///
+/// Encrypted values that cannot be decoded from stable memory are skipped when listing the values of a map.
+/// [`EncryptedMaps::find_corrupt_entries`] reports them and [`EncryptedMaps::remove_corrupt_entries`] removes them.
+///
 /// ## Example Use Case
 ///
 /// 1. **User A** initializes an encrypted map and adds values.
@@ -57,9 +62,9 @@ type Memory = VirtualMemory<DefaultMemoryImpl>;
 ///
 /// ## Summary
 /// **EncryptedMaps** simplifies secure storage, retrieval, and controlled sharing of encrypted data on the Internet Computer, complementing the robust security and permissions management provided by **KeyManager**.
-pub struct EncryptedMaps<T: AccessControl> {
-    pub key_manager: crate::key_manager::KeyManager<T>,
-    pub mapkey_vals: StableBTreeMap<(KeyId, MapKey), EncryptedMapValue, Memory>,
+pub struct EncryptedMaps<T: AccessControl, V: VetKdApi = ManagementCanisterVetKdApi> {
+    pub key_manager: crate::key_manager::KeyManager<T, V>,
+    pub mapkey_vals: StableBTreeMap<(KeyId, MapKey), StoredValue<EncryptedMapValue>, Memory>,
 }
 
 impl<T: AccessControl> EncryptedMaps<T> {
@@ -101,11 +106,34 @@ impl<T: AccessControl> EncryptedMaps<T> {
         memory_shared_keys: Memory,
         memory_encrypted_maps: Memory,
     ) -> Self {
-        let key_manager = crate::key_manager::KeyManager::init(
+        Self::init_with_vetkd_api(
+            domain_separator,
+            memory_domain_separator,
+            memory_access_control,
+            memory_shared_keys,
+            memory_encrypted_maps,
+            ManagementCanisterVetKdApi::default(),
+        )
+    }
+}
+
+impl<T: AccessControl, V: VetKdApi> EncryptedMaps<T, V> {
+    /// Initializes the [`EncryptedMaps`] and the underlying [`crate::key_manager::KeyManager`],
+    /// obtaining vetKeys through `vetkd_api`.
+    pub fn init_with_vetkd_api(
+        domain_separator: &str,
+        memory_domain_separator: Memory,
+        memory_access_control: Memory,
+        memory_shared_keys: Memory,
+        memory_encrypted_maps: Memory,
+        vetkd_api: V,
+    ) -> Self {
+        let key_manager = crate::key_manager::KeyManager::init_with_vetkd_api(
             domain_separator,
             memory_domain_separator,
             memory_access_control,
             memory_shared_keys,
+            vetkd_api,
         );
 
         let mapkey_vals = StableBTreeMap::init(memory_encrypted_maps);
@@ -134,14 +162,19 @@ impl<T: AccessControl> EncryptedMaps<T> {
     }
 
     /// Removes all values from a map if the caller has sufficient rights.
-    /// Returns the removed keys.
-    /// The caller must have write permissions to perform this operation.
+    /// Returns the removed keys if the caller can also read the map, and
+    /// only their number otherwise.
+    /// The caller must have delete permissions to perform this operation.
     pub fn remove_map_values(
         &mut self,
         caller: Principal,
         key_id: KeyId,
-    ) -> Result<Vec<MapKey>, String> {
-        self.key_manager.ensure_user_can_write(caller, key_id)?;
+    ) -> Result<RemovedMapValues, String> {
+        self.key_manager.ensure_user_can_delete(caller, key_id)?;
+        let can_read = self
+            .key_manager
+            .ensure_user_can_read(caller, key_id)
+            .is_ok();
 
         let keys: Vec<_> = self
             .mapkey_vals
@@ -154,10 +187,15 @@ impl<T: AccessControl> EncryptedMaps<T> {
             self.mapkey_vals.remove(&(key_id, *key));
         }
 
-        Ok(keys)
+        if can_read {
+            Ok(RemovedMapValues::Keys(keys))
+        } else {
+            Ok(RemovedMapValues::Count(keys.len() as u64))
+        }
     }
 
     /// Retrieves all encrypted key-value pairs from a map.
+    /// Values that cannot be decoded are skipped.
     /// The caller must have read permissions to access the map values.
     pub fn get_encrypted_values_for_map(
         &self,
@@ -170,12 +208,65 @@ impl<T: AccessControl> EncryptedMaps<T> {
             .mapkey_vals
             .range((key_id, Blob::default())..)
             .take_while(|((k, _), _)| k == &key_id)
-            .map(|((_, k), v)| (k, v))
+            .filter_map(|((_, k), v)| Some((k, v.into_valid()?)))
+            .collect())
+    }
+
+    /// Retrieves the encrypted key-value pairs of a map whose keys lie in the range `[start, end)`.
+    /// If `end` is `None`, the range extends to the last key of the map.
+    /// If `limit` is `Some`, at most `limit` key-value pairs are returned.
+    /// Values that cannot be decoded are skipped.
+    /// The caller must have read permissions to access the map values.
+    pub fn range_encrypted_values(
+        &self,
+        caller: Principal,
+        key_id: KeyId,
+        start: MapKey,
+        end: Option<MapKey>,
+        limit: Option<usize>,
+    ) -> Result<Vec<(MapKey, EncryptedMapValue)>, String> {
+        self.key_manager.ensure_user_can_read(caller, key_id)?;
+
+        Ok(self
+            .mapkey_vals
+            .range((key_id, start)..)
+            .take_while(|((k, map_key), _)| k == &key_id && end.is_none_or(|end| map_key < &end))
+            .filter_map(|((_, k), v)| Some((k, v.into_valid()?)))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    /// Retrieves the encrypted key-value pairs of a map whose keys start with `prefix`.
+    /// This allows to efficiently browse hierarchical keys, e.g., all entries of a "folder".
+    /// If `limit` is `Some`, at most `limit` key-value pairs are returned.
+    /// Values that cannot be decoded are skipped.
+    /// The caller must have read permissions to access the map values.
+    pub fn prefix_encrypted_values(
+        &self,
+        caller: Principal,
+        key_id: KeyId,
+        prefix: &[u8],
+        limit: Option<usize>,
+    ) -> Result<Vec<(MapKey, EncryptedMapValue)>, String> {
+        self.key_manager.ensure_user_can_read(caller, key_id)?;
+
+        let Ok(start) = MapKey::try_from(prefix) else {
+            // no map key can be longer than its maximum size
+            return Ok(vec![]);
+        };
+
+        Ok(self
+            .mapkey_vals
+            .range((key_id, start)..)
+            .take_while(|((k, map_key), _)| k == &key_id && map_key.as_slice().starts_with(prefix))
+            .filter_map(|((_, k), v)| Some((k, v.into_valid()?)))
+            .take(limit.unwrap_or(usize::MAX))
             .collect())
     }
 
     /// Retrieves a specific encrypted value from a map.
     /// The caller must have read permissions to access the value.
+    /// Returns an error if the value cannot be decoded.
     pub fn get_encrypted_value(
         &self,
         caller: Principal,
@@ -183,7 +274,11 @@ impl<T: AccessControl> EncryptedMaps<T> {
         key: MapKey,
     ) -> Result<Option<EncryptedMapValue>, String> {
         self.key_manager.ensure_user_can_read(caller, key_id)?;
-        Ok(self.mapkey_vals.get(&(key_id, key)))
+        match self.mapkey_vals.get(&(key_id, key)) {
+            None => Ok(None),
+            Some(StoredValue::Valid(value)) => Ok(Some(value)),
+            Some(StoredValue::Corrupt(_)) => Err("corrupt encrypted value".to_string()),
+        }
     }
 
     /// Retrieves the non-empty map names owned by the caller.
@@ -193,8 +288,10 @@ impl<T: AccessControl> EncryptedMaps<T> {
     ) -> Vec<(MapId, Vec<(MapKey, EncryptedMapValue)>)> {
         let mut result = Vec::new();
         for map_id in self.get_accessible_map_ids_iter(caller) {
-            let map_values = self.get_encrypted_values_for_map(caller, map_id).unwrap();
-            result.push((map_id, map_values));
+            // skip maps that are shared with the caller without read access
+            if let Ok(map_values) = self.get_encrypted_values_for_map(caller, map_id) {
+                result.push((map_id, map_values));
+            }
         }
         result
     }
@@ -203,9 +300,11 @@ impl<T: AccessControl> EncryptedMaps<T> {
     pub fn get_all_accessible_encrypted_maps(&self, caller: Principal) -> Vec<EncryptedMapData<T>> {
         let mut result = Vec::new();
         for map_id in self.get_accessible_map_ids_iter(caller) {
-            let keyvals = self
-                .get_encrypted_values_for_map(caller, map_id)
-                .unwrap()
+            // skip maps that are shared with the caller without read access
+            let Ok(map_values) = self.get_encrypted_values_for_map(caller, map_id) else {
+                continue;
+            };
+            let keyvals = map_values
                 .into_iter()
                 .map(|(key, value)| (ByteBuf::from(key.as_ref().to_vec()), value))
                 .collect();
@@ -248,7 +347,8 @@ impl<T: AccessControl> EncryptedMaps<T> {
     }
 
     /// Inserts or updates an encrypted value in a map.
-    /// The caller must have write permissions to modify the map.
+    /// The caller must have write permissions to modify the map, or append
+    /// permissions if the key is not yet present in the map.
     pub fn insert_encrypted_value(
         &mut self,
         caller: Principal,
@@ -256,20 +356,30 @@ impl<T: AccessControl> EncryptedMaps<T> {
         key: MapKey,
         encrypted_value: EncryptedMapValue,
     ) -> Result<Option<EncryptedMapValue>, String> {
-        self.key_manager.ensure_user_can_write(caller, key_id)?;
-        Ok(self.mapkey_vals.insert((key_id, key), encrypted_value))
+        if self.mapkey_vals.contains_key(&(key_id, key)) {
+            self.key_manager.ensure_user_can_write(caller, key_id)?;
+        } else {
+            self.key_manager.ensure_user_can_append(caller, key_id)?;
+        }
+        Ok(self
+            .mapkey_vals
+            .insert((key_id, key), encrypted_value.into())
+            .and_then(StoredValue::into_valid))
     }
 
     /// Removes an encrypted value from a map.
-    /// The caller must have write permissions to modify the map.
+    /// The caller must have delete permissions to modify the map.
     pub fn remove_encrypted_value(
         &mut self,
         caller: Principal,
         key_id: KeyId,
         key: MapKey,
     ) -> Result<Option<EncryptedMapValue>, String> {
-        self.key_manager.ensure_user_can_write(caller, key_id)?;
-        Ok(self.mapkey_vals.remove(&(key_id, key)))
+        self.key_manager.ensure_user_can_delete(caller, key_id)?;
+        Ok(self
+            .mapkey_vals
+            .remove(&(key_id, key))
+            .and_then(StoredValue::into_valid))
     }
 
     /// Retrieves the public verification key from KeyManager.
@@ -292,7 +402,8 @@ impl<T: AccessControl> EncryptedMaps<T> {
             .get_encrypted_vetkey(caller, key_id, transport_key)
     }
 
-    /// Retrieves access rights for a user to a map.
+    /// Retrieves access rights for a user to a map, falling back to public
+    /// access for users without access rights of their own.
     /// The caller must have appropriate permissions to view this information.
     pub fn get_user_rights(
         &self,
@@ -316,8 +427,23 @@ impl<T: AccessControl> EncryptedMaps<T> {
             .set_user_rights(caller, key_id, user, access_rights)
     }
 
+    /// Grants the given access rights to a map to every caller, or revokes
+    /// public access if `access_rights` is `None`.
+    /// Only the map owner can perform this action.
+    pub fn set_public_access(
+        &mut self,
+        caller: Principal,
+        key_id: KeyId,
+        access_rights: Option<T>,
+    ) -> Result<Option<T>, String> {
+        self.key_manager
+            .set_public_access(caller, key_id, access_rights)
+    }
+
     /// Removes access rights for a user from a map.
     /// Only the map owner or a user with management rights can perform this action.
+    /// Public access cannot be revoked this way, see
+    /// [`EncryptedMaps::set_public_access`] instead.
     pub fn remove_user(
         &mut self,
         caller: Principal,
@@ -326,6 +452,41 @@ impl<T: AccessControl> EncryptedMaps<T> {
     ) -> Result<Option<T>, String> {
         self.key_manager.remove_user(caller, key_id, user)
     }
+
+    /// Checks the stable memory for encrypted values that cannot be decoded,
+    /// in addition to the entries reported by
+    /// [`crate::key_manager::KeyManager::find_corrupt_entries`].
+    /// This reads every entry, so it should not be called in every message.
+    pub fn find_corrupt_entries(&self) -> Vec<CorruptEntry> {
+        let mut corrupt_entries = self.key_manager.find_corrupt_entries();
+        corrupt_entries.extend(
+            self.mapkey_vals
+                .iter()
+                .filter(|(_, value)| value.is_corrupt())
+                .map(|((map_id, map_key), _)| CorruptEntry::EncryptedMapValue { map_id, map_key }),
+        );
+        corrupt_entries
+    }
+
+    /// Repairs the entries reported by [`EncryptedMaps::find_corrupt_entries`]
+    /// and returns them.
+    /// Encrypted values that cannot be decoded are removed, see
+    /// [`crate::key_manager::KeyManager::remove_corrupt_entries`] for the
+    /// repair of the access rights.
+    pub fn remove_corrupt_entries(&mut self) -> Vec<CorruptEntry> {
+        let mut corrupt_entries = self.key_manager.remove_corrupt_entries();
+        let corrupt_values: Vec<_> = self
+            .mapkey_vals
+            .iter()
+            .filter(|(_, value)| value.is_corrupt())
+            .map(|(map_id_and_key, _)| map_id_and_key)
+            .collect();
+        for (map_id, map_key) in corrupt_values {
+            self.mapkey_vals.remove(&(map_id, map_key));
+            corrupt_entries.push(CorruptEntry::EncryptedMapValue { map_id, map_key });
+        }
+        corrupt_entries
+    }
 }
 
 /// Represents the complete data for an encrypted map, including ownership, contents, and access control.
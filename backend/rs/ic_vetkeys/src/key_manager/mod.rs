@@ -1,6 +1,8 @@
 //! See [`KeyManager`] for the main documentation.
 
-use crate::types::{AccessControl, ByteBuf, KeyName, TransportKey};
+use crate::types::{
+    AccessControl, AccessGrant, ByteBuf, CorruptEntry, KeyName, StoredValue, TransportKey,
+};
 use candid::Principal;
 use ic_cdk::api::management_canister::main::CanisterId;
 use ic_stable_structures::memory_manager::VirtualMemory;
@@ -9,9 +11,9 @@ use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storab
 use std::future::Future;
 use std::str::FromStr;
 
+use crate::vetkd_api::{ManagementCanisterVetKdApi, VetKdApi};
 use crate::vetkd_api_types::{
-    VetKDCurve, VetKDDeriveKeyReply, VetKDDeriveKeyRequest, VetKDKeyId, VetKDPublicKeyReply,
-    VetKDPublicKeyRequest,
+    VetKDCurve, VetKDDeriveKeyRequest, VetKDKeyId, VetKDPublicKeyRequest,
 };
 
 const VETKD_SYSTEM_API_CANISTER_ID: &str = "aaaaa-aa";
@@ -25,7 +27,7 @@ pub type KeyId = (Owner, KeyName);
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 
 /// The **KeyManager** backend is a support library for **vetKeys**.
-/// 
+///
 /// **vetKeys** is a feature of the Internet Computer (ICP) that enables the derivation of **encrypted cryptographic keys**. This library simplifies the process of key retrieval, encryption, and controlled sharing, ensuring secure and efficient key management for canisters and users.
 ///
 /// For an introduction to **vetKeys**, refer to the [vetKeys Overview](https://internetcomputer.org/docs/building-apps/network-features/encryption/vetkeys).
@@ -33,7 +35,7 @@ type Memory = VirtualMemory<DefaultMemoryImpl>;
 /// IMPORTANT:
 /// These support libraries are under active development and are subject to change. Access to the repositories has been opened to allow for early feedback. Check back regularly for updates.
 /// Please share your feedback on the [developer forum](https://forum.dfinity.org/t/threshold-key-derivation-privacy-on-the-ic/16560/179).
-/// 
+///
 /// ## Core Features
 ///
 /// The **KeyManager** support library provides the following core functionalities:
@@ -47,9 +49,14 @@ type Memory = VirtualMemory<DefaultMemoryImpl>;
 ///
 /// The **KeyManager** consists of two primary components:
 ///
-/// 1. **Access Control Map** (`access_control`): Maps `(Caller, KeyId)` to `T`, defining permissions for each user.
+/// 1. **Access Control Map** (`access_control`): Maps `(Caller, KeyId)` to [`AccessGrant<T>`], defining permissions for each user and who granted them.
 /// 2. **Shared Keys Map** (`shared_keys`): Tracks which users have access to shared vetKeys.
 ///
+/// Access grants that cannot be decoded from stable memory grant no access and are skipped when listing keys and users.
+/// [`KeyManager::find_corrupt_entries`] reports them and [`KeyManager::remove_corrupt_entries`] removes them.
+///
+/// vetKeys are obtained through a [`VetKdApi`], which defaults to the vetKD methods of the management canister. Use [`KeyManager::init_with_vetkd_api`] to test the async methods natively with an in-memory implementation.
+///
 /// ## Example Use Case
 ///
 /// 1. **User A** requests a vetKey from KeyManager.
@@ -63,13 +70,14 @@ type Memory = VirtualMemory<DefaultMemoryImpl>;
 /// - Only authorized users can access shared vetKeys.
 /// - Stable storage ensures vetKeys persist across canister upgrades.
 /// - Access control logic ensures only authorized users retrieve vetKeys or modify access rights.
-/// 
+///
 /// ## Summary
 /// [`KeyManager`] simplifies the usage of **vetKeys** on the ICP, providing a secure and efficient mechanism for **cryptographic key derivation, sharing, and management**.
-pub struct KeyManager<T: AccessControl> {
+pub struct KeyManager<T: AccessControl, V: VetKdApi = ManagementCanisterVetKdApi> {
     pub domain_separator: StableCell<String, Memory>,
-    pub access_control: StableBTreeMap<(Principal, KeyId), T, Memory>,
+    pub access_control: StableBTreeMap<(Principal, KeyId), StoredValue<AccessGrant<T>>, Memory>,
     pub shared_keys: StableBTreeMap<(KeyId, Principal), (), Memory>,
+    pub vetkd_api: V,
 }
 
 impl<T: AccessControl> KeyManager<T> {
@@ -93,7 +101,7 @@ impl<T: AccessControl> KeyManager<T> {
     ///         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
     ///     static KEY_MANAGER: RefCell<KeyManager<AccessRights>> = RefCell::new(KeyManager::init("my key manager dapp", id_to_memory(0), id_to_memory(1), id_to_memory(2)));
     /// }
-    /// 
+    ///
     /// fn id_to_memory(id: u8) -> Memory {
     ///     MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(id)))
     /// }
@@ -103,6 +111,25 @@ impl<T: AccessControl> KeyManager<T> {
         memory_domain_separator: Memory,
         memory_access_control: Memory,
         memory_shared_keys: Memory,
+    ) -> Self {
+        Self::init_with_vetkd_api(
+            domain_separator,
+            memory_domain_separator,
+            memory_access_control,
+            memory_shared_keys,
+            ManagementCanisterVetKdApi::default(),
+        )
+    }
+}
+
+impl<T: AccessControl, V: VetKdApi> KeyManager<T, V> {
+    /// Initializes the KeyManager with stable storage, obtaining vetKeys through `vetkd_api`.
+    pub fn init_with_vetkd_api(
+        domain_separator: &str,
+        memory_domain_separator: Memory,
+        memory_access_control: Memory,
+        memory_shared_keys: Memory,
+        vetkd_api: V,
     ) -> Self {
         let domain_separator =
             StableCell::init(memory_domain_separator, domain_separator.to_string())
@@ -111,6 +138,7 @@ impl<T: AccessControl> KeyManager<T> {
             domain_separator,
             access_control: StableBTreeMap::init(memory_access_control),
             shared_keys: StableBTreeMap::init(memory_shared_keys),
+            vetkd_api,
         }
     }
 
@@ -120,11 +148,13 @@ impl<T: AccessControl> KeyManager<T> {
         self.access_control
             .range((caller, (Principal::management_canister(), Blob::default()))..)
             .take_while(|((p, _), _)| p == &caller)
+            .filter(|(_, grant)| !grant.is_corrupt())
             .map(|((_, key_id), _)| key_id)
             .collect()
     }
 
     /// Retrieves a list of users with whom a given vetKey has been shared, along with their access rights.
+    /// Users whose access rights cannot be decoded are skipped.
     /// The caller must have appropriate permissions to view this information.
     pub fn get_shared_user_access_for_key(
         &self,
@@ -140,15 +170,15 @@ impl<T: AccessControl> KeyManager<T> {
             .map(|((_, user), _)| user)
             .collect();
 
-        users
+        Ok(users
             .into_iter()
-            .map(|user| {
-                self.get_user_rights(caller, key_id, user)
-                    .map(|opt_user_rights| {
-                        (user, opt_user_rights.expect("always some access rights"))
-                    })
+            .filter_map(|user| {
+                self.access_control
+                    .get(&(user, key_id))
+                    .and_then(StoredValue::into_valid)
+                    .map(|grant| (user, grant.access_rights))
             })
-            .collect::<Result<Vec<_>, _>>()
+            .collect())
     }
 
     /// Retrieves the vetKD verification key for this canister.
@@ -164,14 +194,8 @@ impl<T: AccessControl> KeyManager<T> {
             key_id: bls12_381_dfx_test_key(),
         };
 
-        let future = ic_cdk::api::call::call::<_, (VetKDPublicKeyReply,)>(
-            vetkd_system_api_canister_id(),
-            "vetkd_public_key",
-            (request,),
-        );
-
-        future.map(|call_result| {
-            let (reply,) = call_result.expect("call to vetkd_public_key failed");
+        self.vetkd_api.vetkd_public_key(request).map(|call_result| {
+            let reply = call_result.expect("call to vetkd_public_key failed");
             VetKeyVerificationKey::from(reply.public_key)
         })
     }
@@ -187,7 +211,7 @@ impl<T: AccessControl> KeyManager<T> {
     ) -> Result<impl Future<Output = VetKey> + Send + Sync, String> {
         use futures::future::FutureExt;
 
-        self.ensure_user_can_read(caller, key_id)?;
+        self.ensure_user_can_derive_key(caller, key_id)?;
 
         let request = VetKDDeriveKeyRequest {
             input: key_id_to_vetkd_input(key_id.0, key_id.1.as_ref()),
@@ -196,21 +220,17 @@ impl<T: AccessControl> KeyManager<T> {
             transport_public_key: transport_key.into(),
         };
 
-        let future = ic_cdk::api::call::call_with_payment128::<_, (VetKDDeriveKeyReply,)>(
-            vetkd_system_api_canister_id(),
-            "vetkd_derive_key",
-            (request,),
-            26_153_846_153,
-        );
-
-        Ok(future.map(|call_result| {
-            let (reply,) = call_result.expect("call to vetkd_derive_key failed");
+        Ok(self.vetkd_api.vetkd_derive_key(request).map(|call_result| {
+            let reply = call_result.expect("call to vetkd_derive_key failed");
             VetKey::from(reply.encrypted_key)
         }))
     }
 
     /// Retrieves the access rights a given user has to a specific vetKey.
+    /// Users without access rights of their own get the access rights granted
+    /// via [`KeyManager::set_public_access`], if any.
     /// The caller must have appropriate permissions to view this information.
+    /// Returns an error if the user's access rights cannot be decoded.
     pub fn get_user_rights(
         &self,
         caller: Principal,
@@ -218,12 +238,30 @@ impl<T: AccessControl> KeyManager<T> {
         user: Principal,
     ) -> Result<Option<T>, String> {
         self.ensure_user_can_get_user_rights(caller, key_id)?;
-        Ok(self.ensure_user_can_read(user, key_id).ok())
+        if user == key_id.0 {
+            return Ok(Some(T::owner_rights()));
+        }
+        let grant = self
+            .access_control
+            .get(&(user, key_id))
+            .or_else(|| self.access_control.get(&(Principal::anonymous(), key_id)));
+        match grant {
+            None => Ok(None),
+            Some(StoredValue::Valid(grant)) => Ok(Some(grant.access_rights)),
+            Some(StoredValue::Corrupt(_)) => Err("corrupt access rights".to_string()),
+        }
     }
 
     /// Grants or modifies access rights for a user to a given vetKey.
     /// Only the vetKey owner or a user with management rights can perform this action.
-    /// The vetKey owner cannot change their own rights.
+    /// The vetKey owner's rights cannot be changed, and users cannot change
+    /// their own rights.
+    /// Sharing with the anonymous principal is rejected, see
+    /// [`KeyManager::set_public_access`] instead.
+    /// The caller can only grant access rights that they hold themselves (see
+    /// [`AccessControl::can_grant`]). Modifying existing access rights
+    /// additionally requires the permission to remove users and is subject to
+    /// the same restrictions as [`KeyManager::remove_user`].
     pub fn set_user_rights(
         &mut self,
         caller: Principal,
@@ -231,62 +269,188 @@ impl<T: AccessControl> KeyManager<T> {
         user: Principal,
         access_rights: T,
     ) -> Result<Option<T>, String> {
-        self.ensure_user_can_set_user_rights(caller, key_id)?;
+        let caller_rights = self.ensure_user_can_set_user_rights(caller, key_id)?;
 
-        if caller == key_id.0 && caller == user {
+        if user == key_id.0 {
             return Err("cannot change key owner's user rights".to_string());
         }
+
+        if user == caller {
+            return Err("cannot change own user rights".to_string());
+        }
+
+        if user == Principal::anonymous() {
+            return Err("cannot share with the anonymous principal".to_string());
+        }
+
+        if !caller_rights.can_grant(&access_rights) {
+            return Err("cannot grant access rights exceeding own rights".to_string());
+        }
+
+        if let Some(existing_grant) = self.access_control.get(&(user, key_id)) {
+            if let StoredValue::Valid(grant) = &existing_grant {
+                if grant.access_rights == access_rights {
+                    // keep track of who originally granted the access rights
+                    return Ok(Some(access_rights));
+                }
+            }
+            if !caller_rights.can_remove_user() {
+                return Err("unauthorized".to_string());
+            }
+            self.ensure_user_can_be_modified_by(caller, &caller_rights, key_id, &existing_grant)?;
+        }
+
         self.shared_keys.insert((key_id, user), ());
-        Ok(self.access_control.insert((user, key_id), access_rights))
+        let grant = AccessGrant {
+            access_rights,
+            granted_by: caller,
+        };
+        Ok(self
+            .access_control
+            .insert((user, key_id), grant.into())
+            .and_then(StoredValue::into_valid)
+            .map(|grant| grant.access_rights))
+    }
+
+    /// Grants the given access rights to a vetKey to every caller,
+    /// including unauthenticated ones, or revokes public access if
+    /// `access_rights` is `None`.
+    /// Only the vetKey owner can perform this action, and public access
+    /// cannot include the permission to manage user rights.
+    /// Public access is stored as a grant to the anonymous principal and
+    /// listed as such by [`KeyManager::get_shared_user_access_for_key`].
+    pub fn set_public_access(
+        &mut self,
+        caller: Principal,
+        key_id: KeyId,
+        access_rights: Option<T>,
+    ) -> Result<Option<T>, String> {
+        if caller != key_id.0 {
+            return Err("unauthorized".to_string());
+        }
+
+        let user = Principal::anonymous();
+        let previous_grant = match access_rights {
+            Some(access_rights) => {
+                if access_rights.can_set_user_rights() || access_rights.can_remove_user() {
+                    return Err("public access cannot include management rights".to_string());
+                }
+                self.shared_keys.insert((key_id, user), ());
+                let grant = AccessGrant {
+                    access_rights,
+                    granted_by: caller,
+                };
+                self.access_control.insert((user, key_id), grant.into())
+            }
+            None => {
+                self.shared_keys.remove(&(key_id, user));
+                self.access_control.remove(&(user, key_id))
+            }
+        };
+        Ok(previous_grant
+            .and_then(StoredValue::into_valid)
+            .map(|grant| grant.access_rights))
     }
 
     /// Revokes a user's access to a shared vetKey.
-    /// The vetKey owner cannot remove their own access.
-    /// Only the vetKey owner or a user with management rights can perform this action.
+    /// The vetKey owner's access cannot be removed.
+    /// Only the vetKey owner or a user with the permission to remove users can perform this action.
+    /// Users other than the vetKey owner can only remove themselves, users
+    /// that they granted access to, or users with strictly lower access rights.
+    /// Public access cannot be revoked this way, see
+    /// [`KeyManager::set_public_access`] instead.
     pub fn remove_user(
         &mut self,
         caller: Principal,
         key_id: KeyId,
         user: Principal,
     ) -> Result<Option<T>, String> {
-        self.ensure_user_can_set_user_rights(caller, key_id)?;
+        let caller_rights = self.ensure_user_can_remove_user(caller, key_id)?;
 
-        if caller == user && caller == key_id.0 {
+        if user == key_id.0 {
             return Err("cannot remove key owner".to_string());
         }
 
+        if user == Principal::anonymous() {
+            return Err("cannot remove public access, use set_public_access".to_string());
+        }
+
+        if caller != user {
+            if let Some(existing_grant) = self.access_control.get(&(user, key_id)) {
+                self.ensure_user_can_be_modified_by(
+                    caller,
+                    &caller_rights,
+                    key_id,
+                    &existing_grant,
+                )?;
+            }
+        }
+
         self.shared_keys.remove(&(key_id, user));
-        Ok(self.access_control.remove(&(user, key_id)))
+        Ok(self
+            .access_control
+            .remove(&(user, key_id))
+            .and_then(StoredValue::into_valid)
+            .map(|grant| grant.access_rights))
+    }
+
+    /// Ensures that the caller may modify or revoke an existing access grant.
+    /// The vetKey owner may modify any grant, including corrupt ones, other
+    /// users only grants they created themselves or grants of strictly lower
+    /// access rights than their own.
+    fn ensure_user_can_be_modified_by(
+        &self,
+        caller: Principal,
+        caller_rights: &T,
+        key_id: KeyId,
+        existing_grant: &StoredValue<AccessGrant<T>>,
+    ) -> Result<(), String> {
+        let is_owner = caller == key_id.0;
+        let existing_grant = match existing_grant {
+            StoredValue::Valid(grant) => grant,
+            StoredValue::Corrupt(_) if is_owner => return Ok(()),
+            StoredValue::Corrupt(_) => return Err("corrupt access rights".to_string()),
+        };
+        let is_granter = caller == existing_grant.granted_by;
+        let has_strictly_higher_rights = caller_rights.can_grant(&existing_grant.access_rights)
+            && !existing_grant.access_rights.can_grant(caller_rights);
+
+        if is_owner || is_granter || has_strictly_higher_rights {
+            Ok(())
+        } else {
+            Err("unauthorized".to_string())
+        }
     }
 
     /// Ensures that a user has read access to a vetKey before proceeding.
+    /// Access rights granted via [`KeyManager::set_public_access`] apply to every user.
     /// Returns an error if the user is not authorized.
     pub fn ensure_user_can_read(&self, user: Principal, key_id: KeyId) -> Result<T, String> {
-        let is_owner = user == key_id.0;
-        if is_owner {
-            return Ok(T::owner_rights());
-        }
+        self.ensure_user_rights(user, key_id, T::can_read)
+    }
 
-        let has_shared_access = self.access_control.get(&(user, key_id));
-        match has_shared_access {
-            Some(access_rights) if access_rights.can_read() => Ok(access_rights),
-            _ => Err("unauthorized".to_string()),
-        }
+    /// Ensures that a user can retrieve a vetKey before proceeding.
+    /// Returns an error if the user is not authorized.
+    pub fn ensure_user_can_derive_key(&self, user: Principal, key_id: KeyId) -> Result<T, String> {
+        self.ensure_user_rights(user, key_id, T::can_derive_key)
     }
 
     /// Ensures that a user has write access to a vetKey before proceeding.
     /// Returns an error if the user is not authorized.
     pub fn ensure_user_can_write(&self, user: Principal, key_id: KeyId) -> Result<T, String> {
-        let is_owner = user == key_id.0;
-        if is_owner {
-            return Ok(T::owner_rights());
-        }
+        self.ensure_user_rights(user, key_id, T::can_write)
+    }
 
-        let has_shared_access = self.access_control.get(&(user, key_id));
-        match has_shared_access {
-            Some(access_rights) if access_rights.can_write() => Ok(access_rights),
-            _ => Err("unauthorized".to_string()),
-        }
+    /// Ensures that a user has append access to a vetKey before proceeding.
+    /// Returns an error if the user is not authorized.
+    pub fn ensure_user_can_append(&self, user: Principal, key_id: KeyId) -> Result<T, String> {
+        self.ensure_user_rights(user, key_id, T::can_append)
+    }
+
+    /// Ensures that a user has delete access to a vetKey before proceeding.
+    /// Returns an error if the user is not authorized.
+    pub fn ensure_user_can_delete(&self, user: Principal, key_id: KeyId) -> Result<T, String> {
+        self.ensure_user_rights(user, key_id, T::can_delete)
     }
 
     /// Ensures that a user has permission to view user rights for a vetKey.
@@ -296,16 +460,7 @@ impl<T: AccessControl> KeyManager<T> {
         user: Principal,
         key_id: KeyId,
     ) -> Result<T, String> {
-        let is_owner = user == key_id.0;
-        if is_owner {
-            return Ok(T::owner_rights());
-        }
-
-        let has_shared_access = self.access_control.get(&(user, key_id));
-        match has_shared_access {
-            Some(access_rights) if access_rights.can_get_user_rights() => Ok(access_rights),
-            _ => Err("unauthorized".to_string()),
-        }
+        self.ensure_user_rights(user, key_id, T::can_get_user_rights)
     }
 
     /// Ensures that a user has management access to a vetKey before proceeding.
@@ -314,28 +469,103 @@ impl<T: AccessControl> KeyManager<T> {
         &self,
         user: Principal,
         key_id: KeyId,
+    ) -> Result<T, String> {
+        self.ensure_user_rights(user, key_id, T::can_set_user_rights)
+    }
+
+    /// Ensures that a user has permission to revoke or modify existing user
+    /// rights for a vetKey before proceeding.
+    /// Returns an error if the user is not authorized.
+    pub fn ensure_user_can_remove_user(&self, user: Principal, key_id: KeyId) -> Result<T, String> {
+        self.ensure_user_rights(user, key_id, T::can_remove_user)
+    }
+
+    fn ensure_user_rights(
+        &self,
+        user: Principal,
+        key_id: KeyId,
+        is_permitted: impl Fn(&T) -> bool,
     ) -> Result<T, String> {
         let is_owner = user == key_id.0;
         if is_owner {
             return Ok(T::owner_rights());
         }
 
-        let has_shared_access = self.access_control.get(&(user, key_id));
-        match has_shared_access {
-            Some(access_rights) if access_rights.can_set_user_rights() => Ok(access_rights),
-            _ => Err("unauthorized".to_string()),
+        // corrupt access grants do not grant any access
+        let is_permitted_grant = |grant: &AccessGrant<T>| is_permitted(&grant.access_rights);
+        let has_shared_access = self
+            .access_control
+            .get(&(user, key_id))
+            .and_then(StoredValue::into_valid)
+            .filter(is_permitted_grant);
+        let has_public_access = || {
+            self.access_control
+                .get(&(Principal::anonymous(), key_id))
+                .and_then(StoredValue::into_valid)
+                .filter(is_permitted_grant)
+        };
+        match has_shared_access.or_else(has_public_access) {
+            Some(grant) => Ok(grant.access_rights),
+            None => Err("unauthorized".to_string()),
+        }
+    }
+
+    /// Checks the stable memory for access grants that cannot be decoded and
+    /// for inconsistencies between `access_control` and `shared_keys`.
+    /// This reads every entry, so it should not be called in every message.
+    pub fn find_corrupt_entries(&self) -> Vec<CorruptEntry> {
+        let mut corrupt_entries = Vec::new();
+        for ((user, key_id), grant) in self.access_control.iter() {
+            if grant.is_corrupt() {
+                corrupt_entries.push(CorruptEntry::AccessGrant { user, key_id });
+            }
+            if !self.shared_keys.contains_key(&(key_id, user)) {
+                corrupt_entries.push(CorruptEntry::AccessGrantWithoutSharedKey { user, key_id });
+            }
+        }
+        for (key_id, user) in self.shared_keys.keys() {
+            if !self.access_control.contains_key(&(user, key_id)) {
+                corrupt_entries.push(CorruptEntry::SharedKeyWithoutAccessGrant { key_id, user });
+            }
+        }
+        corrupt_entries
+    }
+
+    /// Repairs the entries reported by [`KeyManager::find_corrupt_entries`]
+    /// and returns them.
+    /// Access grants that cannot be decoded are revoked, and `shared_keys` is
+    /// updated to list exactly the users with access grants.
+    pub fn remove_corrupt_entries(&mut self) -> Vec<CorruptEntry> {
+        let corrupt_entries = self.find_corrupt_entries();
+        for entry in corrupt_entries.iter() {
+            match *entry {
+                CorruptEntry::AccessGrant { user, key_id } => {
+                    self.access_control.remove(&(user, key_id));
+                    self.shared_keys.remove(&(key_id, user));
+                }
+                CorruptEntry::AccessGrantWithoutSharedKey { user, key_id } => {
+                    if self.access_control.contains_key(&(user, key_id)) {
+                        self.shared_keys.insert((key_id, user), ());
+                    }
+                }
+                CorruptEntry::SharedKeyWithoutAccessGrant { key_id, user } => {
+                    self.shared_keys.remove(&(key_id, user));
+                }
+                CorruptEntry::EncryptedMapValue { .. } => {}
+            }
         }
+        corrupt_entries
     }
 }
 
-fn bls12_381_dfx_test_key() -> VetKDKeyId {
+pub(crate) fn bls12_381_dfx_test_key() -> VetKDKeyId {
     VetKDKeyId {
         curve: VetKDCurve::Bls12_381_G2,
         name: "dfx_test_key".to_string(),
     }
 }
 
-fn vetkd_system_api_canister_id() -> CanisterId {
+pub(crate) fn vetkd_system_api_canister_id() -> CanisterId {
     CanisterId::from_str(VETKD_SYSTEM_API_CANISTER_ID).expect("failed to create canister ID")
 }
 
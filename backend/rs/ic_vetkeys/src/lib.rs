@@ -4,7 +4,11 @@
 
 pub mod encrypted_maps;
 pub mod key_manager;
+pub mod random_beacon;
+pub mod threshold_bls;
+pub mod timelock;
 pub mod types;
+pub mod vetkd_api;
 pub mod vetkd_api_types;
 
 mod utils;
@@ -0,0 +1,240 @@
+//! See [`RandomBeacon`] for the main documentation.
+
+use crate::key_manager::bls12_381_dfx_test_key;
+use crate::types::{ByteBuf, StoredValue};
+use crate::vetkd_api::{derive_public_vetkey, ManagementCanisterVetKdApi, VetKdApi};
+use crate::vetkd_api_types::VetKDPublicKeyRequest;
+use crate::{DerivedPublicKey, VetKey, VrfOutput};
+use candid::CandidType;
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+
+pub type VetKeyVerificationKey = ByteBuf;
+pub type Round = u64;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+/*
+ * Domain separator prefixed to the vetKD input of beacon rounds
+ */
+const RANDOM_BEACON_INPUT_PREFIX: &[u8] = b"ic-vetkeys-random-beacon";
+
+/// The beacon of a round, which anyone can verify against the verification
+/// key using [`VrfOutput::verify`] with the input [`random_beacon_input`].
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Beacon {
+    pub round: Round,
+    pub proof: ByteBuf,
+    pub randomness: ByteBuf,
+}
+
+/// Returns the vetKD input of the beacon for `round`, i.e. a fixed prefix
+/// followed by the round as a 64-bit big endian integer.
+pub fn random_beacon_input(round: Round) -> Vec<u8> {
+    let mut input = Vec::with_capacity(RANDOM_BEACON_INPUT_PREFIX.len() + 8);
+    input.extend_from_slice(RANDOM_BEACON_INPUT_PREFIX);
+    input.extend_from_slice(&round.to_be_bytes());
+    input
+}
+
+/// The **RandomBeacon** backend is a support library for publicly verifiable randomness with **vetKeys**.
+///
+/// Since a vetKey for a fixed input is a unique BLS signature, hashing it
+/// gives randomness that nobody, including the canister, can predict before
+/// the vetKey is derived, and that anyone can verify (see [`VrfOutput`]). The
+/// **RandomBeacon** produces one such value per round, e.g. to draw lottery
+/// winners in a way that participants can check.
+///
+/// ## RandomBeacon Architecture
+///
+/// The **RandomBeacon** consists of two components:
+///
+/// 1. **Domain Separator** (`domain_separator`): The vetKD context of all beacons.
+/// 2. **Beacons Map** (`beacons`): Maps each `Round` to the proof of its beacon.
+///
+/// Round `r` starts at time `r * period` (in nanoseconds since the UNIX
+/// epoch), and its beacon can only be derived once the round has started.
+///
+/// Beacons are obtained through a [`VetKdApi`], which defaults to the vetKD
+/// methods of the management canister, see
+/// [`RandomBeacon::init_with_vetkd_api`].
+///
+/// ## Security Considerations
+///
+/// - A beacon is unpredictable until it is derived, but the canister learns it
+///   before publishing it. The canister can thus not bias the beacon, but it
+///   could refuse to publish it. Commit to the use of a round's beacon (e.g.
+///   close the lottery) before the round starts.
+/// - Beacons are fetched using a transport key generated from `raw_rand`, so
+///   users cannot learn a beacon before it is published. The subnet's nodes,
+///   however, can see the transport key and thus the beacon as soon as it is
+///   derived.
+/// - Beacons are verified against the verification key and the input of
+///   their round before being published, since a published beacon cannot be
+///   replaced.
+pub struct RandomBeacon<V: VetKdApi = ManagementCanisterVetKdApi> {
+    pub domain_separator: StableCell<String, Memory>,
+    pub period: u64,
+    pub beacons: StableBTreeMap<Round, StoredValue<ByteBuf>, Memory>,
+    pub vetkd_api: V,
+}
+
+impl RandomBeacon {
+    /// Initializes the RandomBeacon with stable storage.
+    ///
+    /// Panics if `period` is zero.
+    pub fn init(
+        domain_separator: &str,
+        period: u64,
+        memory_domain_separator: Memory,
+        memory_beacons: Memory,
+    ) -> Self {
+        Self::init_with_vetkd_api(
+            domain_separator,
+            period,
+            memory_domain_separator,
+            memory_beacons,
+            ManagementCanisterVetKdApi::default(),
+        )
+    }
+}
+
+impl<V: VetKdApi> RandomBeacon<V> {
+    /// Initializes the RandomBeacon with stable storage, obtaining beacons through `vetkd_api`.
+    ///
+    /// Panics if `period` is zero.
+    pub fn init_with_vetkd_api(
+        domain_separator: &str,
+        period: u64,
+        memory_domain_separator: Memory,
+        memory_beacons: Memory,
+        vetkd_api: V,
+    ) -> Self {
+        assert!(period > 0, "random beacon period must be positive");
+        let domain_separator =
+            StableCell::init(memory_domain_separator, domain_separator.to_string())
+                .expect("failed to initialize domain separator");
+        RandomBeacon {
+            domain_separator,
+            period,
+            beacons: StableBTreeMap::init(memory_beacons),
+            vetkd_api,
+        }
+    }
+
+    /// Returns the latest round that has started at time `now`.
+    pub fn current_round(&self, now: u64) -> Round {
+        now / self.period
+    }
+
+    /// Retrieves the public key to verify beacons against, i.e. the derived
+    /// public key of the domain separator.
+    pub fn get_verification_key(
+        &self,
+    ) -> impl Future<Output = VetKeyVerificationKey> + Send + Sync {
+        use futures::future::FutureExt;
+
+        let request = VetKDPublicKeyRequest {
+            canister_id: None,
+            context: self.domain_separator.get().to_bytes().to_vec(),
+            key_id: bls12_381_dfx_test_key(),
+        };
+
+        self.vetkd_api.vetkd_public_key(request).map(|call_result| {
+            let reply = call_result.expect("call to vetkd_public_key failed");
+            VetKeyVerificationKey::from(reply.public_key)
+        })
+    }
+
+    /// Retrieves the published beacon of `round`, or None if it has not been
+    /// published yet.
+    ///
+    /// Returns an error if the stored beacon cannot be decoded.
+    pub fn get_beacon(&self, round: Round) -> Result<Option<Beacon>, String> {
+        let proof = match self.beacons.get(&round) {
+            None => return Ok(None),
+            Some(StoredValue::Valid(proof)) => proof,
+            Some(StoredValue::Corrupt(_)) => return Err("corrupt beacon".to_string()),
+        };
+        let vetkey =
+            VetKey::deserialize(proof.as_ref()).map_err(|_| "corrupt beacon".to_string())?;
+        let output = VrfOutput::from_vetkey(vetkey);
+        Ok(Some(Beacon {
+            round,
+            proof,
+            randomness: ByteBuf::from(output.randomness().to_vec()),
+        }))
+    }
+
+    /// Derives the beacon of `round`.
+    ///
+    /// Returns an error if the round has not started at time `now`. The
+    /// returned future fetches and verifies the beacon but does not publish
+    /// it, which is left to [`RandomBeacon::publish_beacon`] so that the
+    /// RandomBeacon does not need to be borrowed across the call.
+    pub fn derive_beacon(
+        &self,
+        round: Round,
+        now: u64,
+    ) -> Result<impl Future<Output = Result<VrfOutput, String>> + Send + Sync, String>
+    where
+        V: Clone + Send + Sync,
+    {
+        use futures::future::FutureExt;
+
+        self.ensure_round_started(round, now)?;
+
+        let context = self.domain_separator.get().to_bytes().to_vec();
+        Ok(
+            derive_public_vetkey(&self.vetkd_api, context, random_beacon_input(round))
+                .map(|vetkey| vetkey.map(VrfOutput::from_vetkey)),
+        )
+    }
+
+    /// Publishes the beacon of `round`.
+    ///
+    /// The beacon is expected to be obtained from
+    /// [`RandomBeacon::derive_beacon`]. Returns an error if the round has not
+    /// started at time `now`, if a beacon was already published for the
+    /// round, or if `output` is not the beacon of `round` under
+    /// `verification_key`, i.e. the key returned by
+    /// [`RandomBeacon::get_verification_key`].
+    pub fn publish_beacon(
+        &mut self,
+        round: Round,
+        now: u64,
+        output: &VrfOutput,
+        verification_key: &DerivedPublicKey,
+    ) -> Result<Beacon, String> {
+        self.ensure_round_started(round, now)?;
+
+        if self.beacons.contains_key(&round) {
+            return Err("beacon already published".to_string());
+        }
+
+        VrfOutput::verify(
+            verification_key,
+            &random_beacon_input(round),
+            output.proof(),
+        )
+        .map_err(|_| "invalid beacon for this round".to_string())?;
+
+        let proof = ByteBuf::from(output.proof().to_vec());
+        self.beacons
+            .insert(round, StoredValue::Valid(proof.clone()));
+        Ok(Beacon {
+            round,
+            proof,
+            randomness: ByteBuf::from(output.randomness().to_vec()),
+        })
+    }
+
+    fn ensure_round_started(&self, round: Round, now: u64) -> Result<(), String> {
+        match round.checked_mul(self.period) {
+            Some(start) if start <= now => Ok(()),
+            _ => Err("round has not started yet".to_string()),
+        }
+    }
+}
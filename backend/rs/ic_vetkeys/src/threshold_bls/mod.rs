@@ -0,0 +1,103 @@
+//! See [`ThresholdBlsSigner`] for the main documentation.
+
+use crate::key_manager::bls12_381_dfx_test_key;
+use crate::types::ByteBuf;
+use crate::vetkd_api::{
+    derive_public_vetkey_with_zero_knowledge_transport_key, ManagementCanisterVetKdApi, VetKdApi,
+};
+use crate::vetkd_api_types::VetKDPublicKeyRequest;
+use std::future::Future;
+
+pub type VetKeyVerificationKey = ByteBuf;
+pub type BlsSignature = ByteBuf;
+
+/// The **ThresholdBlsSigner** is a support library for using **vetKeys** as a threshold BLS signing service.
+///
+/// A vetKey is an augmented BLS signature over its `input`, created jointly by
+/// the subnet nodes with respect to the derived public key of its `context`.
+/// The **ThresholdBlsSigner** requests the vetKey for a message and returns it
+/// as a signature, which anyone can verify against the derived public key
+/// using [`crate::verify_bls_signature`] or [`crate::BlsSignatureVerifier`].
+///
+/// Signatures are obtained through a [`VetKdApi`], which defaults to the
+/// vetKD methods of the management canister, see
+/// [`ThresholdBlsSigner::new_with_vetkd_api`].
+///
+/// ## Security Considerations
+///
+/// - Signatures are requested with the zero-knowledge transport key, a fixed
+///   and publicly known transport key, see
+///   [`crate::vetkd_api::zero_knowledge_transport_secret_key`]. The encrypted
+///   signature is therefore readable by anyone who sees it, including the
+///   nodes of the subnet, before the canister returns it. This is intended,
+///   since a signature is public, and avoids a call to `raw_rand`.
+/// - Signatures are verified inside the canister before being returned.
+/// - Any message signed by the canister under a domain separator can be used
+///   as a vetKey for that message, e.g. to decrypt IBE ciphertexts encrypted
+///   to it. Do not use the same domain separator for signing and for key
+///   derivation or encryption.
+/// - The signer has no notion of access control. The canister decides which
+///   messages it signs.
+pub struct ThresholdBlsSigner<V: VetKdApi = ManagementCanisterVetKdApi> {
+    domain_separator: String,
+    vetkd_api: V,
+}
+
+impl ThresholdBlsSigner {
+    /// Creates a signer for the given domain separator, which is used as the
+    /// vetKD `context` of all signatures.
+    pub fn new(domain_separator: &str) -> Self {
+        Self::new_with_vetkd_api(domain_separator, ManagementCanisterVetKdApi::default())
+    }
+}
+
+impl<V: VetKdApi> ThresholdBlsSigner<V> {
+    /// Creates a signer for the given domain separator, obtaining signatures
+    /// through `vetkd_api`.
+    pub fn new_with_vetkd_api(domain_separator: &str, vetkd_api: V) -> Self {
+        ThresholdBlsSigner {
+            domain_separator: domain_separator.to_string(),
+            vetkd_api,
+        }
+    }
+
+    /// Retrieves the public key to verify signatures against, i.e. the
+    /// derived public key of the domain separator.
+    pub fn get_verification_key(
+        &self,
+    ) -> impl Future<Output = VetKeyVerificationKey> + Send + Sync {
+        use futures::future::FutureExt;
+
+        let request = VetKDPublicKeyRequest {
+            canister_id: None,
+            context: self.domain_separator.as_bytes().to_vec(),
+            key_id: bls12_381_dfx_test_key(),
+        };
+
+        self.vetkd_api.vetkd_public_key(request).map(|call_result| {
+            let reply = call_result.expect("call to vetkd_public_key failed");
+            VetKeyVerificationKey::from(reply.public_key)
+        })
+    }
+
+    /// Signs `message`, returning a verified BLS signature.
+    ///
+    /// The signature is fetched with the zero-knowledge transport key, see the
+    /// security considerations of [`ThresholdBlsSigner`].
+    pub fn sign(
+        &self,
+        message: &[u8],
+    ) -> impl Future<Output = Result<BlsSignature, String>> + Send + Sync
+    where
+        V: Clone + Send + Sync,
+    {
+        use futures::future::FutureExt;
+
+        derive_public_vetkey_with_zero_knowledge_transport_key(
+            &self.vetkd_api,
+            self.domain_separator.as_bytes().to_vec(),
+            message.to_vec(),
+        )
+        .map(|vetkey| vetkey.map(|vetkey| BlsSignature::from(vetkey.signature_bytes().to_vec())))
+    }
+}
@@ -0,0 +1,228 @@
+//! See [`Timelock`] for the main documentation.
+
+use crate::key_manager::bls12_381_dfx_test_key;
+use crate::types::{ByteBuf, StoredValue};
+use crate::utils::{timelock_round, timelock_round_end};
+use crate::vetkd_api::{derive_public_vetkey, ManagementCanisterVetKdApi, VetKdApi};
+use crate::vetkd_api_types::VetKDPublicKeyRequest;
+use crate::{verify_bls_signature, DerivedPublicKey, IbeIdentity, VetKey};
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, StableCell, Storable};
+use std::future::Future;
+
+pub type VetKeyVerificationKey = ByteBuf;
+pub type DecryptionKey = ByteBuf;
+pub type Granularity = u64;
+pub type Round = u64;
+
+type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+/// The **Timelock** backend is a support library for time-lock encryption with **vetKeys**.
+///
+/// Messages are time-locked by encrypting them to the identity of a point in
+/// time (see [`IbeIdentity::from_timestamp`] and [`crate::TimelockCiphertext`]).
+/// Once that point in time has passed, the canister derives the decryption
+/// key for it and publishes it, so that anyone can decrypt all messages
+/// time-locked to that point in time. This can be used for sealed-bid
+/// auctions, but equally for time-released documents, commit-reveal schemes,
+/// or front-running protection.
+///
+/// ## Timelock Architecture
+///
+/// The **Timelock** consists of two components:
+///
+/// 1. **Domain Separator** (`domain_separator`): The vetKD context of all time-lock keys.
+/// 2. **Published Keys Map** (`published_keys`): Maps `(Granularity, Round)` to the published decryption key.
+///
+/// Time is divided into rounds of a fixed granularity in nanoseconds, and
+/// each unlock time is rounded up to the end of its round. The granularity is
+/// passed on initialization and must match the one used for encryption.
+///
+/// Keys are obtained through a [`VetKdApi`], which defaults to the vetKD
+/// methods of the management canister, see [`Timelock::init_with_vetkd_api`].
+///
+/// ## Security Considerations
+///
+/// - The decryption key of a round is only derived once the round has ended
+///   according to the canister's time.
+/// - Decryption keys are fetched using a transport key generated from
+///   `raw_rand`, so they are not revealed to users before being published.
+///   The subnet's nodes, however, can see the transport key and thus a
+///   decryption key as soon as it is derived.
+/// - Keys are verified against the time-lock public key and the identity of
+///   their round before being published.
+/// - The library does not fetch keys on its own. The canister decides when to
+///   publish a key, e.g. using a timer, see [`Timelock::derive_decryption_key`].
+pub struct Timelock<V: VetKdApi = ManagementCanisterVetKdApi> {
+    pub domain_separator: StableCell<String, Memory>,
+    pub granularity: Granularity,
+    pub published_keys: StableBTreeMap<(Granularity, Round), StoredValue<DecryptionKey>, Memory>,
+    pub vetkd_api: V,
+}
+
+impl Timelock {
+    /// Initializes the Timelock with stable storage.
+    ///
+    /// Panics if `granularity` is zero.
+    pub fn init(
+        domain_separator: &str,
+        granularity: Granularity,
+        memory_domain_separator: Memory,
+        memory_published_keys: Memory,
+    ) -> Self {
+        Self::init_with_vetkd_api(
+            domain_separator,
+            granularity,
+            memory_domain_separator,
+            memory_published_keys,
+            ManagementCanisterVetKdApi::default(),
+        )
+    }
+}
+
+impl<V: VetKdApi> Timelock<V> {
+    /// Initializes the Timelock with stable storage, obtaining keys through `vetkd_api`.
+    ///
+    /// Panics if `granularity` is zero.
+    pub fn init_with_vetkd_api(
+        domain_separator: &str,
+        granularity: Granularity,
+        memory_domain_separator: Memory,
+        memory_published_keys: Memory,
+        vetkd_api: V,
+    ) -> Self {
+        assert!(granularity > 0, "time-lock granularity must be positive");
+        let domain_separator =
+            StableCell::init(memory_domain_separator, domain_separator.to_string())
+                .expect("failed to initialize domain separator");
+        Timelock {
+            domain_separator,
+            granularity,
+            published_keys: StableBTreeMap::init(memory_published_keys),
+            vetkd_api,
+        }
+    }
+
+    /// Returns the time at which messages time-locked to `unlock_time` can
+    /// be decrypted, i.e. `unlock_time` rounded up to the granularity.
+    ///
+    /// Returns None if the rounded up time is not representable.
+    pub fn rounded_unlock_time(&self, unlock_time: u64) -> Option<u64> {
+        timelock_round_end(
+            timelock_round(unlock_time, self.granularity),
+            self.granularity,
+        )
+    }
+
+    /// Retrieves the public key to time-lock messages to, i.e. the derived
+    /// public key of the domain separator.
+    ///
+    /// Returns an error if the call to `vetkd_public_key` fails, so that
+    /// callers that already modified their state, such as a timer closing an
+    /// auction, can recover instead of trapping.
+    pub fn get_timelock_public_key(
+        &self,
+    ) -> impl Future<Output = Result<VetKeyVerificationKey, String>> + Send + Sync {
+        use futures::future::FutureExt;
+
+        let request = VetKDPublicKeyRequest {
+            canister_id: None,
+            context: self.domain_separator.get().to_bytes().to_vec(),
+            key_id: bls12_381_dfx_test_key(),
+        };
+
+        self.vetkd_api.vetkd_public_key(request).map(|call_result| {
+            let reply = call_result.map_err(|e| format!("call to vetkd_public_key failed: {e}"))?;
+            Ok(VetKeyVerificationKey::from(reply.public_key))
+        })
+    }
+
+    /// Retrieves the published decryption key for messages time-locked to
+    /// `unlock_time`, or None if it has not been published yet.
+    ///
+    /// Returns an error if the stored key cannot be decoded. Since keys are
+    /// verified before being published, the stored key is decoded without
+    /// the subgroup check, see [`VetKey::deserialize_unchecked`].
+    pub fn get_decryption_key(&self, unlock_time: u64) -> Result<Option<DecryptionKey>, String> {
+        let round = timelock_round(unlock_time, self.granularity);
+        let key = match self.published_keys.get(&(self.granularity, round)) {
+            None => return Ok(None),
+            Some(StoredValue::Valid(key)) => key,
+            Some(StoredValue::Corrupt(_)) => return Err("corrupt decryption key".to_string()),
+        };
+        VetKey::deserialize_unchecked(key.as_ref())
+            .map_err(|_| "corrupt decryption key".to_string())?;
+        Ok(Some(key))
+    }
+
+    /// Derives the decryption key for messages time-locked to `unlock_time`.
+    ///
+    /// Returns an error if the time lock has not expired at time `now`. The
+    /// returned future fetches and verifies the key but does not publish it,
+    /// which is left to [`Timelock::publish_decryption_key`] so that the
+    /// Timelock does not need to be borrowed across the call:
+    ///
+    /// ```rust,ignore
+    /// let now = ic_cdk::api::time();
+    /// let future = TIMELOCK.with_borrow(|timelock| timelock.derive_decryption_key(unlock_time, now))?;
+    /// let key = future.await?;
+    /// let public_key = TIMELOCK.with_borrow(|timelock| timelock.get_timelock_public_key()).await?;
+    /// let public_key = DerivedPublicKey::deserialize(public_key.as_ref())?;
+    /// TIMELOCK.with_borrow_mut(|timelock| timelock.publish_decryption_key(unlock_time, now, &key, &public_key))?;
+    /// ```
+    pub fn derive_decryption_key(
+        &self,
+        unlock_time: u64,
+        now: u64,
+    ) -> Result<impl Future<Output = Result<VetKey, String>> + Send + Sync, String>
+    where
+        V: Clone + Send + Sync,
+    {
+        self.ensure_time_lock_expired(unlock_time, now)?;
+
+        let context = self.domain_separator.get().to_bytes().to_vec();
+        let identity = IbeIdentity::from_timestamp(unlock_time, self.granularity);
+
+        Ok(derive_public_vetkey(
+            &self.vetkd_api,
+            context,
+            identity.value().to_vec(),
+        ))
+    }
+
+    /// Publishes the decryption key for messages time-locked to `unlock_time`.
+    ///
+    /// The key is expected to be obtained from
+    /// [`Timelock::derive_decryption_key`]. Returns an error if the time lock
+    /// has not expired at time `now`, or if `key` is not the decryption key of
+    /// the round of `unlock_time` under `timelock_public_key`, i.e. the key
+    /// returned by [`Timelock::get_timelock_public_key`].
+    pub fn publish_decryption_key(
+        &mut self,
+        unlock_time: u64,
+        now: u64,
+        key: &VetKey,
+        timelock_public_key: &DerivedPublicKey,
+    ) -> Result<(), String> {
+        self.ensure_time_lock_expired(unlock_time, now)?;
+
+        let identity = IbeIdentity::from_timestamp(unlock_time, self.granularity);
+        if !verify_bls_signature(timelock_public_key, identity.value(), key.signature_bytes()) {
+            return Err("invalid decryption key for this round".to_string());
+        }
+
+        let round = timelock_round(unlock_time, self.granularity);
+        self.published_keys.insert(
+            (self.granularity, round),
+            StoredValue::Valid(DecryptionKey::from(key.signature_bytes().to_vec())),
+        );
+        Ok(())
+    }
+
+    fn ensure_time_lock_expired(&self, unlock_time: u64, now: u64) -> Result<(), String> {
+        match self.rounded_unlock_time(unlock_time) {
+            Some(rounded_unlock_time) if rounded_unlock_time <= now => Ok(()),
+            _ => Err("time lock has not expired yet".to_string()),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, convert::TryInto};
+use std::borrow::Cow;
 
 use candid::{CandidType, Decode, Encode};
 use ic_stable_structures::{
@@ -7,6 +7,8 @@ use ic_stable_structures::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::DeserializationError;
+
 pub type KeyName = Blob<32>;
 pub type MapName = KeyName;
 pub type MapId = KeyId;
@@ -16,6 +18,11 @@ pub type TransportKey = ByteBuf;
 pub type EncryptedMapValue = ByteBuf;
 
 /// Access rights of a user to a vetKey in [`crate::key_manager::KeyManager`] and/or an encrypted map in [`crate::encrypted_maps::EncryptedMaps`].
+///
+/// The roles are not totally ordered by privilege, e.g., [`AccessRights::Delete`]
+/// cannot read while [`AccessRights::Read`] cannot delete, so `AccessRights`
+/// deliberately does not implement `PartialOrd`/`Ord`. Rights must be checked
+/// through the [`AccessControl`] methods, e.g., [`AccessControl::can_read`].
 #[repr(u8)]
 #[derive(
     CandidType,
@@ -25,8 +32,6 @@ pub type EncryptedMapValue = ByteBuf;
     Copy,
     PartialEq,
     Eq,
-    PartialOrd,
-    Ord,
     Debug,
     strum_macros::FromRepr,
     strum_macros::EnumIter,
@@ -38,16 +43,28 @@ pub enum AccessRights {
     ReadWrite = 1,
     /// User can view/share/revoke access to the vetKey or encrypted map.
     ReadWriteManage = 2,
+    /// User can retrieve the vetKey or encrypted map and view/share access to
+    /// it, but cannot revoke or modify existing access.
+    ReadShare = 3,
+    /// User can read the encrypted values of the encrypted map, but cannot
+    /// retrieve the vetKey, i.e., cannot decrypt the values.
+    ReadMetadata = 4,
+    /// User can retrieve the vetKey or encrypted map and add new values to the
+    /// encrypted map, but cannot modify or remove existing values.
+    ReadAppend = 5,
+    /// User can remove values from the encrypted map, but can neither read nor
+    /// add values. Removing all values of the map only returns their number,
+    /// see [`crate::encrypted_maps::EncryptedMaps::remove_map_values`].
+    Delete = 6,
 }
 
 impl Storable for AccessRights {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(vec![*self as u8])
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        let v = <u8>::from_be_bytes(bytes.as_ref().try_into().unwrap());
-        Self::from_repr(v).unwrap()
+        Self::try_from_bytes(&bytes).expect("invalid stored AccessRights")
     }
 
     const BOUND: Bound = Bound::Bounded {
@@ -60,7 +77,23 @@ impl AccessControl for AccessRights {
     fn can_read(&self) -> bool {
         matches!(
             self,
-            AccessRights::Read | AccessRights::ReadWrite | AccessRights::ReadWriteManage
+            AccessRights::Read
+                | AccessRights::ReadWrite
+                | AccessRights::ReadWriteManage
+                | AccessRights::ReadShare
+                | AccessRights::ReadMetadata
+                | AccessRights::ReadAppend
+        )
+    }
+
+    fn can_derive_key(&self) -> bool {
+        matches!(
+            self,
+            AccessRights::Read
+                | AccessRights::ReadWrite
+                | AccessRights::ReadWriteManage
+                | AccessRights::ReadShare
+                | AccessRights::ReadAppend
         )
     }
 
@@ -71,11 +104,35 @@ impl AccessControl for AccessRights {
         )
     }
 
+    fn can_append(&self) -> bool {
+        matches!(
+            self,
+            AccessRights::ReadWrite | AccessRights::ReadWriteManage | AccessRights::ReadAppend
+        )
+    }
+
+    fn can_delete(&self) -> bool {
+        matches!(
+            self,
+            AccessRights::ReadWrite | AccessRights::ReadWriteManage | AccessRights::Delete
+        )
+    }
+
     fn can_get_user_rights(&self) -> bool {
-        matches!(self, AccessRights::ReadWriteManage)
+        matches!(
+            self,
+            AccessRights::ReadWriteManage | AccessRights::ReadShare
+        )
     }
 
     fn can_set_user_rights(&self) -> bool {
+        matches!(
+            self,
+            AccessRights::ReadWriteManage | AccessRights::ReadShare
+        )
+    }
+
+    fn can_remove_user(&self) -> bool {
         matches!(self, AccessRights::ReadWriteManage)
     }
 
@@ -84,6 +141,62 @@ impl AccessControl for AccessRights {
     }
 }
 
+impl TryFromBytes for AccessRights {
+    /// Returns an error if the encoding is not a single byte holding one of
+    /// the variants.
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        let [v] = bytes else {
+            return Err(DeserializationError::InvalidLength);
+        };
+        Self::from_repr(*v).ok_or(DeserializationError::InvalidValue)
+    }
+}
+
+/// Access rights of a user to a vetKey together with the user who granted them.
+///
+/// Stored in the access control map of [`crate::key_manager::KeyManager`] to
+/// prevent users with management rights from modifying users that they did
+/// not grant access to, unless these users have strictly lower rights.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AccessGrant<T> {
+    pub access_rights: T,
+    pub granted_by: candid::Principal,
+}
+
+impl<T: AccessControl> Storable for AccessGrant<T> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self::try_from_bytes(&bytes).expect("invalid stored AccessGrant")
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+impl<T: AccessControl> TryFromBytes for AccessGrant<T> {
+    /// Entries written before the granting user was tracked only contain the
+    /// access rights. These are attributed to the management canister, i.e.,
+    /// to no user that could call the key manager.
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        match Decode!(bytes, Self) {
+            Ok(grant) => Ok(grant),
+            Err(_) => Ok(Self {
+                access_rights: T::try_from_bytes(bytes)?,
+                granted_by: candid::Principal::management_canister(),
+            }),
+        }
+    }
+}
+
+/// Permissions of a user to a vetKey or encrypted map.
+///
+/// Access control types are not required to be ordered, and rights checks must
+/// go through the `can_*` methods rather than comparisons of values. Their
+/// [`TryFromBytes`] implementation allows [`crate::key_manager::KeyManager`]
+/// to skip corrupt entries in stable memory, so it must not panic on
+/// arbitrary bytes.
 pub trait AccessControl:
     CandidType
     + Serialize
@@ -92,24 +205,159 @@ pub trait AccessControl:
     + Copy
     + PartialEq
     + Eq
-    + PartialOrd
-    + Ord
     + std::fmt::Debug
     + strum::IntoEnumIterator
-    + Storable
+    + TryFromBytes
 {
     /// Returns if the user can read the vetKey or encrypted map.
     fn can_read(&self) -> bool;
+    /// Returns if the user can retrieve the vetKey, i.e., decrypt the contents
+    /// of the encrypted map.
+    ///
+    /// Defaults to [`AccessControl::can_read`].
+    fn can_derive_key(&self) -> bool {
+        self.can_read()
+    }
     /// Returns if the user can write to the vetKey or encrypted map.
     fn can_write(&self) -> bool;
+    /// Returns if the user can add new values to the encrypted map without
+    /// modifying existing ones.
+    ///
+    /// Defaults to [`AccessControl::can_write`].
+    fn can_append(&self) -> bool {
+        self.can_write()
+    }
+    /// Returns if the user can remove values from the encrypted map.
+    ///
+    /// Defaults to [`AccessControl::can_write`].
+    fn can_delete(&self) -> bool {
+        self.can_write()
+    }
     /// Returns if the user can view the access rights to the vetKey or encrypted map.
     fn can_get_user_rights(&self) -> bool;
     /// Returns if the user can modify the access rights to the vetKey or encrypted map.
     fn can_set_user_rights(&self) -> bool;
+    /// Returns if the user can revoke or modify existing access rights to the
+    /// vetKey or encrypted map.
+    ///
+    /// Defaults to [`AccessControl::can_set_user_rights`].
+    fn can_remove_user(&self) -> bool {
+        self.can_set_user_rights()
+    }
+    /// Returns if a user with these access rights can grant `target` to
+    /// another user.
+    ///
+    /// By default, this requires that the user can set user rights and holds
+    /// every permission contained in `target`, so that users with management
+    /// rights cannot escalate others above their own rights.
+    fn can_grant(&self, target: &Self) -> bool {
+        let implies = |granted: bool, held: bool| !granted || held;
+        self.can_set_user_rights()
+            && implies(target.can_read(), self.can_read())
+            && implies(target.can_derive_key(), self.can_derive_key())
+            && implies(target.can_write(), self.can_write())
+            && implies(target.can_append(), self.can_append())
+            && implies(target.can_delete(), self.can_delete())
+            && implies(target.can_get_user_rights(), self.can_get_user_rights())
+            && implies(target.can_set_user_rights(), self.can_set_user_rights())
+            && implies(target.can_remove_user(), self.can_remove_user())
+    }
     /// Returns the access rights of the owner of the vetKey or encrypted map.
     fn owner_rights() -> Self;
 }
 
+/// Decoding of a [`Storable`] encoding that returns an error instead of
+/// panicking if the bytes are invalid.
+pub trait TryFromBytes: Storable + Sized {
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, DeserializationError>;
+}
+
+/// A value in a stable map that is kept as raw bytes if it cannot be decoded.
+///
+/// [`Storable::from_bytes`] cannot return an error, so a map whose values
+/// panic when decoding invalid bytes traps on every access to a corrupt
+/// entry. Wrapping the values in `StoredValue` makes reading such an entry
+/// return [`StoredValue::Corrupt`] instead, and writing it back preserves the
+/// bytes. A valid value is encoded exactly like the wrapped value, so
+/// wrapping the values of an existing map does not require a migration.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StoredValue<V> {
+    Valid(V),
+    Corrupt(Vec<u8>),
+}
+
+impl<V> StoredValue<V> {
+    /// Returns the value if it is valid.
+    pub fn into_valid(self) -> Option<V> {
+        match self {
+            Self::Valid(value) => Some(value),
+            Self::Corrupt(_) => None,
+        }
+    }
+
+    pub fn is_corrupt(&self) -> bool {
+        matches!(self, Self::Corrupt(_))
+    }
+}
+
+impl<V> From<V> for StoredValue<V> {
+    fn from(value: V) -> Self {
+        Self::Valid(value)
+    }
+}
+
+impl<V: TryFromBytes> Storable for StoredValue<V> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            Self::Valid(value) => value.to_bytes(),
+            Self::Corrupt(bytes) => Cow::Borrowed(bytes),
+        }
+    }
+
+    fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+        match V::try_from_bytes(&bytes) {
+            Ok(value) => Self::Valid(value),
+            Err(_) => Self::Corrupt(bytes.into_owned()),
+        }
+    }
+
+    const BOUND: Bound = V::BOUND;
+}
+
+/// An entry in the stable memory of [`crate::key_manager::KeyManager`] or
+/// [`crate::encrypted_maps::EncryptedMaps`] that is corrupt or inconsistent
+/// with the other entries.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum CorruptEntry {
+    /// The access rights of `user` to `key_id` cannot be decoded.
+    AccessGrant {
+        user: candid::Principal,
+        key_id: KeyId,
+    },
+    /// `user` is listed as a user of `key_id`, but has no access rights.
+    SharedKeyWithoutAccessGrant {
+        key_id: KeyId,
+        user: candid::Principal,
+    },
+    /// `user` has access rights to `key_id`, but is not listed as a user.
+    AccessGrantWithoutSharedKey {
+        user: candid::Principal,
+        key_id: KeyId,
+    },
+    /// The encrypted value of `map_key` in `map_id` cannot be decoded.
+    EncryptedMapValue { map_id: MapId, map_key: MapKey },
+}
+
+/// The values removed by [`crate::encrypted_maps::EncryptedMaps::remove_map_values`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RemovedMapValues {
+    /// The keys of the removed values, if the caller can read the map.
+    Keys(Vec<MapKey>),
+    /// The number of removed values, if the caller can delete but not read
+    /// the map, so that it does not learn the keys.
+    Count(u64),
+}
+
 /// Efficiently serializable and deserializable byte vector that is `Storable` with `ic_stable_structures`.
 /// See, e.g., [https://mmapped.blog/posts/01-effective-rust-canisters#serde-bytes](https://mmapped.blog/posts/01-effective-rust-canisters#serde-bytes) for more details regarding why `Vec<u8>` does not work out of the box.
 /// Also, we cannot use `serde_bytes::ByteBuf` directly because it is not `Storable`.
@@ -150,11 +398,19 @@ impl Default for ByteBuf {
 }
 
 impl Storable for ByteBuf {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(Encode!(self).unwrap())
     }
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        Self::try_from_bytes(&bytes).expect("invalid stored ByteBuf")
     }
     const BOUND: Bound = Bound::Unbounded;
 }
+
+impl TryFromBytes for ByteBuf {
+    /// Returns an error if the bytes are not a Candid encoding of a
+    /// [`ByteBuf`].
+    fn try_from_bytes(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        Decode!(bytes, Self).map_err(|_| DeserializationError::InvalidValue)
+    }
+}
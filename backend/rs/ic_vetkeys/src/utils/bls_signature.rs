@@ -0,0 +1,191 @@
+//! Verification of BLS signatures produced by VetKD
+//!
+//! A VetKey is an augmented BLS signature over its `input`, with respect to
+//! the derived public key of its `context`. This allows a canister to use
+//! VetKD as a threshold BLS signing service.
+
+use super::{
+    augmented_hash_to_g1, deserialize_g1, gt_multipairing, DerivedPublicKey,
+    PreparedDerivedPublicKey, G2PREPARED_NEG_G,
+};
+use ic_bls12_381::{G1Affine, G1Projective, Scalar};
+use pairing::group::Group;
+
+/*
+ * Size of the random coefficients used for batch verification. A batch
+ * containing an invalid signature is accepted with probability at most 2^-128.
+ */
+const BATCH_VERIFICATION_COEFFICIENT_BYTES: usize = 16;
+
+/// Verify a batch of BLS signatures with respect to the same derived public key
+///
+/// The batch is first verified using a random linear combination of the
+/// signatures, which costs roughly a single multi-pairing regardless of the
+/// batch size. Only if that fails, the batch is recursively split to
+/// identify the invalid entries.
+///
+/// Returns Ok if all signatures are valid, or Err with the indices (in
+/// ascending order) of all entries with an invalid signature.
+pub fn verify_bls_signatures_batch(
+    dpk: &DerivedPublicKey,
+    batch: &[(&[u8], &[u8])],
+) -> Result<(), Vec<usize>> {
+    BlsSignatureVerifier::new(dpk).find_invalid_signatures(batch)
+}
+
+/// A verifier for BLS signatures with respect to a derived public key
+///
+/// The verifier caches the preprocessed public key, so verifying several
+/// signatures with the same verifier is cheaper than calling
+/// [`verify_bls_signature`](super::verify_bls_signature) repeatedly.
+pub struct BlsSignatureVerifier {
+    dpk: PreparedDerivedPublicKey,
+}
+
+impl From<PreparedDerivedPublicKey> for BlsSignatureVerifier {
+    fn from(dpk: PreparedDerivedPublicKey) -> Self {
+        Self { dpk }
+    }
+}
+
+impl BlsSignatureVerifier {
+    /// Create a verifier for signatures with respect to `dpk`
+    pub fn new(dpk: &DerivedPublicKey) -> Self {
+        Self {
+            dpk: dpk.prepared(),
+        }
+    }
+
+    /// Return the public key of this verifier
+    pub fn public_key(&self) -> &DerivedPublicKey {
+        self.dpk.public_key()
+    }
+
+    /// Verify a BLS signature
+    ///
+    /// Returns true if and only if `signature` is a valid signature of `input`
+    pub fn verify(&self, input: &[u8], signature: &[u8]) -> bool {
+        self.dpk.verify_bls_signature(input, signature)
+    }
+
+    /// Verify a batch of BLS signatures
+    ///
+    /// Returns true if and only if each signature is a valid signature of its
+    /// input, except with negligible probability. The batch is verified
+    /// using a random linear combination of the signatures, which requires
+    /// only two pairings regardless of the batch size.
+    pub fn verify_batch(&self, batch: &[(&[u8], &[u8])]) -> bool {
+        let signatures: Option<Vec<G1Affine>> = batch
+            .iter()
+            .map(|(_input, signature)| deserialize_g1(signature).ok())
+            .collect();
+
+        match signatures {
+            Some(signatures) => {
+                let inputs: Vec<&[u8]> = batch.iter().map(|(input, _)| *input).collect();
+                self.verify_batch_pts(&inputs, &signatures)
+            }
+            None => false,
+        }
+    }
+
+    /// Verify a batch of BLS signatures, identifying the invalid entries
+    ///
+    /// See [`verify_bls_signatures_batch`]
+    pub fn find_invalid_signatures(&self, batch: &[(&[u8], &[u8])]) -> Result<(), Vec<usize>> {
+        let mut invalid = Vec::new();
+        let mut candidates = Vec::with_capacity(batch.len());
+        for (index, (input, signature)) in batch.iter().enumerate() {
+            match deserialize_g1(signature) {
+                Ok(signature) => candidates.push((index, *input, signature)),
+                Err(_) => invalid.push(index),
+            }
+        }
+
+        self.bisect_invalid_signatures(&candidates, &mut invalid);
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            invalid.sort_unstable();
+            Err(invalid)
+        }
+    }
+
+    fn bisect_invalid_signatures(
+        &self,
+        candidates: &[(usize, &[u8], G1Affine)],
+        invalid: &mut Vec<usize>,
+    ) {
+        let inputs: Vec<&[u8]> = candidates.iter().map(|(_, input, _)| *input).collect();
+        let signatures: Vec<G1Affine> = candidates.iter().map(|(_, _, sig)| *sig).collect();
+
+        if self.verify_batch_pts(&inputs, &signatures) {
+            return;
+        }
+
+        if candidates.len() == 1 {
+            invalid.push(candidates[0].0);
+        } else {
+            let (left, right) = candidates.split_at(candidates.len() / 2);
+            self.bisect_invalid_signatures(left, invalid);
+            self.bisect_invalid_signatures(right, invalid);
+        }
+    }
+
+    pub(crate) fn verify_batch_pts(&self, inputs: &[&[u8]], signatures: &[G1Affine]) -> bool {
+        match inputs.len() {
+            0 => return true,
+            1 => return self.dpk.verify_pt(inputs[0], &signatures[0]),
+            _ => {}
+        }
+
+        let coefficients = self.batch_coefficients(inputs, signatures);
+
+        let mut signature_sum = G1Projective::identity();
+        let mut msg_sum = G1Projective::identity();
+        for ((input, signature), coefficient) in inputs.iter().zip(signatures).zip(&coefficients) {
+            signature_sum += signature * coefficient;
+            msg_sum += augmented_hash_to_g1(&self.public_key().point, input) * coefficient;
+        }
+
+        let is_valid = gt_multipairing(&[
+            (&G1Affine::from(signature_sum), &G2PREPARED_NEG_G),
+            (&G1Affine::from(msg_sum), self.dpk.g2_prepared()),
+        ])
+        .is_identity();
+        bool::from(is_valid)
+    }
+
+    /// Derive the coefficients of the linear combination from the batch
+    ///
+    /// The coefficients are derived by hashing the public key and the whole
+    /// batch, so they cannot be predicted before the signatures are fixed.
+    fn batch_coefficients(&self, inputs: &[&[u8]], signatures: &[G1Affine]) -> Vec<Scalar> {
+        use sha3::{
+            digest::{ExtendableOutput, Update, XofReader},
+            Shake256,
+        };
+
+        let mut transcript = Shake256::default();
+        let domain_sep = "ic-vetkd-bls12-381-batch-verification-coefficients";
+        transcript.update(&(domain_sep.len() as u64).to_be_bytes());
+        transcript.update(domain_sep.as_bytes());
+        transcript.update(&self.public_key().point.to_compressed());
+        transcript.update(&(inputs.len() as u64).to_be_bytes());
+        for (input, signature) in inputs.iter().zip(signatures) {
+            transcript.update(&(input.len() as u64).to_be_bytes());
+            transcript.update(input);
+            transcript.update(&signature.to_compressed());
+        }
+        let mut xof = transcript.finalize_xof();
+
+        (0..inputs.len())
+            .map(|_| {
+                let mut bytes = [0u8; 32];
+                xof.read(&mut bytes[..BATCH_VERIFICATION_COEFFICIENT_BYTES]);
+                Scalar::from_bytes(&bytes).expect("128-bit value is a valid scalar")
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,121 @@
+//! Candid, serde and stable memory support for the VetKD types
+//!
+//! Each type is encoded as its byte encoding, i.e. as `blob` in Candid and as
+//! bytes in serde, and is validated when decoded. This allows using the types
+//! directly in canister interfaces and stable structures.
+//!
+//! Values in stable memory are decoded with the same checks as any other
+//! input, since the memory may be corrupt or have been written by an earlier
+//! version of the canister. Canisters that trust their stored keys can still
+//! store the bytes and decode them with `deserialize_unchecked`.
+//! [`TryFromBytes`] returns an error instead of panicking, e.g., to keep
+//! corrupt values in a [`crate::types::StoredValue`].
+
+use super::{
+    DerivedPublicKey, EncryptedVetKey, IbeCiphertext, MasterPublicKey, VetKey, G1AFFINE_BYTES,
+    G2AFFINE_BYTES,
+};
+use crate::types::TryFromBytes;
+use crate::DeserializationError;
+use candid::types::{Serializer, Type, TypeInner};
+use candid::CandidType;
+use ic_stable_structures::{storable::Bound, Storable};
+use std::borrow::Cow;
+
+macro_rules! impl_byte_encoding {
+    ($type:ty, |$value:ident| $to_bytes:expr, $deserialize:path, $bound:expr) => {
+        impl CandidType for $type {
+            fn _ty() -> Type {
+                TypeInner::Vec(TypeInner::Nat8.into()).into()
+            }
+
+            fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+                let $value = self;
+                let bytes: Cow<'_, [u8]> = $to_bytes;
+                serializer.serialize_blob(&bytes)
+            }
+        }
+
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let $value = self;
+                let bytes: Cow<'_, [u8]> = $to_bytes;
+                serializer.serialize_bytes(&bytes)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let bytes =
+                    <serde_bytes::ByteBuf as serde::Deserialize>::deserialize(deserializer)?;
+                <$type>::deserialize(&bytes).map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl Storable for $type {
+            fn to_bytes(&self) -> Cow<'_, [u8]> {
+                let $value = self;
+                $to_bytes
+            }
+
+            fn from_bytes(bytes: Cow<'_, [u8]>) -> Self {
+                Self::try_from_bytes(&bytes).expect(concat!("invalid stored ", stringify!($type)))
+            }
+
+            const BOUND: Bound = $bound;
+        }
+
+        impl TryFromBytes for $type {
+            fn try_from_bytes(bytes: &[u8]) -> Result<Self, DeserializationError> {
+                $deserialize(bytes)
+            }
+        }
+    };
+}
+
+impl_byte_encoding!(
+    MasterPublicKey,
+    |key| Cow::Owned(key.serialize()),
+    MasterPublicKey::deserialize,
+    Bound::Bounded {
+        max_size: G2AFFINE_BYTES as u32,
+        is_fixed_size: true,
+    }
+);
+
+impl_byte_encoding!(
+    DerivedPublicKey,
+    |key| Cow::Owned(key.serialize()),
+    DerivedPublicKey::deserialize,
+    Bound::Bounded {
+        max_size: G2AFFINE_BYTES as u32,
+        is_fixed_size: true,
+    }
+);
+
+impl_byte_encoding!(
+    EncryptedVetKey,
+    |key| Cow::Owned(key.serialize()),
+    EncryptedVetKey::deserialize,
+    Bound::Bounded {
+        max_size: (2 * G1AFFINE_BYTES + G2AFFINE_BYTES) as u32,
+        is_fixed_size: true,
+    }
+);
+
+impl_byte_encoding!(
+    VetKey,
+    |key| Cow::Borrowed(key.signature_bytes()),
+    VetKey::deserialize,
+    Bound::Bounded {
+        max_size: G1AFFINE_BYTES as u32,
+        is_fixed_size: true,
+    }
+);
+
+impl_byte_encoding!(
+    IbeCiphertext,
+    |ctext| Cow::Owned(ctext.serialize()),
+    IbeCiphertext::deserialize,
+    Bound::Unbounded
+);
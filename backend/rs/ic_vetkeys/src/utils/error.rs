@@ -0,0 +1,163 @@
+//! Errors returned by the VetKD utilities
+//!
+//! Each fallible operation returns the most specific error type that
+//! describes its failures. All of them can be converted into [`Error`], so
+//! that callers combining several operations can use `?` and still match on
+//! the kind of failure.
+
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Error indicating that deserializing a value failed
+pub enum DeserializationError {
+    /// The encoding has an invalid length
+    InvalidLength,
+    /// The encoding starts with an unknown header
+    UnknownHeader,
+    /// The encoding contains an invalid elliptic curve point
+    InvalidPoint,
+    /// The encoding contains an invalid scalar
+    InvalidScalar,
+    /// The encoding contains a value that is out of range
+    InvalidValue,
+}
+
+impl fmt::Display for DeserializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength => write!(f, "invalid encoding length"),
+            Self::UnknownHeader => write!(f, "unknown header"),
+            Self::InvalidPoint => write!(f, "invalid elliptic curve point"),
+            Self::InvalidScalar => write!(f, "invalid scalar"),
+            Self::InvalidValue => write!(f, "invalid value"),
+        }
+    }
+}
+
+impl std::error::Error for DeserializationError {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Error indicating that a VetKey or signature failed verification
+pub enum VerificationError {
+    /// The components of the encrypted VetKey are inconsistent
+    InconsistentEncryptedKey,
+    /// The VetKey or signature is not a valid signature of the input
+    InvalidSignature,
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InconsistentEncryptedKey => {
+                write!(f, "invalid encrypted key: c1 inconsistent with c2")
+            }
+            Self::InvalidSignature => write!(f, "invalid signature"),
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Error indicating that decrypting a ciphertext failed
+pub enum DecryptionError {
+    /// Associated data was provided for a ciphertext not bound to any
+    AssociatedDataNotSupported,
+    /// None of the recipients of a multi-recipient ciphertext matches the VetKey
+    NoMatchingRecipient,
+    /// The VetKey or associated data is wrong, or the ciphertext was modified
+    DecryptionFailed,
+}
+
+impl fmt::Display for DecryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AssociatedDataNotSupported => {
+                write!(f, "ciphertext is not bound to associated data")
+            }
+            Self::NoMatchingRecipient => write!(f, "decryption failed: no matching recipient"),
+            Self::DecryptionFailed => write!(f, "decryption failed"),
+        }
+    }
+}
+
+impl std::error::Error for DecryptionError {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Error indicating that a seed cannot be used
+pub enum InvalidSeedError {
+    /// The seed does not have the required length
+    InvalidLength,
+    /// The seed is too short to contain sufficient entropy
+    InsufficientInput,
+}
+
+impl fmt::Display for InvalidSeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength => write!(f, "invalid seed length"),
+            Self::InsufficientInput => write!(f, "insufficient input material for seed"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidSeedError {}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+/// Any error returned by the VetKD utilities
+pub enum Error {
+    /// Deserializing a value failed
+    Deserialization(DeserializationError),
+    /// Verifying a VetKey or signature failed
+    Verification(VerificationError),
+    /// Decrypting a ciphertext failed
+    Decryption(DecryptionError),
+    /// A seed cannot be used
+    InvalidSeed(InvalidSeedError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deserialization(e) => write!(f, "deserialization failed: {e}"),
+            Self::Verification(e) => write!(f, "verification failed: {e}"),
+            Self::Decryption(e) => write!(f, "{e}"),
+            Self::InvalidSeed(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Deserialization(e) => Some(e),
+            Self::Verification(e) => Some(e),
+            Self::Decryption(e) => Some(e),
+            Self::InvalidSeed(e) => Some(e),
+        }
+    }
+}
+
+impl From<DeserializationError> for Error {
+    fn from(e: DeserializationError) -> Self {
+        Self::Deserialization(e)
+    }
+}
+
+impl From<VerificationError> for Error {
+    fn from(e: VerificationError) -> Self {
+        Self::Verification(e)
+    }
+}
+
+impl From<DecryptionError> for Error {
+    fn from(e: DecryptionError) -> Self {
+        Self::Decryption(e)
+    }
+}
+
+impl From<InvalidSeedError> for Error {
+    fn from(e: InvalidSeedError) -> Self {
+        Self::InvalidSeed(e)
+    }
+}
@@ -0,0 +1,356 @@
+//! Hybrid identity based encryption for large payloads
+//!
+//! A fresh data key is encapsulated using IBE, and the payload is encrypted
+//! using AES-256-GCM under the data key in chunks of [`IBE_HYBRID_CHUNK_BYTES`].
+//! This allows encrypting and decrypting payloads of arbitrary size without
+//! holding them in memory, and authenticates each chunk individually.
+//!
+//! The ciphertext consists of the IBE encapsulation of the data key (using
+//! version 0x02 of the IBE header) followed by the encrypted chunks. The nonce
+//! of each chunk encodes its index and whether it is the final chunk, so chunks
+//! cannot be reordered, dropped, or truncated without detection. The final chunk
+//! is always shorter than [`IBE_HYBRID_CHUNK_BYTES`], and thus may be empty.
+
+use super::{
+    derive_symmetric_key, deserialize_g2, DecryptionError, DerivedPublicKey, DeserializationError,
+    Error, IbeCiphertext, IbeEncryptionContext, IbeIdentity, IbeSeed, VetKey, G2AFFINE_BYTES,
+    IBE_SEED_BYTES,
+};
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use std::io::{self, Read, Write};
+use zeroize::Zeroizing;
+
+/*
+ * Same as the IBE header, but with version 0x02 to indicate a hybrid ciphertext
+ */
+const IBE_HYBRID_HEADER: [u8; 8] = [b'I', b'C', b' ', b'I', b'B', b'E', 0x00, 0x02];
+
+const DATA_KEY_BYTES: usize = 32;
+
+const ENCAPSULATED_KEY_BYTES: usize =
+    IBE_HYBRID_HEADER.len() + G2AFFINE_BYTES + IBE_SEED_BYTES + DATA_KEY_BYTES;
+
+const AES_GCM_TAG_BYTES: usize = 16;
+
+/// The size of the plaintext of each chunk of a hybrid IBE ciphertext
+pub const IBE_HYBRID_CHUNK_BYTES: usize = 64 * 1024;
+
+const CIPHERTEXT_CHUNK_BYTES: usize = IBE_HYBRID_CHUNK_BYTES + AES_GCM_TAG_BYTES;
+
+/// Encrypt a message of arbitrary size using hybrid IBE
+///
+/// See [`IbeHybridEncryptor`] for encrypting a stream instead.
+pub fn ibe_hybrid_encrypt(
+    dpk: &DerivedPublicKey,
+    identity: &IbeIdentity,
+    msg: &[u8],
+    seed: &IbeSeed,
+) -> Vec<u8> {
+    let chunks = msg.len() / IBE_HYBRID_CHUNK_BYTES + 1;
+    let output =
+        Vec::with_capacity(ENCAPSULATED_KEY_BYTES + msg.len() + chunks * AES_GCM_TAG_BYTES);
+
+    let mut encryptor =
+        IbeHybridEncryptor::new(output, dpk, identity, seed).expect("writing to a Vec never fails");
+    encryptor
+        .write_all(msg)
+        .expect("writing to a Vec never fails");
+    encryptor.finish().expect("writing to a Vec never fails")
+}
+
+/// Decrypt a hybrid IBE ciphertext
+///
+/// The VetKey provided must be the VetKey produced by a request to the IC
+/// for a given `identity` (aka `input`) and `context` both matching the
+/// values used during encryption.
+///
+/// Returns the plaintext, or Err if the ciphertext is malformed or decryption
+/// failed
+pub fn ibe_hybrid_decrypt(ctext: &[u8], vetkey: &VetKey) -> Result<Vec<u8>, Error> {
+    let mut decryptor = IbeHybridDecryptor::new(ctext, vetkey).map_err(into_error)?;
+    let mut msg = Vec::with_capacity(ctext.len().saturating_sub(ENCAPSULATED_KEY_BYTES));
+    decryptor.read_to_end(&mut msg).map_err(into_error)?;
+    Ok(msg)
+}
+
+/// Streaming encryption of a hybrid IBE ciphertext
+///
+/// All data written to the encryptor is encrypted and written to the
+/// underlying writer in chunks. [`IbeHybridEncryptor::finish`] must be called
+/// after all data has been written, otherwise the ciphertext is incomplete and
+/// cannot be decrypted.
+pub struct IbeHybridEncryptor<W: Write> {
+    writer: W,
+    chunks: ChunkCipher,
+    buffer: Zeroizing<Vec<u8>>,
+}
+
+impl<W: Write> IbeHybridEncryptor<W> {
+    /// Start encrypting a stream to the given identity
+    ///
+    /// The seed should be generated with a cryptographically secure random
+    /// number generator. Do not reuse the seed for encrypting another message
+    /// or any other purpose.
+    ///
+    /// Writes the encapsulated data key to the writer.
+    pub fn new(
+        mut writer: W,
+        dpk: &DerivedPublicKey,
+        identity: &IbeIdentity,
+        seed: &IbeSeed,
+    ) -> io::Result<Self> {
+        let data_key = Zeroizing::new(derive_symmetric_key(
+            seed.value(),
+            "ic-vetkd-bls12-381-ibe-hybrid-data-key",
+            DATA_KEY_BYTES,
+        ));
+        let kem_seed = IbeSeed::from_bytes(&derive_symmetric_key(
+            seed.value(),
+            "ic-vetkd-bls12-381-ibe-hybrid-kem-seed",
+            IBE_SEED_BYTES,
+        ))
+        .expect("seed has sufficient length");
+
+        let encapsulated_key = IbeEncryptionContext::new(dpk, identity)
+            .encrypt_with_header(&IBE_HYBRID_HEADER, &data_key, &[], &kem_seed)
+            .serialize();
+        writer.write_all(&encapsulated_key)?;
+
+        Ok(Self {
+            writer,
+            chunks: ChunkCipher::new(&data_key, encapsulated_key),
+            buffer: Zeroizing::new(Vec::with_capacity(IBE_HYBRID_CHUNK_BYTES)),
+        })
+    }
+
+    /// Encrypt the remaining data as the final chunk and return the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let chunk = self.chunks.seal(&self.buffer, true)?;
+        self.writer.write_all(&chunk)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for IbeHybridEncryptor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(IBE_HYBRID_CHUNK_BYTES - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+
+        if self.buffer.len() == IBE_HYBRID_CHUNK_BYTES {
+            let chunk = self.chunks.seal(&self.buffer, false)?;
+            self.writer.write_all(&chunk)?;
+            self.buffer.clear();
+        }
+
+        Ok(len)
+    }
+
+    /// Flushes the underlying writer
+    ///
+    /// Data of an incomplete chunk is only written once the chunk is complete
+    /// or [`IbeHybridEncryptor::finish`] is called.
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Streaming decryption of a hybrid IBE ciphertext
+///
+/// Each chunk is authenticated before any of its plaintext is returned.
+/// However, the plaintext is only known to be complete once reading returns
+/// `Ok(0)`; if reading fails at any point the data read so far must be
+/// discarded.
+///
+/// Errors caused by a malformed ciphertext or a failed decryption have kind
+/// `InvalidData` and wrap an [`Error`], which can be recovered using
+/// `get_ref` and `downcast_ref`.
+pub struct IbeHybridDecryptor<R: Read> {
+    reader: R,
+    chunks: ChunkCipher,
+    plaintext: Zeroizing<Vec<u8>>,
+    position: usize,
+    is_finished: bool,
+}
+
+impl<R: Read> IbeHybridDecryptor<R> {
+    /// Start decrypting a stream
+    ///
+    /// Reads and decrypts the encapsulated data key. The VetKey provided must
+    /// be the VetKey produced by a request to the IC for a given `identity`
+    /// (aka `input`) and `context` both matching the values used during
+    /// encryption.
+    pub fn new(mut reader: R, vetkey: &VetKey) -> io::Result<Self> {
+        let mut encapsulated_key = vec![0u8; ENCAPSULATED_KEY_BYTES];
+        reader.read_exact(&mut encapsulated_key).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                invalid_data(DeserializationError::InvalidLength)
+            } else {
+                e
+            }
+        })?;
+
+        let data_key = Zeroizing::new(
+            deserialize_encapsulated_key(&encapsulated_key)?
+                .decrypt(vetkey)
+                .map_err(invalid_data)?,
+        );
+
+        Ok(Self {
+            reader,
+            chunks: ChunkCipher::new(&data_key, encapsulated_key),
+            plaintext: Zeroizing::new(Vec::with_capacity(IBE_HYBRID_CHUNK_BYTES)),
+            position: 0,
+            is_finished: false,
+        })
+    }
+
+    /// Return the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn open_next_chunk(&mut self) -> io::Result<()> {
+        let mut chunk = vec![0u8; CIPHERTEXT_CHUNK_BYTES];
+        let len = read_up_to(&mut self.reader, &mut chunk)?;
+        chunk.truncate(len);
+
+        // only the final chunk is shorter than a full chunk
+        let is_last = len < CIPHERTEXT_CHUNK_BYTES;
+        if is_last && len < AES_GCM_TAG_BYTES {
+            return Err(invalid_data(DeserializationError::InvalidLength));
+        }
+
+        self.plaintext = Zeroizing::new(self.chunks.open(&chunk, is_last)?);
+        self.position = 0;
+
+        if is_last {
+            if read_up_to(&mut self.reader, &mut [0u8; 1])? != 0 {
+                return Err(invalid_data(DeserializationError::InvalidLength));
+            }
+            self.is_finished = true;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for IbeHybridDecryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.is_finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.open_next_chunk()?;
+        }
+
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// AES-256-GCM encryption of the chunks of a single hybrid IBE ciphertext
+///
+/// The encapsulated data key is used as associated data of every chunk.
+struct ChunkCipher {
+    cipher: Aes256Gcm,
+    associated_data: Vec<u8>,
+    next_index: u64,
+}
+
+impl ChunkCipher {
+    fn new(data_key: &[u8], associated_data: Vec<u8>) -> Self {
+        Self {
+            cipher: Aes256Gcm::new_from_slice(data_key).expect("data key has valid length"),
+            associated_data,
+            next_index: 0,
+        }
+    }
+
+    /// The nonce consists of 3 zero bytes, the chunk index as a 64-bit big
+    /// endian integer, and a final byte which is 1 for the final chunk and 0
+    /// otherwise. Since the data key is never reused, the nonces are unique.
+    fn next_nonce(&mut self, is_last: bool) -> io::Result<[u8; 12]> {
+        let mut nonce = [0u8; 12];
+        nonce[3..11].copy_from_slice(&self.next_index.to_be_bytes());
+        nonce[11] = u8::from(is_last);
+        self.next_index = self
+            .next_index
+            .checked_add(1)
+            .ok_or_else(|| invalid_data(DeserializationError::InvalidLength))?;
+        Ok(nonce)
+    }
+
+    fn seal(&mut self, plaintext: &[u8], is_last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce(is_last)?;
+        let payload = Payload {
+            msg: plaintext,
+            aad: &self.associated_data,
+        };
+        self.cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| io::Error::other("chunk encryption failed"))
+    }
+
+    fn open(&mut self, ciphertext: &[u8], is_last: bool) -> io::Result<Vec<u8>> {
+        let nonce = self.next_nonce(is_last)?;
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &self.associated_data,
+        };
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| invalid_data(DecryptionError::DecryptionFailed))
+    }
+}
+
+fn deserialize_encapsulated_key(bytes: &[u8]) -> io::Result<IbeCiphertext> {
+    let (header, rest) = bytes.split_at(IBE_HYBRID_HEADER.len());
+    if header != IBE_HYBRID_HEADER {
+        return Err(invalid_data(DeserializationError::UnknownHeader));
+    }
+
+    let (c1, rest) = rest.split_at(G2AFFINE_BYTES);
+    let (c2, c3) = rest.split_at(IBE_SEED_BYTES);
+
+    Ok(IbeCiphertext {
+        header: header.to_vec(),
+        c1: deserialize_g2(c1).map_err(invalid_data)?,
+        c2: c2.try_into().expect("length already checked"),
+        c3: c3.to_vec(),
+    })
+}
+
+/// Read until the buffer is full or the reader is exhausted
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+fn invalid_data<E: Into<Error>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.into())
+}
+
+/// Recover the [`Error`] wrapped by [`invalid_data`]
+///
+/// Reading from a slice never fails otherwise.
+fn into_error(error: io::Error) -> Error {
+    error
+        .into_inner()
+        .and_then(|inner| inner.downcast::<Error>().ok())
+        .map(|error| *error)
+        .unwrap_or(Error::Decryption(DecryptionError::DecryptionFailed))
+}
@@ -12,9 +12,25 @@ use ic_bls12_381::{
 };
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
-use std::array::TryFromSliceError;
 use std::ops::Neg;
-use zeroize::{Zeroize, ZeroizeOnDrop};
+use subtle::CtOption;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+mod bls_signature;
+pub use bls_signature::*;
+mod error;
+pub use error::*;
+mod encoding;
+mod hybrid_ibe;
+pub use hybrid_ibe::*;
+mod multi_recipient_ibe;
+pub use multi_recipient_ibe::*;
+mod prepared;
+pub use prepared::*;
+mod timelock;
+pub use timelock::*;
+mod vrf;
+pub use vrf::*;
 
 lazy_static::lazy_static! {
     static ref G2PREPARED_NEG_G : G2Prepared = G2Affine::generator().neg().into();
@@ -22,6 +38,8 @@ lazy_static::lazy_static! {
 
 const G1AFFINE_BYTES: usize = 48; // Size of compressed form
 const G2AFFINE_BYTES: usize = 96; // Size of compressed form
+const G1AFFINE_UNCOMPRESSED_BYTES: usize = 2 * G1AFFINE_BYTES;
+const G2AFFINE_UNCOMPRESSED_BYTES: usize = 2 * G2AFFINE_BYTES;
 
 /// Derive a symmetric key using HKDF-SHA256
 pub fn derive_symmetric_key(input: &[u8], domain_sep: &str, len: usize) -> Vec<u8> {
@@ -68,13 +86,23 @@ pub struct TransportSecretKey {
 }
 
 impl TransportSecretKey {
-    /// Creates a transport secret key from a 32-byte seed.
-    pub fn from_seed(seed: Vec<u8>) -> Result<TransportSecretKey, String> {
-        let seed_32_bytes: [u8; 32] = seed.try_into().map_err(|_e| "seed not 32 bytes")?;
-        let rng = &mut ChaCha20Rng::from_seed(seed_32_bytes);
+    /// Creates a random transport secret key
+    pub fn random<R: rand::CryptoRng + rand::RngCore>(rng: &mut R) -> Self {
         use pairing::group::ff::Field;
-        let secret_key = Scalar::random(rng);
-        Ok(Self { secret_key })
+        Self {
+            secret_key: Scalar::random(rng),
+        }
+    }
+
+    /// Creates a transport secret key from a 32-byte seed.
+    ///
+    /// The seed must be generated with a cryptographically secure random
+    /// number generator, and must not be reused.
+    pub fn from_seed(seed: Vec<u8>) -> Result<TransportSecretKey, InvalidSeedError> {
+        let seed_32_bytes: [u8; 32] = seed
+            .try_into()
+            .map_err(|_e| InvalidSeedError::InvalidLength)?;
+        Ok(Self::random(&mut ChaCha20Rng::from_seed(seed_32_bytes)))
     }
 
     /// Returns the serialized public key associated with this secret key
@@ -90,20 +118,15 @@ impl TransportSecretKey {
     }
 
     /// Serialize this transport secret key to a bytestring
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, String> {
-        if bytes.len() != 32 {
-            return Err(format!(
-                "TransportSecretKey must be exactly 32 bytes not {}",
-                bytes.len()
-            ));
-        }
-
-        let bytes: [u8; 32] = bytes.try_into().expect("Length already checked");
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_e| DeserializationError::InvalidLength)?;
 
         if let Some(s) = Scalar::from_bytes(&bytes).into_option() {
             Ok(Self { secret_key: s })
         } else {
-            Err("Invalid TransportSecretKey bytes".to_string())
+            Err(DeserializationError::InvalidScalar)
         }
     }
 }
@@ -116,13 +139,6 @@ pub fn is_valid_transport_public_key_encoding(bytes: &[u8]) -> bool {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-/// Error indicating deserializing a derived public key failed
-pub enum PublicKeyDeserializationError {
-    /// The public key is invalid
-    InvalidPublicKey,
-}
-
 #[derive(Clone, Debug, Eq, PartialEq)]
 /// A master VetKD public key
 pub struct MasterPublicKey {
@@ -145,12 +161,12 @@ impl MasterPublicKey {
     /// Returns an error if the key is invalid (e.g., it has invalid length,
     /// i.e., not 96 bytes, it is not in compressed format, is is not a point
     /// on the curve, it is not torsion-free).
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, PublicKeyDeserializationError> {
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
         let dpk_bytes: &[u8; Self::BYTES] = bytes
             .try_into()
-            .map_err(|_e: TryFromSliceError| PublicKeyDeserializationError::InvalidPublicKey)?;
+            .map_err(|_e| DeserializationError::InvalidLength)?;
         let dpk = option_from_ctoption(G2Affine::from_compressed(dpk_bytes))
-            .ok_or(PublicKeyDeserializationError::InvalidPublicKey)?;
+            .ok_or(DeserializationError::InvalidPoint)?;
         Ok(Self { point: dpk })
     }
 
@@ -204,12 +220,12 @@ impl DerivedPublicKey {
     /// Returns an error if the key is invalid (e.g., it has invalid length,
     /// i.e., not 96 bytes, it is not in compressed format, is is not a point
     /// on the curve, it is not torsion-free).
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, PublicKeyDeserializationError> {
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
         let dpk_bytes: &[u8; Self::BYTES] = bytes
             .try_into()
-            .map_err(|_e: TryFromSliceError| PublicKeyDeserializationError::InvalidPublicKey)?;
+            .map_err(|_e| DeserializationError::InvalidLength)?;
         let dpk = option_from_ctoption(G2Affine::from_compressed(dpk_bytes))
-            .ok_or(PublicKeyDeserializationError::InvalidPublicKey)?;
+            .ok_or(DeserializationError::InvalidPoint)?;
         Ok(Self { point: dpk })
     }
 
@@ -243,6 +259,27 @@ impl DerivedPublicKey {
     pub fn serialize(&self) -> Vec<u8> {
         self.point.to_compressed().to_vec()
     }
+
+    /// Return the uncompressed byte encoding of this derived public key
+    ///
+    /// The uncompressed encoding is twice as large, but is much cheaper to
+    /// deserialize using [`DerivedPublicKey::deserialize_unchecked`].
+    pub fn serialize_uncompressed(&self) -> Vec<u8> {
+        self.point.to_uncompressed().to_vec()
+    }
+
+    /// Deserializes a derived public key from trusted storage
+    ///
+    /// Accepts both the compressed and the uncompressed encoding, but skips
+    /// the subgroup check, which dominates the cost of [`DerivedPublicKey::deserialize`].
+    /// Only use this for bytes that were produced by this library and stored
+    /// where they cannot be modified by others, e.g. in stable memory, and
+    /// never for bytes received from users.
+    pub fn deserialize_unchecked(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        Ok(Self {
+            point: deserialize_g2_unchecked(bytes)?,
+        })
+    }
 }
 
 /// A verifiably encrypted threshold key derived by the VetKD protocol
@@ -250,12 +287,29 @@ impl DerivedPublicKey {
 /// A VetKey is a valid BLS signature created for an input specified
 /// by the user
 ///
-#[derive(Clone, Debug, Eq, PartialEq)]
+/// The VetKey is zeroized when dropped, is not printed by `Debug`, and is
+/// compared in constant time.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
 pub struct VetKey {
     pt: G1Affine,
     pt_bytes: [u8; 48],
 }
 
+impl std::fmt::Debug for VetKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("VetKey(REDACTED)")
+    }
+}
+
+impl PartialEq for VetKey {
+    fn eq(&self, other: &Self) -> bool {
+        use subtle::ConstantTimeEq;
+        bool::from(self.pt_bytes.ct_eq(&other.pt_bytes))
+    }
+}
+
+impl Eq for VetKey {}
+
 impl VetKey {
     fn new(pt: G1Affine) -> Self {
         Self {
@@ -283,9 +337,11 @@ impl VetKey {
      * also your usage of the resulting key. For example say your application
      * "my-app" is deriving two keys, one for usage "foo" and the other for
      * "bar". You might use as domain separators "my-app-foo" and "my-app-bar".
+     *
+     * The key is returned in a wrapper that zeroizes it when dropped.
      */
-    pub fn derive_symmetric_key(&self, domain_sep: &str, output_len: usize) -> Vec<u8> {
-        derive_symmetric_key(&self.pt_bytes, domain_sep, output_len)
+    pub fn derive_symmetric_key(&self, domain_sep: &str, output_len: usize) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(derive_symmetric_key(&self.pt_bytes, domain_sep, output_len))
     }
 
     /**
@@ -293,10 +349,10 @@ impl VetKey {
      *
      * Typically this would have been created using [`VetKey::signature_bytes`]
      */
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, String> {
-        let bytes48: [u8; 48] = bytes.try_into().map_err(|_e: TryFromSliceError| {
-            format!("Vetkey is unexpected length {}", bytes.len())
-        })?;
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        let bytes48: [u8; 48] = bytes
+            .try_into()
+            .map_err(|_e| DeserializationError::InvalidLength)?;
 
         if let Some(pt) = option_from_ctoption(G1Affine::from_compressed(&bytes48)) {
             Ok(Self {
@@ -304,16 +360,31 @@ impl VetKey {
                 pt_bytes: bytes48,
             })
         } else {
-            Err("Invalid VetKey".to_string())
+            Err(DeserializationError::InvalidPoint)
         }
     }
-}
 
-#[derive(Copy, Clone, Debug)]
-/// Error indicating that deserializing an encrypted key failed
-pub enum EncryptedVetKeyDeserializationError {
-    /// Error indicating one or more of the points was invalid
-    InvalidEncryptedVetKey,
+    /**
+     * Return the uncompressed byte encoding of the VetKey
+     *
+     * The uncompressed encoding is twice as large, but is much cheaper to
+     * deserialize using [`VetKey::deserialize_unchecked`].
+     */
+    pub fn serialize_uncompressed(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(self.pt.to_uncompressed().to_vec())
+    }
+
+    /**
+     * Deserialize a VetKey from trusted storage
+     *
+     * Accepts both the compressed and the uncompressed encoding, but skips
+     * the subgroup check. Only use this for bytes that were produced by this
+     * library and stored where they cannot be modified by others, e.g. in
+     * stable memory, and never for bytes received from users.
+     */
+    pub fn deserialize_unchecked(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        Ok(Self::new(deserialize_g1_unchecked(bytes)?))
+    }
 }
 
 /// An encrypted VetKey
@@ -334,7 +405,20 @@ impl EncryptedVetKey {
         tsk: &TransportSecretKey,
         derived_public_key: &DerivedPublicKey,
         input: &[u8],
-    ) -> Result<VetKey, String> {
+    ) -> Result<VetKey, VerificationError> {
+        self.decrypt_and_verify_prepared(tsk, &derived_public_key.prepared(), input)
+    }
+
+    /// Decrypts and verifies the VetKey using a preprocessed derived public key
+    ///
+    /// This is cheaper than [`EncryptedVetKey::decrypt_and_verify`] when
+    /// decrypting several VetKeys for the same derived public key.
+    pub fn decrypt_and_verify_prepared(
+        &self,
+        tsk: &TransportSecretKey,
+        derived_public_key: &PreparedDerivedPublicKey,
+        input: &[u8],
+    ) -> Result<VetKey, VerificationError> {
         use pairing::group::Group;
 
         // Check that c1 and c2 have the same discrete logarithm
@@ -347,53 +431,84 @@ impl EncryptedVetKey {
         ]);
 
         if !bool::from(c1_c2.is_identity()) {
-            return Err("invalid encrypted key: c1 inconsistent with c2".to_string());
+            return Err(VerificationError::InconsistentEncryptedKey);
         }
 
         // Recover the purported VetKey
         let k = G1Affine::from(G1Projective::from(&self.c3) - self.c1 * tsk.secret_key);
 
         // Check that the VetKey is a valid BLS signature
-        if verify_bls_signature_pt(derived_public_key, input, &k) {
+        if derived_public_key.verify_pt(input, &k) {
             Ok(VetKey::new(k))
         } else {
-            Err("invalid encrypted key: verification failed".to_string())
+            Err(VerificationError::InvalidSignature)
         }
     }
 
+    /// Serializes this encrypted key to a byte vector
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(Self::BYTES);
+        output.extend_from_slice(&self.c1.to_compressed());
+        output.extend_from_slice(&self.c2.to_compressed());
+        output.extend_from_slice(&self.c3.to_compressed());
+        output
+    }
+
+    /// Serializes this encrypted key to a byte vector in uncompressed form
+    ///
+    /// The uncompressed encoding is twice as large, but is much cheaper to
+    /// deserialize using [`EncryptedVetKey::deserialize_unchecked`].
+    pub fn serialize_uncompressed(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(2 * Self::BYTES);
+        output.extend_from_slice(&self.c1.to_uncompressed());
+        output.extend_from_slice(&self.c2.to_uncompressed());
+        output.extend_from_slice(&self.c3.to_uncompressed());
+        output
+    }
+
+    /// Deserializes an encrypted key from trusted storage
+    ///
+    /// Accepts both the compressed and the uncompressed encoding, but skips
+    /// the subgroup checks. Only use this for bytes that were produced by this
+    /// library and stored where they cannot be modified by others, e.g. in
+    /// stable memory, and never for bytes received from users or the IC.
+    pub fn deserialize_unchecked(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        let (g1_bytes, g2_bytes) = if bytes.len() == Self::BYTES {
+            (G1AFFINE_BYTES, G2AFFINE_BYTES)
+        } else if bytes.len() == 2 * Self::BYTES {
+            (G1AFFINE_UNCOMPRESSED_BYTES, G2AFFINE_UNCOMPRESSED_BYTES)
+        } else {
+            return Err(DeserializationError::InvalidLength);
+        };
+
+        let (c1, rest) = bytes.split_at(g1_bytes);
+        let (c2, c3) = rest.split_at(g2_bytes);
+
+        Ok(Self {
+            c1: deserialize_g1_unchecked(c1)?,
+            c2: deserialize_g2_unchecked(c2)?,
+            c3: deserialize_g1_unchecked(c3)?,
+        })
+    }
+
     /// Deserializes an encrypted key from a byte vector
-    pub fn deserialize(bytes: &[u8]) -> Result<EncryptedVetKey, String> {
-        let ek_bytes: &[u8; Self::BYTES] = bytes.try_into().map_err(|_e: TryFromSliceError| {
-            format!("key not {} bytes but {}", Self::BYTES, bytes.len())
-        })?;
-        Self::deserialize_array(ek_bytes).map_err(|e| format!("{:?}", e))
+    pub fn deserialize(bytes: &[u8]) -> Result<EncryptedVetKey, DeserializationError> {
+        let ek_bytes: &[u8; Self::BYTES] = bytes
+            .try_into()
+            .map_err(|_e| DeserializationError::InvalidLength)?;
+        Self::deserialize_array(ek_bytes)
     }
 
     /// Deserializes an encrypted key from a byte array
-    pub fn deserialize_array(
-        val: &[u8; Self::BYTES],
-    ) -> Result<Self, EncryptedVetKeyDeserializationError> {
+    pub fn deserialize_array(val: &[u8; Self::BYTES]) -> Result<Self, DeserializationError> {
         let c2_start = G1AFFINE_BYTES;
         let c3_start = G1AFFINE_BYTES + G2AFFINE_BYTES;
 
-        let c1_bytes: &[u8; G1AFFINE_BYTES] = &val[..c2_start]
-            .try_into()
-            .map_err(|_e| EncryptedVetKeyDeserializationError::InvalidEncryptedVetKey)?;
-        let c2_bytes: &[u8; G2AFFINE_BYTES] = &val[c2_start..c3_start]
-            .try_into()
-            .map_err(|_e| EncryptedVetKeyDeserializationError::InvalidEncryptedVetKey)?;
-        let c3_bytes: &[u8; G1AFFINE_BYTES] = &val[c3_start..]
-            .try_into()
-            .map_err(|_e| EncryptedVetKeyDeserializationError::InvalidEncryptedVetKey)?;
+        let c1 = deserialize_g1(&val[..c2_start])?;
+        let c2 = deserialize_g2(&val[c2_start..c3_start])?;
+        let c3 = deserialize_g1(&val[c3_start..])?;
 
-        let c1 = option_from_ctoption(G1Affine::from_compressed(c1_bytes));
-        let c2 = option_from_ctoption(G2Affine::from_compressed(c2_bytes));
-        let c3 = option_from_ctoption(G1Affine::from_compressed(c3_bytes));
-
-        match (c1, c2, c3) {
-            (Some(c1), Some(c2), Some(c3)) => Ok(Self { c1, c2, c3 }),
-            (_, _, _) => Err(EncryptedVetKeyDeserializationError::InvalidEncryptedVetKey),
-        }
+        Ok(Self { c1, c2, c3 })
     }
 }
 
@@ -458,9 +573,9 @@ impl IbeSeed {
     ///
     /// If the input is exactly 256 bits it is used directly. Otherwise the input
     /// is hashed with HKDF to produce a 256 bit seed.
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidSeedError> {
         if bytes.len() < 16 {
-            return Err("Insufficient input material for IbeSeed derivation".to_string())
+            return Err(InvalidSeedError::InsufficientInput);
         }
 
         let mut val = [0u8; IBE_SEED_BYTES];
@@ -488,9 +603,19 @@ impl IbeSeed {
  * here are just fixed and effectively arbitrary values, but could be used to
  * indicate for example a version in the future should we need to support multiple
  * variants of the IBE scheme.
+ *
+ * Version 0x02 is used by the hybrid IBE scheme, see the `hybrid_ibe` module.
+ *
+ * Version 0x03 indicates that the associated data was included in the hash
+ * used to derive the mask, see `IbeCiphertext::encrypt_with_associated_data`.
+ *
+ * Version 0x04 is used for ciphertexts with multiple recipients, see the
+ * `multi_recipient_ibe` module.
 */
 const IBE_HEADER: [u8; 8] = [b'I', b'C', b' ', b'I', b'B', b'E', 0x00, 0x01];
 
+const IBE_HEADER_WITH_ASSOCIATED_DATA: [u8; 8] = [b'I', b'C', b' ', b'I', b'B', b'E', 0x00, 0x03];
+
 const IBE_HEADER_BYTES: usize = IBE_HEADER.len();
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -542,9 +667,9 @@ impl IbeCiphertext {
     /// Deserialize an IBE ciphertext
     ///
     /// Returns Err if the encoding is not valid
-    pub fn deserialize(bytes: &[u8]) -> Result<Self, String> {
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
         if bytes.len() < IBE_HEADER_BYTES + G2AFFINE_BYTES + IBE_SEED_BYTES {
-            return Err("IbeCiphertext too short to be valid".to_string());
+            return Err(DeserializationError::InvalidLength);
         }
 
         let header = bytes[0..IBE_HEADER_BYTES].to_vec();
@@ -558,18 +683,28 @@ impl IbeCiphertext {
 
         let c3 = bytes[IBE_HEADER_BYTES + G2AFFINE_BYTES + IBE_SEED_BYTES..].to_vec();
 
-        if header != IBE_HEADER {
-            return Err("IbeCiphertext has unknown header".to_string());
+        if header != IBE_HEADER && header != IBE_HEADER_WITH_ASSOCIATED_DATA {
+            return Err(DeserializationError::UnknownHeader);
         }
 
         Ok(Self { header, c1, c2, c3 })
     }
 
-    fn hash_to_mask(header: &[u8], seed: &[u8; IBE_SEED_BYTES], msg: &[u8]) -> Scalar {
+    fn hash_to_mask(
+        header: &[u8],
+        seed: &[u8; IBE_SEED_BYTES],
+        associated_data: &[u8],
+        msg: &[u8],
+    ) -> Scalar {
         let domain_sep = IbeDomainSep::HashToMask;
-        let mut ro_input = Vec::with_capacity(seed.len() + msg.len());
+        let mut ro_input =
+            Vec::with_capacity(header.len() + seed.len() + 8 + associated_data.len() + msg.len());
         ro_input.extend_from_slice(header);
         ro_input.extend_from_slice(seed);
+        if header == IBE_HEADER_WITH_ASSOCIATED_DATA {
+            ro_input.extend_from_slice(&(associated_data.len() as u64).to_be_bytes());
+            ro_input.extend_from_slice(associated_data);
+        }
         ro_input.extend_from_slice(msg);
 
         hash_to_scalar(&ro_input, &domain_sep.to_string())
@@ -632,13 +767,48 @@ impl IbeCiphertext {
         msg: &[u8],
         seed: &IbeSeed,
     ) -> Self {
-        let header = IBE_HEADER.to_vec();
+        IbeEncryptionContext::new(dpk, identity).encrypt(msg, seed)
+    }
+
+    /// Encrypt a message using IBE, binding the ciphertext to associated data
+    ///
+    /// The associated data is not encrypted and not included in the
+    /// ciphertext, but decryption fails unless exactly the same associated
+    /// data is provided to [`IbeCiphertext::decrypt_with_associated_data`].
+    /// This can be used to bind a ciphertext to the context it is used in (for
+    /// example the sender, or an auction lot) so that it cannot be replayed in
+    /// a different context.
+    ///
+    /// Otherwise the same considerations as for [`IbeCiphertext::encrypt`] apply.
+    pub fn encrypt_with_associated_data(
+        dpk: &DerivedPublicKey,
+        identity: &IbeIdentity,
+        msg: &[u8],
+        associated_data: &[u8],
+        seed: &IbeSeed,
+    ) -> Self {
+        IbeEncryptionContext::new(dpk, identity).encrypt_with_associated_data(
+            msg,
+            associated_data,
+            seed,
+        )
+    }
 
-        let t = Self::hash_to_mask(&header, seed.value(), msg);
+    /// Encrypt using the pairing of the identity with the derived public key
+    ///
+    /// See [`IbeEncryptionContext`]
+    fn encrypt_with_header(
+        header: &[u8],
+        identity_pairing: &Gt,
+        msg: &[u8],
+        associated_data: &[u8],
+        seed: &IbeSeed,
+    ) -> Self {
+        let header = header.to_vec();
 
-        let pt = augmented_hash_to_g1(&dpk.point, identity.value());
+        let t = Self::hash_to_mask(&header, seed.value(), associated_data, msg);
 
-        let tsig = ic_bls12_381::pairing(&pt, &dpk.point) * t;
+        let tsig = identity_pairing * t;
 
         let c1 = G2Affine::from(G2Affine::generator() * t);
         let c2 = Self::mask_seed(seed.value(), &tsig);
@@ -647,6 +817,16 @@ impl IbeCiphertext {
         Self { header, c1, c2, c3 }
     }
 
+    /// Return true if this ciphertext is bound to associated data
+    ///
+    /// This is the case for ciphertexts created with
+    /// [`IbeCiphertext::encrypt_with_associated_data`], even if the associated
+    /// data is empty, and lets applications that require associated data
+    /// reject ciphertexts created with [`IbeCiphertext::encrypt`].
+    pub fn has_associated_data(&self) -> bool {
+        self.header == IBE_HEADER_WITH_ASSOCIATED_DATA
+    }
+
     /// Decrypt an IBE ciphertext
     ///
     /// The VetKey provided must be the VetKey produced by a request to the IC
@@ -654,21 +834,41 @@ impl IbeCiphertext {
     /// values used during encryption.
     ///
     /// Returns the plaintext, or Err if decryption failed
-    pub fn decrypt(&self, vetkey: &VetKey) -> Result<Vec<u8>, String> {
+    pub fn decrypt(&self, vetkey: &VetKey) -> Result<Vec<u8>, DecryptionError> {
+        self.decrypt_with_associated_data(vetkey, &[])
+    }
+
+    /// Decrypt an IBE ciphertext that is bound to associated data
+    ///
+    /// The associated data must be equal to the associated data provided to
+    /// [`IbeCiphertext::encrypt_with_associated_data`]. Ciphertexts created
+    /// with [`IbeCiphertext::encrypt`] can only be decrypted with empty
+    /// associated data.
+    ///
+    /// Returns the plaintext, or Err if decryption failed
+    pub fn decrypt_with_associated_data(
+        &self,
+        vetkey: &VetKey,
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, DecryptionError> {
+        if self.header != IBE_HEADER_WITH_ASSOCIATED_DATA && !associated_data.is_empty() {
+            return Err(DecryptionError::AssociatedDataNotSupported);
+        }
+
         let t = ic_bls12_381::pairing(&vetkey.pt, &self.c1);
 
         let seed = Self::mask_seed(&self.c2, &t);
 
         let msg = Self::mask_msg(&self.c3, &seed);
 
-        let t = Self::hash_to_mask(&self.header, &seed, &msg);
+        let t = Self::hash_to_mask(&self.header, &seed, associated_data, &msg);
 
         let g_t = G2Affine::from(G2Affine::generator() * t);
 
         if self.c1 == g_t {
             Ok(msg)
         } else {
-            Err("decryption failed".to_string())
+            Err(DecryptionError::DecryptionFailed)
         }
     }
 }
@@ -685,30 +885,11 @@ impl IbeCiphertext {
 ///
 /// Returns true if and only if the provided signature is valid with respect to
 /// the provided public key and input
-pub fn verify_bls_signature(dpk: &DerivedPublicKey, input: &[u8], signature: &[u8]) -> bool {
-    let signature: G1Affine = match <[u8; 48]>::try_from(signature) {
-        Ok(bytes) => match option_from_ctoption(G1Affine::from_compressed(&bytes)) {
-            Some(pt) => pt,
-            None => return false,
-        },
-        Err(_) => return false,
-    };
-
-    verify_bls_signature_pt(dpk, input, &signature)
-}
-
-/// Verify an augmented BLS signature
 ///
-/// Returns true if and only if the provided signature is valid with respect to
-/// the provided public key and input
-fn verify_bls_signature_pt(dpk: &DerivedPublicKey, input: &[u8], signature: &G1Affine) -> bool {
-    let msg = augmented_hash_to_g1(&dpk.point, input);
-    let dpk_prep = G2Prepared::from(dpk.point);
-
-    use pairing::group::Group;
-    let is_valid =
-        gt_multipairing(&[(signature, &G2PREPARED_NEG_G), (&msg, &dpk_prep)]).is_identity();
-    bool::from(is_valid)
+/// See [`PreparedDerivedPublicKey::verify_bls_signature`] for verifying
+/// several signatures with respect to the same public key.
+pub fn verify_bls_signature(dpk: &DerivedPublicKey, input: &[u8], signature: &[u8]) -> bool {
+    dpk.prepared().verify_bls_signature(input, signature)
 }
 
 fn augmented_hash_to_g1(pk: &G2Affine, data: &[u8]) -> G1Affine {
@@ -737,15 +918,52 @@ fn option_from_ctoption<T>(ctoption: subtle::CtOption<T>) -> Option<T> {
     }
 }
 
-fn deserialize_g2(bytes: &[u8]) -> Result<G2Affine, String> {
-    let bytes: &[u8; G2AFFINE_BYTES] = bytes
+fn deserialize_g1(bytes: &[u8]) -> Result<G1Affine, DeserializationError> {
+    let bytes: &[u8; G1AFFINE_BYTES] = bytes
         .try_into()
-        .map_err(|_| "Invalid length for G2".to_string())?;
+        .map_err(|_| DeserializationError::InvalidLength)?;
+
+    option_from_ctoption(G1Affine::from_compressed(bytes)).ok_or(DeserializationError::InvalidPoint)
+}
 
-    let pt = G2Affine::from_compressed(bytes);
-    if bool::from(pt.is_some()) {
-        Ok(pt.unwrap())
+/// Deserialize a G1 point without the subgroup check
+///
+/// Accepts both the compressed and the uncompressed encoding. The point is
+/// guaranteed to be on the curve, but may not be in the prime order subgroup.
+fn deserialize_g1_unchecked(bytes: &[u8]) -> Result<G1Affine, DeserializationError> {
+    let pt = if let Ok(bytes) = <&[u8; G1AFFINE_BYTES]>::try_from(bytes) {
+        G1Affine::from_compressed_unchecked(bytes)
+    } else if let Ok(bytes) = <&[u8; G1AFFINE_UNCOMPRESSED_BYTES]>::try_from(bytes) {
+        G1Affine::from_uncompressed_unchecked(bytes)
+            .and_then(|pt| CtOption::new(pt, pt.is_on_curve()))
     } else {
-        Err("Invalid G2 elliptic curve point".to_string())
-    }
+        return Err(DeserializationError::InvalidLength);
+    };
+
+    option_from_ctoption(pt).ok_or(DeserializationError::InvalidPoint)
+}
+
+/// Deserialize a G2 point without the subgroup check
+///
+/// Accepts both the compressed and the uncompressed encoding. The point is
+/// guaranteed to be on the curve, but may not be in the prime order subgroup.
+fn deserialize_g2_unchecked(bytes: &[u8]) -> Result<G2Affine, DeserializationError> {
+    let pt = if let Ok(bytes) = <&[u8; G2AFFINE_BYTES]>::try_from(bytes) {
+        G2Affine::from_compressed_unchecked(bytes)
+    } else if let Ok(bytes) = <&[u8; G2AFFINE_UNCOMPRESSED_BYTES]>::try_from(bytes) {
+        G2Affine::from_uncompressed_unchecked(bytes)
+            .and_then(|pt| CtOption::new(pt, pt.is_on_curve()))
+    } else {
+        return Err(DeserializationError::InvalidLength);
+    };
+
+    option_from_ctoption(pt).ok_or(DeserializationError::InvalidPoint)
+}
+
+fn deserialize_g2(bytes: &[u8]) -> Result<G2Affine, DeserializationError> {
+    let bytes: &[u8; G2AFFINE_BYTES] = bytes
+        .try_into()
+        .map_err(|_| DeserializationError::InvalidLength)?;
+
+    option_from_ctoption(G2Affine::from_compressed(bytes)).ok_or(DeserializationError::InvalidPoint)
 }
@@ -0,0 +1,219 @@
+//! Identity based encryption of a single message to multiple identities
+//!
+//! The message is masked once using a random seed, and the seed is masked
+//! separately for each recipient identity. All recipients share the same
+//! `c1 = g2*t`, which is safe since the identities, and thus the pairing
+//! values used to mask the seed, are distinct. Each recipient slot starts with
+//! a pseudorandom hint derived from the recipient's pairing value, which allows
+//! a recipient to locate its slot without revealing the recipient identities.
+//!
+//! Note that a recipient learns the seed, and thus could create a ciphertext
+//! for a different set of recipients with the same message. As with
+//! [`IbeCiphertext`], the sender of a ciphertext is not authenticated.
+
+use super::{
+    augmented_hash_to_g1, derive_symmetric_key, deserialize_g2, gt_multipairing, DecryptionError,
+    DerivedPublicKey, DeserializationError, IbeCiphertext, IbeIdentity, IbeSeed, VetKey,
+    G2AFFINE_BYTES, IBE_SEED_BYTES,
+};
+use ic_bls12_381::{G2Affine, G2Prepared, Gt};
+
+/*
+ * Same as the IBE header, but with version 0x04 to indicate a multi-recipient ciphertext
+ */
+const IBE_MULTI_RECIPIENT_HEADER: [u8; 8] = [b'I', b'C', b' ', b'I', b'B', b'E', 0x00, 0x04];
+
+const IBE_RECIPIENT_HINT_BYTES: usize = 16;
+
+const IBE_RECIPIENT_SLOT_BYTES: usize = IBE_RECIPIENT_HINT_BYTES + IBE_SEED_BYTES;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct IbeRecipientSlot {
+    hint: [u8; IBE_RECIPIENT_HINT_BYTES],
+    masked_seed: [u8; IBE_SEED_BYTES],
+}
+
+impl IbeRecipientSlot {
+    fn new(seed: &[u8; IBE_SEED_BYTES], t: &Gt) -> Self {
+        Self {
+            hint: Self::hint(t),
+            masked_seed: IbeCiphertext::mask_seed(seed, t),
+        }
+    }
+
+    fn hint(t: &Gt) -> [u8; IBE_RECIPIENT_HINT_BYTES] {
+        let hint = derive_symmetric_key(
+            &t.to_bytes(),
+            "ic-vetkd-bls12-381-ibe-recipient-hint",
+            IBE_RECIPIENT_HINT_BYTES,
+        );
+        hint.try_into().expect("hint has the expected length")
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// An IBE (identity based encryption) ciphertext for multiple recipients
+pub struct IbeMultiRecipientCiphertext {
+    header: Vec<u8>,
+    c1: G2Affine,
+    slots: Vec<IbeRecipientSlot>,
+    c3: Vec<u8>,
+}
+
+impl IbeMultiRecipientCiphertext {
+    /// Serialize this multi-recipient IBE ciphertext
+    ///
+    /// The encoding consists of the header, `c1`, the number of recipient
+    /// slots as a 32-bit big endian integer, the recipient slots, and the
+    /// masked message.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(
+            self.header.len()
+                + G2AFFINE_BYTES
+                + 4
+                + self.slots.len() * IBE_RECIPIENT_SLOT_BYTES
+                + self.c3.len(),
+        );
+
+        output.extend_from_slice(&self.header);
+        output.extend_from_slice(&self.c1.to_compressed());
+        output.extend_from_slice(&(self.slots.len() as u32).to_be_bytes());
+        for slot in &self.slots {
+            output.extend_from_slice(&slot.hint);
+            output.extend_from_slice(&slot.masked_seed);
+        }
+        output.extend_from_slice(&self.c3);
+
+        output
+    }
+
+    /// Deserialize a multi-recipient IBE ciphertext
+    ///
+    /// Returns Err if the encoding is not valid
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        let header_bytes = IBE_MULTI_RECIPIENT_HEADER.len();
+        if bytes.len() < header_bytes + G2AFFINE_BYTES + 4 {
+            return Err(DeserializationError::InvalidLength);
+        }
+
+        let (header, rest) = bytes.split_at(header_bytes);
+        if header != IBE_MULTI_RECIPIENT_HEADER {
+            return Err(DeserializationError::UnknownHeader);
+        }
+
+        let (c1, rest) = rest.split_at(G2AFFINE_BYTES);
+        let c1 = deserialize_g2(c1)?;
+
+        let (slot_count, rest) = rest.split_at(4);
+        let slot_count = u32::from_be_bytes(slot_count.try_into().expect("length already checked"));
+
+        let slots_bytes = (slot_count as usize)
+            .checked_mul(IBE_RECIPIENT_SLOT_BYTES)
+            .filter(|slots_bytes| *slots_bytes <= rest.len())
+            .ok_or(DeserializationError::InvalidLength)?;
+        let (slots, c3) = rest.split_at(slots_bytes);
+
+        let slots = slots
+            .chunks_exact(IBE_RECIPIENT_SLOT_BYTES)
+            .map(|slot| {
+                let (hint, masked_seed) = slot.split_at(IBE_RECIPIENT_HINT_BYTES);
+                IbeRecipientSlot {
+                    hint: hint.try_into().expect("length already checked"),
+                    masked_seed: masked_seed.try_into().expect("length already checked"),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            header: header.to_vec(),
+            c1,
+            slots,
+            c3: c3.to_vec(),
+        })
+    }
+
+    /// Return the number of recipient slots of this ciphertext
+    pub fn recipient_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Encrypt a message to multiple identities using IBE
+    ///
+    /// The message can be of arbitrary length and is included in the
+    /// ciphertext only once.
+    ///
+    /// The seed should be generated with a cryptographically secure random
+    /// number generator. Do not reuse the seed for encrypting another message
+    /// or any other purpose.
+    ///
+    /// The message can be decrypted using the VetKey associated with the
+    /// provided derived public key (ie the same master key and context string)
+    /// and with an `input` equal to any of the provided `identities`.
+    /// Duplicate identities are encrypted to only once.
+    pub fn encrypt(
+        dpk: &DerivedPublicKey,
+        identities: &[IbeIdentity],
+        msg: &[u8],
+        seed: &IbeSeed,
+    ) -> Self {
+        let header = IBE_MULTI_RECIPIENT_HEADER.to_vec();
+
+        let t = IbeCiphertext::hash_to_mask(&header, seed.value(), &[], msg);
+
+        let mut unique_identities: Vec<&[u8]> = identities.iter().map(|id| id.value()).collect();
+        unique_identities.sort_unstable();
+        unique_identities.dedup();
+
+        let dpk_prepared = G2Prepared::from(dpk.point);
+        let slots = unique_identities
+            .into_iter()
+            .map(|identity| {
+                let pt = augmented_hash_to_g1(&dpk.point, identity);
+                let tsig = gt_multipairing(&[(&pt, &dpk_prepared)]) * t;
+                IbeRecipientSlot::new(seed.value(), &tsig)
+            })
+            .collect();
+
+        let c1 = G2Affine::from(G2Affine::generator() * t);
+        let c3 = IbeCiphertext::mask_msg(msg, seed.value());
+
+        Self {
+            header,
+            c1,
+            slots,
+            c3,
+        }
+    }
+
+    /// Decrypt a multi-recipient IBE ciphertext
+    ///
+    /// The VetKey provided must be the VetKey produced by a request to the IC
+    /// for one of the recipient identities (aka `input`) and a `context`
+    /// matching the value used during encryption.
+    ///
+    /// Returns the plaintext, or Err if decryption failed
+    pub fn decrypt(&self, vetkey: &VetKey) -> Result<Vec<u8>, DecryptionError> {
+        let t = ic_bls12_381::pairing(&vetkey.pt, &self.c1);
+        let hint = IbeRecipientSlot::hint(&t);
+
+        let slot = self
+            .slots
+            .iter()
+            .find(|slot| slot.hint == hint)
+            .ok_or(DecryptionError::NoMatchingRecipient)?;
+
+        let seed = IbeCiphertext::mask_seed(&slot.masked_seed, &t);
+
+        let msg = IbeCiphertext::mask_msg(&self.c3, &seed);
+
+        let t = IbeCiphertext::hash_to_mask(&self.header, &seed, &[], &msg);
+
+        let g_t = G2Affine::from(G2Affine::generator() * t);
+
+        if self.c1 == g_t {
+            Ok(msg)
+        } else {
+            Err(DecryptionError::DecryptionFailed)
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Derived public keys with precomputation for repeated use
+//!
+//! Verifying a BLS signature (and thus a VetKey) requires preprocessing the
+//! derived public key for the pairing, and IBE encryption requires pairing
+//! the identity with the derived public key. When verifying or encrypting
+//! many times under the same key, this work can be done only once.
+
+use super::{
+    augmented_hash_to_g1, deserialize_g1, gt_multipairing, DerivedPublicKey, IbeCiphertext,
+    IbeIdentity, IbeSeed, G2PREPARED_NEG_G, IBE_HEADER, IBE_HEADER_WITH_ASSOCIATED_DATA,
+};
+use ic_bls12_381::{G1Affine, G2Prepared, Gt};
+
+#[derive(Clone)]
+/// A derived public key, preprocessed for verifying signatures and VetKeys
+///
+/// See [`DerivedPublicKey::prepared`]
+pub struct PreparedDerivedPublicKey {
+    dpk: DerivedPublicKey,
+    prepared: G2Prepared,
+}
+
+impl DerivedPublicKey {
+    /// Preprocess this derived public key for repeated use
+    pub fn prepared(&self) -> PreparedDerivedPublicKey {
+        PreparedDerivedPublicKey {
+            dpk: self.clone(),
+            prepared: G2Prepared::from(self.point),
+        }
+    }
+}
+
+impl From<DerivedPublicKey> for PreparedDerivedPublicKey {
+    fn from(dpk: DerivedPublicKey) -> Self {
+        let prepared = G2Prepared::from(dpk.point);
+        Self { dpk, prepared }
+    }
+}
+
+impl PreparedDerivedPublicKey {
+    /// Return the derived public key
+    pub fn public_key(&self) -> &DerivedPublicKey {
+        &self.dpk
+    }
+
+    /// Verify an augmented BLS signature
+    ///
+    /// Same as [`verify_bls_signature`](super::verify_bls_signature), but
+    /// without preprocessing the public key again.
+    pub fn verify_bls_signature(&self, input: &[u8], signature: &[u8]) -> bool {
+        match deserialize_g1(signature) {
+            Ok(signature) => self.verify_pt(input, &signature),
+            Err(_) => false,
+        }
+    }
+
+    /// Create a context for encrypting messages to `identity`
+    pub fn encryption_context(&self, identity: &IbeIdentity) -> IbeEncryptionContext {
+        let pt = augmented_hash_to_g1(&self.dpk.point, identity.value());
+        IbeEncryptionContext {
+            dpk: self.dpk.clone(),
+            identity: identity.clone(),
+            identity_pairing: gt_multipairing(&[(&pt, &self.prepared)]),
+        }
+    }
+
+    pub(crate) fn g2_prepared(&self) -> &G2Prepared {
+        &self.prepared
+    }
+
+    pub(crate) fn verify_pt(&self, input: &[u8], signature: &G1Affine) -> bool {
+        use pairing::group::Group;
+
+        let msg = augmented_hash_to_g1(&self.dpk.point, input);
+        let is_valid = gt_multipairing(&[(signature, &G2PREPARED_NEG_G), (&msg, &self.prepared)])
+            .is_identity();
+        bool::from(is_valid)
+    }
+}
+
+#[derive(Clone)]
+/// A context for encrypting many messages to the same identity using IBE
+///
+/// The pairing of the identity with the derived public key is computed once
+/// when the context is created, which makes each encryption considerably
+/// cheaper than [`IbeCiphertext::encrypt`]. The ciphertexts are identical to
+/// the ones created by [`IbeCiphertext::encrypt`] and
+/// [`IbeCiphertext::encrypt_with_associated_data`].
+pub struct IbeEncryptionContext {
+    dpk: DerivedPublicKey,
+    identity: IbeIdentity,
+    identity_pairing: Gt,
+}
+
+impl IbeEncryptionContext {
+    /// Create a context for encrypting messages to `identity`
+    ///
+    /// See also [`PreparedDerivedPublicKey::encryption_context`]
+    pub fn new(dpk: &DerivedPublicKey, identity: &IbeIdentity) -> Self {
+        let pt = augmented_hash_to_g1(&dpk.point, identity.value());
+        Self {
+            dpk: dpk.clone(),
+            identity: identity.clone(),
+            identity_pairing: ic_bls12_381::pairing(&pt, &dpk.point),
+        }
+    }
+
+    /// Return the derived public key messages are encrypted under
+    pub fn public_key(&self) -> &DerivedPublicKey {
+        &self.dpk
+    }
+
+    /// Return the identity messages are encrypted to
+    pub fn identity(&self) -> &IbeIdentity {
+        &self.identity
+    }
+
+    /// Encrypt a message using IBE
+    ///
+    /// See [`IbeCiphertext::encrypt`]
+    pub fn encrypt(&self, msg: &[u8], seed: &IbeSeed) -> IbeCiphertext {
+        self.encrypt_with_header(&IBE_HEADER, msg, &[], seed)
+    }
+
+    /// Encrypt a message using IBE, binding the ciphertext to associated data
+    ///
+    /// See [`IbeCiphertext::encrypt_with_associated_data`]
+    pub fn encrypt_with_associated_data(
+        &self,
+        msg: &[u8],
+        associated_data: &[u8],
+        seed: &IbeSeed,
+    ) -> IbeCiphertext {
+        self.encrypt_with_header(&IBE_HEADER_WITH_ASSOCIATED_DATA, msg, associated_data, seed)
+    }
+
+    pub(super) fn encrypt_with_header(
+        &self,
+        header: &[u8],
+        msg: &[u8],
+        associated_data: &[u8],
+        seed: &IbeSeed,
+    ) -> IbeCiphertext {
+        IbeCiphertext::encrypt_with_header(
+            header,
+            &self.identity_pairing,
+            msg,
+            associated_data,
+            seed,
+        )
+    }
+}
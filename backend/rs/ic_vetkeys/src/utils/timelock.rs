@@ -0,0 +1,176 @@
+//! Time-lock encryption based on IBE
+//!
+//! A message is time-locked by encrypting it to an identity derived from a
+//! point in time. Once that point in time has passed, a canister derives the
+//! VetKey for that identity and publishes it, which allows anyone to decrypt
+//! all messages time-locked to that point in time.
+//!
+//! Points in time are nanoseconds since the UNIX epoch, as returned by
+//! `ic_cdk::api::time`. To limit the number of keys that need to be derived,
+//! time is divided into rounds of a fixed granularity and each timestamp is
+//! rounded up to the end of its round, so a message is never released before
+//! the requested time.
+
+use super::{
+    DecryptionError, DerivedPublicKey, DeserializationError, IbeCiphertext, IbeIdentity, IbeSeed,
+    VetKey,
+};
+
+/*
+ * Domain separator prefixed to the encoding of time-lock identities, so that
+ * they cannot collide with identities used for other purposes
+ */
+const TIMELOCK_IDENTITY_PREFIX: &[u8] = b"ic-vetkeys-timelock";
+
+/*
+ * Header of a serialized time-lock ciphertext, followed by the granularity
+ * and the round, each as a 64-bit big endian integer, and the IBE ciphertext
+ */
+const TIMELOCK_HEADER: [u8; 8] = [b'I', b'C', b' ', b'T', b'L', b'E', 0x00, 0x01];
+
+const TIMELOCK_PREFIX_BYTES: usize = TIMELOCK_HEADER.len() + 8 + 8;
+
+/// Return the round that `timestamp` belongs to, rounding up
+///
+/// Panics if `granularity` is zero
+pub(crate) fn timelock_round(timestamp: u64, granularity: u64) -> u64 {
+    assert!(granularity > 0, "time-lock granularity must be positive");
+    timestamp.div_ceil(granularity)
+}
+
+/// Return the time at which `round` ends, or None if it is not representable
+pub(crate) fn timelock_round_end(round: u64, granularity: u64) -> Option<u64> {
+    round.checked_mul(granularity)
+}
+
+impl IbeIdentity {
+    /// Create the time-lock identity for a point in time
+    ///
+    /// The timestamp is rounded up to a multiple of `granularity` (both in
+    /// nanoseconds), so all timestamps within the same round map to the same
+    /// identity. The identity is encoded canonically as a fixed prefix,
+    /// followed by the granularity and the round number, each as a 64-bit
+    /// big endian integer.
+    ///
+    /// Panics if `granularity` is zero
+    pub fn from_timestamp(timestamp: u64, granularity: u64) -> Self {
+        Self::from_round(timelock_round(timestamp, granularity), granularity)
+    }
+
+    fn from_round(round: u64, granularity: u64) -> Self {
+        let mut identity = Vec::with_capacity(TIMELOCK_IDENTITY_PREFIX.len() + 8 + 8);
+        identity.extend_from_slice(TIMELOCK_IDENTITY_PREFIX);
+        identity.extend_from_slice(&granularity.to_be_bytes());
+        identity.extend_from_slice(&round.to_be_bytes());
+        Self::from_bytes(&identity)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A message time-locked to a point in time
+///
+/// This is an [`IbeCiphertext`] for the time-lock identity of the point in
+/// time (see [`IbeIdentity::from_timestamp`]) that additionally records the
+/// point in time, so that the recipient knows which key to request.
+pub struct TimelockCiphertext {
+    granularity: u64,
+    round: u64,
+    ciphertext: IbeCiphertext,
+}
+
+impl TimelockCiphertext {
+    /// Time-lock a message until `unlock_time`
+    ///
+    /// The unlock time is rounded up to a multiple of `granularity`, see
+    /// [`IbeIdentity::from_timestamp`]. Otherwise the same considerations as
+    /// for [`IbeCiphertext::encrypt`] apply.
+    ///
+    /// Panics if `granularity` is zero
+    pub fn encrypt(
+        dpk: &DerivedPublicKey,
+        unlock_time: u64,
+        granularity: u64,
+        msg: &[u8],
+        seed: &IbeSeed,
+    ) -> Self {
+        let round = timelock_round(unlock_time, granularity);
+        let identity = IbeIdentity::from_round(round, granularity);
+        Self {
+            granularity,
+            round,
+            ciphertext: IbeCiphertext::encrypt(dpk, &identity, msg, seed),
+        }
+    }
+
+    /// Return the time after which this message can be decrypted
+    ///
+    /// Returns None if the rounded up unlock time is not representable, in
+    /// which case the message can never be decrypted.
+    pub fn unlock_time(&self) -> Option<u64> {
+        timelock_round_end(self.round, self.granularity)
+    }
+
+    /// Return the granularity used to round the unlock time
+    pub fn granularity(&self) -> u64 {
+        self.granularity
+    }
+
+    /// Return the IBE identity whose VetKey decrypts this message
+    pub fn identity(&self) -> IbeIdentity {
+        IbeIdentity::from_round(self.round, self.granularity)
+    }
+
+    /// Serialize this time-lock ciphertext
+    pub fn serialize(&self) -> Vec<u8> {
+        let ciphertext = self.ciphertext.serialize();
+        let mut output = Vec::with_capacity(TIMELOCK_PREFIX_BYTES + ciphertext.len());
+
+        output.extend_from_slice(&TIMELOCK_HEADER);
+        output.extend_from_slice(&self.granularity.to_be_bytes());
+        output.extend_from_slice(&self.round.to_be_bytes());
+        output.extend_from_slice(&ciphertext);
+
+        output
+    }
+
+    /// Deserialize a time-lock ciphertext
+    ///
+    /// Returns Err if the encoding is not valid
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, DeserializationError> {
+        if bytes.len() < TIMELOCK_PREFIX_BYTES {
+            return Err(DeserializationError::InvalidLength);
+        }
+
+        let (header, rest) = bytes.split_at(TIMELOCK_HEADER.len());
+        if header != TIMELOCK_HEADER {
+            return Err(DeserializationError::UnknownHeader);
+        }
+
+        let (granularity, rest) = rest.split_at(8);
+        let granularity =
+            u64::from_be_bytes(granularity.try_into().expect("length already checked"));
+        if granularity == 0 {
+            return Err(DeserializationError::InvalidValue);
+        }
+
+        let (round, ciphertext) = rest.split_at(8);
+        let round = u64::from_be_bytes(round.try_into().expect("length already checked"));
+
+        Ok(Self {
+            granularity,
+            round,
+            ciphertext: IbeCiphertext::deserialize(ciphertext)?,
+        })
+    }
+
+    /// Decrypt a time-locked message
+    ///
+    /// The VetKey provided must be the VetKey for [`TimelockCiphertext::identity`]
+    /// and a `context` matching the value used during encryption, as
+    /// published once the unlock time has passed.
+    ///
+    /// Returns the plaintext, or Err if decryption failed
+    pub fn decrypt(&self, vetkey: &VetKey) -> Result<Vec<u8>, DecryptionError> {
+        self.ciphertext.decrypt(vetkey)
+    }
+}
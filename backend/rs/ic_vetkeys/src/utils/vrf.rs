@@ -0,0 +1,60 @@
+//! Verifiable random function (VRF) based on VetKD
+//!
+//! A VetKey is a BLS signature, which is unique for a given derived public
+//! key and input. Hashing it thus gives pseudorandom output that nobody can
+//! predict before the VetKey is derived, and that anyone can verify against
+//! the derived public key, using the VetKey as the proof.
+
+use super::{verify_bls_signature, DerivedPublicKey, VerificationError, VetKey};
+
+const VRF_RANDOMNESS_BYTES: usize = 32;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// The output of the VetKD based VRF
+pub struct VrfOutput {
+    proof: VetKey,
+    randomness: [u8; VRF_RANDOMNESS_BYTES],
+}
+
+impl VrfOutput {
+    /// Create the VRF output for a VetKey
+    ///
+    /// The VetKey must have been verified against the derived public key and
+    /// input, e.g. by [`crate::EncryptedVetKey::decrypt_and_verify`].
+    pub fn from_vetkey(vetkey: VetKey) -> Self {
+        let randomness = vetkey
+            .derive_symmetric_key("ic-vetkd-bls12-381-vrf-randomness", VRF_RANDOMNESS_BYTES)
+            .as_slice()
+            .try_into()
+            .expect("randomness has the expected length");
+        Self {
+            proof: vetkey,
+            randomness,
+        }
+    }
+
+    /// Verify a VRF proof for `input` against the derived public key
+    ///
+    /// Returns the VRF output, or Err if the proof is invalid
+    pub fn verify(
+        dpk: &DerivedPublicKey,
+        input: &[u8],
+        proof: &[u8],
+    ) -> Result<Self, VerificationError> {
+        if !verify_bls_signature(dpk, input, proof) {
+            return Err(VerificationError::InvalidSignature);
+        }
+        let proof = VetKey::deserialize(proof).expect("valid signature is a valid VetKey");
+        Ok(Self::from_vetkey(proof))
+    }
+
+    /// Return the proof, i.e. the bytes of the VetKey
+    pub fn proof(&self) -> &[u8; 48] {
+        self.proof.signature_bytes()
+    }
+
+    /// Return the pseudorandom output
+    pub fn randomness(&self) -> &[u8; VRF_RANDOMNESS_BYTES] {
+        &self.randomness
+    }
+}
@@ -0,0 +1,230 @@
+//! The vetKD API used by [`crate::key_manager::KeyManager`],
+//! [`crate::encrypted_maps::EncryptedMaps`], [`crate::timelock::Timelock`],
+//! [`crate::random_beacon::RandomBeacon`] and
+//! [`crate::threshold_bls::ThresholdBlsSigner`].
+//!
+//! In a canister, [`ManagementCanisterVetKdApi`] calls the vetKD methods of the
+//! management canister. Tests can provide a different [`VetKdApi`], such as
+//! the in-memory implementation in `ic_vetkeys_test_utils::mock_vetkd`, to
+//! run the async code paths natively with `cargo test`.
+
+use crate::key_manager::bls12_381_dfx_test_key;
+use crate::vetkd_api_types::{
+    VetKDDeriveKeyReply, VetKDDeriveKeyRequest, VetKDPublicKeyReply, VetKDPublicKeyRequest,
+};
+use futures::future::FutureExt;
+use ic_cdk::api::management_canister::main::CanisterId;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The future returned by the methods of [`VetKdApi`].
+///
+/// It does not borrow the [`VetKdApi`], so it can be awaited after the
+/// `RefCell` holding the `KeyManager` has been released.
+pub type VetKdApiFuture<T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send + Sync>>;
+
+/// The cycles attached to a `vetkd_derive_key` call.
+const VETKD_DERIVE_KEY_CYCLES: u128 = 26_153_846_153;
+
+/// An implementation of the vetKD methods `vetkd_public_key` and
+/// `vetkd_derive_key`, and optionally of `raw_rand` for the transport keys of
+/// vetKeys that are decrypted by the canister itself.
+pub trait VetKdApi {
+    /// Obtains the derived public key for the given request.
+    fn vetkd_public_key(
+        &self,
+        request: VetKDPublicKeyRequest,
+    ) -> VetKdApiFuture<VetKDPublicKeyReply>;
+
+    /// Obtains an encrypted vetKey for the given request.
+    fn vetkd_derive_key(
+        &self,
+        request: VetKDDeriveKeyRequest,
+    ) -> VetKdApiFuture<VetKDDeriveKeyReply>;
+
+    /// Obtains 32 bytes of randomness, see [`random_transport_secret_key`].
+    ///
+    /// Calls the management canister's `raw_rand` unless overridden, e.g. by
+    /// an implementation that runs outside of a canister.
+    fn raw_rand(&self) -> VetKdApiFuture<[u8; 32]> {
+        Box::pin(
+            ic_cdk::api::management_canister::main::raw_rand().map(|call_result| {
+                let (bytes,) =
+                    call_result.map_err(|e| format!("call to raw_rand failed: {e:?}"))?;
+                <[u8; 32]>::try_from(bytes)
+                    .map_err(|bytes| format!("invalid raw_rand output of {} bytes", bytes.len()))
+            }),
+        )
+    }
+}
+
+/// Calls the vetKD methods of the management canister, or of a canister that
+/// emulates them, such as `ic_vetkeys_mock_vetkd_canister`.
+/// Randomness is always obtained from the management canister's `raw_rand`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManagementCanisterVetKdApi {
+    canister_id: CanisterId,
+}
+
+impl ManagementCanisterVetKdApi {
+    /// Calls the canister `canister_id` instead of the management canister.
+    pub fn with_canister_id(canister_id: CanisterId) -> Self {
+        Self { canister_id }
+    }
+
+    /// The id of the canister that is called.
+    pub fn canister_id(&self) -> CanisterId {
+        self.canister_id
+    }
+}
+
+impl Default for ManagementCanisterVetKdApi {
+    fn default() -> Self {
+        Self::with_canister_id(crate::key_manager::vetkd_system_api_canister_id())
+    }
+}
+
+impl VetKdApi for ManagementCanisterVetKdApi {
+    fn vetkd_public_key(
+        &self,
+        request: VetKDPublicKeyRequest,
+    ) -> VetKdApiFuture<VetKDPublicKeyReply> {
+        Box::pin(
+            ic_cdk::api::call::call::<_, (VetKDPublicKeyReply,)>(
+                self.canister_id,
+                "vetkd_public_key",
+                (request,),
+            )
+            .map(|call_result| {
+                call_result
+                    .map(|(reply,)| reply)
+                    .map_err(|e| format!("call to vetkd_public_key failed: {e:?}"))
+            }),
+        )
+    }
+
+    fn vetkd_derive_key(
+        &self,
+        request: VetKDDeriveKeyRequest,
+    ) -> VetKdApiFuture<VetKDDeriveKeyReply> {
+        Box::pin(
+            ic_cdk::api::call::call_with_payment128::<_, (VetKDDeriveKeyReply,)>(
+                self.canister_id,
+                "vetkd_derive_key",
+                (request,),
+                VETKD_DERIVE_KEY_CYCLES,
+            )
+            .map(|call_result| {
+                call_result
+                    .map(|(reply,)| reply)
+                    .map_err(|e| format!("call to vetkd_derive_key failed: {e:?}"))
+            }),
+        )
+    }
+}
+
+/// Creates a transport secret key for decrypting vetKeys inside the canister.
+///
+/// The key is generated from randomness obtained from [`VetKdApi::raw_rand`],
+/// i.e. the management canister's `raw_rand` in a canister, so it cannot be
+/// predicted before the call and is not exposed to the canister's users. It
+/// is, however, part of the canister's state and thus visible to the nodes of
+/// the subnet, which can therefore decrypt vetKeys fetched with it.
+pub fn random_transport_secret_key(
+    vetkd_api: &impl VetKdApi,
+) -> impl Future<Output = Result<crate::TransportSecretKey, String>> + Send + Sync {
+    vetkd_api.raw_rand().map(|seed| {
+        crate::TransportSecretKey::from_seed(seed?.to_vec())
+            .map_err(|e| format!("invalid raw_rand output: {e}"))
+    })
+}
+
+/// Returns the zero-knowledge transport secret key, a fixed key that is
+/// publicly known: its secret scalar is 1, so its public key is the generator
+/// of G1.
+///
+/// A vetKey fetched with this key is effectively not encrypted, since anyone
+/// who sees the encrypted vetKey, e.g. the nodes of the subnet or observers
+/// of the call, can decrypt it. Only use it for vetKeys that are public
+/// anyway, such as BLS signatures. In exchange, no randomness needs to be
+/// obtained before fetching the vetKey.
+pub fn zero_knowledge_transport_secret_key() -> crate::TransportSecretKey {
+    let mut one = [0; 32];
+    one[0] = 1; // little-endian
+    crate::TransportSecretKey::deserialize(&one).expect("1 is a valid scalar")
+}
+
+/// Derives a vetKey that is not meant to be kept secret, such as a BLS
+/// signature or a published time-lock decryption key.
+///
+/// The vetKey is fetched using a transport key from
+/// [`random_transport_secret_key`] and verified against the derived public
+/// key of `context`. The returned future holds a clone of `vetkd_api`, so that
+/// its owner does not need to be borrowed across the calls.
+pub fn derive_public_vetkey(
+    vetkd_api: &(impl VetKdApi + Clone + Send + Sync),
+    context: Vec<u8>,
+    input: Vec<u8>,
+) -> impl Future<Output = Result<crate::VetKey, String>> + Send + Sync {
+    derive_and_verify_vetkey(
+        vetkd_api,
+        context,
+        input,
+        random_transport_secret_key(vetkd_api),
+    )
+}
+
+/// Like [`derive_public_vetkey`], but fetches the vetKey with the
+/// [`zero_knowledge_transport_secret_key`] instead of a random transport key,
+/// so that anyone who sees the encrypted vetKey can decrypt it.
+pub fn derive_public_vetkey_with_zero_knowledge_transport_key(
+    vetkd_api: &(impl VetKdApi + Clone + Send + Sync),
+    context: Vec<u8>,
+    input: Vec<u8>,
+) -> impl Future<Output = Result<crate::VetKey, String>> + Send + Sync {
+    derive_and_verify_vetkey(
+        vetkd_api,
+        context,
+        input,
+        std::future::ready(Ok(zero_knowledge_transport_secret_key())),
+    )
+}
+
+fn derive_and_verify_vetkey(
+    vetkd_api: &(impl VetKdApi + Clone + Send + Sync),
+    context: Vec<u8>,
+    input: Vec<u8>,
+    transport_secret_key_future: impl Future<Output = Result<crate::TransportSecretKey, String>>
+        + Send
+        + Sync,
+) -> impl Future<Output = Result<crate::VetKey, String>> + Send + Sync {
+    let public_key_request = VetKDPublicKeyRequest {
+        canister_id: None,
+        context: context.clone(),
+        key_id: bls12_381_dfx_test_key(),
+    };
+    let public_key_future = vetkd_api.vetkd_public_key(public_key_request);
+    let vetkd_api = vetkd_api.clone();
+
+    async move {
+        let transport_secret_key = transport_secret_key_future.await?;
+        let public_key_reply = public_key_future.await?;
+
+        let derive_key_request = VetKDDeriveKeyRequest {
+            input: input.clone(),
+            context,
+            key_id: bls12_381_dfx_test_key(),
+            transport_public_key: transport_secret_key.public_key(),
+        };
+
+        let derive_key_reply = vetkd_api.vetkd_derive_key(derive_key_request).await?;
+
+        let derived_public_key = crate::DerivedPublicKey::deserialize(&public_key_reply.public_key)
+            .map_err(|e| format!("invalid derived public key: {e}"))?;
+
+        crate::EncryptedVetKey::deserialize(&derive_key_reply.encrypted_key)
+            .map_err(|e| format!("invalid encrypted key: {e}"))?
+            .decrypt_and_verify(&transport_secret_key, &derived_public_key, &input)
+            .map_err(|e| format!("invalid encrypted key: {e}"))
+    }
+}
@@ -0,0 +1,12 @@
+use ic_vetkeys_test_utils::access_control_model::EncryptedMapsStateMachine;
+use proptest::prelude::ProptestConfig;
+use proptest_state_machine::prop_state_machine;
+
+prop_state_machine! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn encrypted_maps_should_follow_access_control_model(
+        sequential 1..64 => EncryptedMapsStateMachine
+    );
+}
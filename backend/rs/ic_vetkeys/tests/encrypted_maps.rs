@@ -15,8 +15,14 @@ use ic_vetkeys_test_utils::{
 use rand::{CryptoRng, Rng};
 use strum::IntoEnumIterator;
 
+use futures::executor::block_on;
 use ic_vetkeys::encrypted_maps::EncryptedMaps;
-use ic_vetkeys::types::{AccessControl, AccessRights};
+use ic_vetkeys::key_manager::key_id_to_vetkd_input;
+use ic_vetkeys::types::{
+    AccessControl, AccessRights, CorruptEntry, RemovedMapValues, StoredValue, TransportKey,
+};
+use ic_vetkeys::{DerivedPublicKey, EncryptedVetKey, TransportSecretKey};
+use ic_vetkeys_test_utils::mock_vetkd::{InMemoryVetKdApi, MockVetKd};
 
 #[test]
 fn can_init_memory() {
@@ -31,7 +37,7 @@ fn can_remove_map_values() {
     let name = random_name(rng);
     let mut encrypted_maps = random_encrypted_maps(rng);
     let result = encrypted_maps.remove_map_values(caller, (caller, name));
-    assert_eq!(result, Ok(vec![]));
+    assert_eq!(result, Ok(RemovedMapValues::Keys(vec![])));
 }
 
 #[test]
@@ -310,7 +316,7 @@ fn can_access_map_values() {
             .insert_encrypted_value(caller, (caller, name), key, value.clone())
             .unwrap();
 
-        for access_rights in AccessRights::iter() {
+        for access_rights in AccessRights::iter().filter(AccessControl::can_read) {
             let user_to_be_added = random_self_authenticating_principal(rng);
             assert_eq!(
                 encrypted_maps.set_user_rights(
@@ -337,12 +343,11 @@ fn can_access_map_values() {
     }
 
     for (user, access_rights) in authorized_users.clone() {
-        let expected_map = BTreeMap::from_iter(keyvals.clone().into_iter());
+        let expected_map = BTreeMap::from_iter(keyvals.clone());
         let computed_map_single = BTreeMap::from_iter(
             encrypted_maps
                 .get_encrypted_values_for_map(user, (caller, name))
-                .expect("failed to obtain values")
-                .into_iter(),
+                .expect("failed to obtain values"),
         );
         assert_eq!(expected_map, computed_map_single);
 
@@ -371,7 +376,7 @@ fn can_access_map_values() {
 
         let all_destructured = all_values.into_iter().next().unwrap();
         assert_eq!((caller, name), all_destructured.0);
-        let computed_map_wildcard = BTreeMap::from_iter(all_destructured.1.into_iter());
+        let computed_map_wildcard = BTreeMap::from_iter(all_destructured.1);
         assert_eq!(expected_map, computed_map_wildcard);
 
         for map in all_maps {
@@ -380,7 +385,7 @@ fn can_access_map_values() {
                     BTreeMap::<Principal, AccessRights>::from_iter(
                         map.access_control
                             .into_iter()
-                            .chain(std::iter::once((caller, access_rights)))
+                            .chain(std::iter::once((caller, AccessRights::owner_rights())))
                     ),
                     BTreeMap::from_iter(authorized_users.clone().into_iter())
                 );
@@ -450,6 +455,432 @@ fn modify_a_key_value_in_map_by_unauthorized_fails() {
     );
 }
 
+#[test]
+fn append_only_user_can_only_add_values() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let appender = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let existing_key = random_key(rng);
+    encrypted_maps
+        .insert_encrypted_value(owner, map_id, existing_key, random_bytebuf(rng, 0..100))
+        .unwrap();
+    encrypted_maps
+        .set_user_rights(owner, map_id, appender, AccessRights::ReadAppend)
+        .unwrap();
+
+    let new_key = random_key(rng);
+    let new_value = random_bytebuf(rng, 0..100);
+    assert_eq!(
+        encrypted_maps.insert_encrypted_value(appender, map_id, new_key, new_value.clone()),
+        Ok(None)
+    );
+    assert_eq!(
+        encrypted_maps.get_encrypted_value(appender, map_id, new_key),
+        Ok(Some(new_value))
+    );
+
+    for key in [existing_key, new_key] {
+        assert_eq!(
+            encrypted_maps.insert_encrypted_value(
+                appender,
+                map_id,
+                key,
+                random_bytebuf(rng, 0..100)
+            ),
+            Err("unauthorized".to_string())
+        );
+        assert_eq!(
+            encrypted_maps.remove_encrypted_value(appender, map_id, key),
+            Err("unauthorized".to_string())
+        );
+    }
+    assert_eq!(
+        encrypted_maps.remove_map_values(appender, map_id),
+        Err("unauthorized".to_string())
+    );
+}
+
+#[test]
+fn delete_only_user_can_only_remove_values() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let deleter = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let key = random_key(rng);
+    let value = random_bytebuf(rng, 0..100);
+    for key in [key, random_key(rng), random_key(rng)] {
+        encrypted_maps
+            .insert_encrypted_value(owner, map_id, key, value.clone())
+            .unwrap();
+    }
+    encrypted_maps
+        .set_user_rights(owner, map_id, deleter, AccessRights::Delete)
+        .unwrap();
+
+    assert_eq!(
+        encrypted_maps.get_encrypted_value(deleter, map_id, key),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        encrypted_maps.insert_encrypted_value(deleter, map_id, random_key(rng), value.clone()),
+        Err("unauthorized".to_string())
+    );
+    assert!(encrypted_maps
+        .get_all_accessible_encrypted_values(deleter)
+        .is_empty());
+    assert!(encrypted_maps
+        .get_all_accessible_encrypted_maps(deleter)
+        .is_empty());
+
+    assert_eq!(
+        encrypted_maps.remove_encrypted_value(deleter, map_id, key),
+        Ok(Some(value))
+    );
+    // the keys of the other values are not revealed to the deleter
+    assert_eq!(
+        encrypted_maps.remove_map_values(deleter, map_id),
+        Ok(RemovedMapValues::Count(2))
+    );
+    assert_eq!(
+        encrypted_maps.get_encrypted_values_for_map(owner, map_id),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn delete_only_user_with_public_read_access_learns_removed_keys() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let deleter = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let key = random_key(rng);
+    encrypted_maps
+        .insert_encrypted_value(owner, map_id, key, random_bytebuf(rng, 0..100))
+        .unwrap();
+    encrypted_maps
+        .set_user_rights(owner, map_id, deleter, AccessRights::Delete)
+        .unwrap();
+    encrypted_maps
+        .set_public_access(owner, map_id, Some(AccessRights::Read))
+        .unwrap();
+
+    assert_eq!(
+        encrypted_maps.remove_map_values(deleter, map_id),
+        Ok(RemovedMapValues::Keys(vec![key]))
+    );
+}
+
+#[test]
+fn metadata_only_user_can_read_values_but_not_vetkey() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let reader = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let key = random_key(rng);
+    let value = random_bytebuf(rng, 0..100);
+    encrypted_maps
+        .insert_encrypted_value(owner, map_id, key, value.clone())
+        .unwrap();
+    encrypted_maps
+        .set_user_rights(owner, map_id, reader, AccessRights::ReadMetadata)
+        .unwrap();
+
+    assert_eq!(
+        encrypted_maps.get_encrypted_value(reader, map_id, key),
+        Ok(Some(value))
+    );
+    assert_eq!(
+        encrypted_maps
+            .get_encrypted_vetkey(reader, map_id, random_bytebuf(rng, 48..49))
+            .err(),
+        Some("unauthorized".to_string())
+    );
+}
+
+#[test]
+fn reader_obtains_verifiable_vetkey_for_shared_map() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let reader = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps_with_in_memory_vetkd(rng);
+
+    encrypted_maps
+        .set_user_rights(owner, map_id, reader, AccessRights::Read)
+        .unwrap();
+
+    let verification_key = block_on(encrypted_maps.get_vetkey_verification_key());
+    let derived_public_key = DerivedPublicKey::deserialize(verification_key.as_ref()).unwrap();
+
+    let vetkeys: Vec<_> = [owner, reader]
+        .into_iter()
+        .map(|caller| {
+            let transport_key =
+                TransportSecretKey::from_seed(rng.gen::<[u8; 32]>().to_vec()).unwrap();
+            let encrypted_vetkey = block_on(
+                encrypted_maps
+                    .get_encrypted_vetkey(
+                        caller,
+                        map_id,
+                        TransportKey::from(transport_key.public_key()),
+                    )
+                    .unwrap(),
+            );
+            EncryptedVetKey::deserialize(encrypted_vetkey.as_ref())
+                .unwrap()
+                .decrypt_and_verify(
+                    &transport_key,
+                    &derived_public_key,
+                    &key_id_to_vetkd_input(map_id.0, map_id.1.as_ref()),
+                )
+                .unwrap()
+        })
+        .collect();
+
+    assert_eq!(vetkeys[0], vetkeys[1]);
+}
+
+#[test]
+fn public_map_values_can_be_read_by_anyone_until_revoked() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let map_id = (owner, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let key = random_key(rng);
+    let value = random_bytebuf(rng, 0..100);
+    encrypted_maps
+        .insert_encrypted_value(owner, map_id, key, value.clone())
+        .unwrap();
+    encrypted_maps
+        .set_public_access(owner, map_id, Some(AccessRights::Read))
+        .unwrap();
+
+    for caller in [Principal::anonymous(), user] {
+        assert_eq!(
+            encrypted_maps.get_encrypted_value(caller, map_id, key),
+            Ok(Some(value.clone()))
+        );
+        assert_eq!(
+            encrypted_maps.insert_encrypted_value(caller, map_id, key, value.clone()),
+            Err("unauthorized".to_string())
+        );
+    }
+
+    assert_eq!(
+        encrypted_maps.set_public_access(owner, map_id, None),
+        Ok(Some(AccessRights::Read))
+    );
+    for caller in [Principal::anonymous(), user] {
+        assert_eq!(
+            encrypted_maps.get_encrypted_value(caller, map_id, key),
+            Err("unauthorized".to_string())
+        );
+    }
+}
+
+#[test]
+fn can_range_scan_map_values() {
+    let rng = &mut reproducible_rng();
+    let caller = random_self_authenticating_principal(rng);
+    let name = random_name(rng);
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let mut expected_map = BTreeMap::new();
+    for _ in 0..20 {
+        let key = random_key(rng);
+        let value = random_bytebuf(rng, 0..100);
+        encrypted_maps
+            .insert_encrypted_value(caller, (caller, name), key, value.clone())
+            .unwrap();
+        expected_map.insert(key, value);
+    }
+    // values of another map must never be returned
+    encrypted_maps
+        .insert_encrypted_value(
+            caller,
+            (caller, random_name(rng)),
+            random_key(rng),
+            random_bytebuf(rng, 0..100),
+        )
+        .unwrap();
+
+    let keys: Vec<_> = expected_map.keys().cloned().collect();
+    let start = keys[5];
+    let end = keys[15];
+
+    assert_eq!(
+        encrypted_maps.range_encrypted_values(caller, (caller, name), start, Some(end), None),
+        Ok(expected_map
+            .range(start..end)
+            .map(|(k, v)| (*k, v.clone()))
+            .collect())
+    );
+    assert_eq!(
+        encrypted_maps.range_encrypted_values(caller, (caller, name), start, None, None),
+        Ok(expected_map
+            .range(start..)
+            .map(|(k, v)| (*k, v.clone()))
+            .collect())
+    );
+    assert_eq!(
+        encrypted_maps.range_encrypted_values(caller, (caller, name), start, None, Some(3)),
+        Ok(expected_map
+            .range(start..)
+            .take(3)
+            .map(|(k, v)| (*k, v.clone()))
+            .collect())
+    );
+    assert_eq!(
+        encrypted_maps.range_encrypted_values(caller, (caller, name), end, Some(start), None),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn can_prefix_scan_map_values() {
+    let rng = &mut reproducible_rng();
+    let caller = random_self_authenticating_principal(rng);
+    let name = random_name(rng);
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let mut folder_a = vec![];
+    for path in ["a/1", "a/2", "a/3/x", "ab", "b/1", "a"] {
+        let key = Blob::<32>::try_from(path.as_bytes()).unwrap();
+        let value = random_bytebuf(rng, 0..100);
+        encrypted_maps
+            .insert_encrypted_value(caller, (caller, name), key, value.clone())
+            .unwrap();
+        if path.starts_with("a/") {
+            folder_a.push((key, value));
+        }
+    }
+    folder_a.sort();
+
+    assert_eq!(
+        encrypted_maps.prefix_encrypted_values(caller, (caller, name), b"a/", None),
+        Ok(folder_a.clone())
+    );
+    assert_eq!(
+        encrypted_maps.prefix_encrypted_values(caller, (caller, name), b"a/", Some(2)),
+        Ok(folder_a[..2].to_vec())
+    );
+    assert_eq!(
+        encrypted_maps
+            .prefix_encrypted_values(caller, (caller, name), b"", None)
+            .map(|values| values.len()),
+        Ok(6)
+    );
+    assert_eq!(
+        encrypted_maps.prefix_encrypted_values(caller, (caller, name), b"c", None),
+        Ok(vec![])
+    );
+    assert_eq!(
+        encrypted_maps.prefix_encrypted_values(caller, (caller, name), &[b'a'; 33], None),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn range_and_prefix_scan_by_unauthorized_fails() {
+    let rng = &mut reproducible_rng();
+    let caller = random_self_authenticating_principal(rng);
+    let unauthorized = random_self_authenticating_principal(rng);
+    let name = random_name(rng);
+    let key = random_key(rng);
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    encrypted_maps
+        .insert_encrypted_value(caller, (caller, name), key, random_bytebuf(rng, 0..100))
+        .unwrap();
+
+    assert_eq!(
+        encrypted_maps.range_encrypted_values(unauthorized, (caller, name), key, None, None),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        encrypted_maps.prefix_encrypted_values(unauthorized, (caller, name), b"", None),
+        Err("unauthorized".to_string())
+    );
+}
+
+#[test]
+fn corrupt_encrypted_value_is_skipped_and_removed() {
+    let rng = &mut reproducible_rng();
+    let caller = random_self_authenticating_principal(rng);
+    let map_id = (caller, random_name(rng));
+    let mut encrypted_maps = random_encrypted_maps(rng);
+
+    let mut expected_map = BTreeMap::new();
+    for _ in 0..5 {
+        let key = random_key(rng);
+        let value = random_bytebuf(rng, 0..100);
+        encrypted_maps
+            .insert_encrypted_value(caller, map_id, key, value.clone())
+            .unwrap();
+        expected_map.insert(key, value);
+    }
+    let corrupt_key = *expected_map.keys().nth(2).unwrap();
+    expected_map.remove(&corrupt_key);
+    encrypted_maps
+        .mapkey_vals
+        .insert((map_id, corrupt_key), StoredValue::Corrupt(vec![1, 2, 3]));
+    let expected_values: Vec<_> = expected_map.into_iter().collect();
+
+    assert_eq!(
+        encrypted_maps.get_encrypted_value(caller, map_id, corrupt_key),
+        Err("corrupt encrypted value".to_string())
+    );
+    assert_eq!(
+        encrypted_maps.get_encrypted_values_for_map(caller, map_id),
+        Ok(expected_values.clone())
+    );
+    assert_eq!(
+        encrypted_maps.range_encrypted_values(caller, map_id, Blob::default(), None, Some(3)),
+        Ok(expected_values[..3].to_vec())
+    );
+    assert_eq!(
+        encrypted_maps.prefix_encrypted_values(caller, map_id, b"", None),
+        Ok(expected_values.clone())
+    );
+    assert_eq!(
+        encrypted_maps.get_all_accessible_encrypted_values(caller),
+        vec![(map_id, expected_values.clone())]
+    );
+
+    let expected_corrupt_entries = vec![CorruptEntry::EncryptedMapValue {
+        map_id,
+        map_key: corrupt_key,
+    }];
+    assert_eq!(
+        encrypted_maps.find_corrupt_entries(),
+        expected_corrupt_entries
+    );
+    assert_eq!(
+        encrypted_maps.remove_corrupt_entries(),
+        expected_corrupt_entries
+    );
+    assert_eq!(encrypted_maps.find_corrupt_entries(), vec![]);
+    assert_eq!(
+        encrypted_maps.get_encrypted_value(caller, map_id, corrupt_key),
+        Ok(None)
+    );
+    assert_eq!(
+        encrypted_maps.get_encrypted_values_for_map(caller, map_id),
+        Ok(expected_values)
+    );
+}
+
 #[test]
 fn can_get_owned_map_names() {
     use rand::Rng;
@@ -507,3 +938,22 @@ fn random_encrypted_maps<R: Rng + CryptoRng>(rng: &mut R) -> EncryptedMaps<Acces
         memory_manager.get(MemoryId::new(memory_ids_key_manager[2])),
     )
 }
+
+fn random_encrypted_maps_with_in_memory_vetkd<R: Rng + CryptoRng>(
+    rng: &mut R,
+) -> EncryptedMaps<AccessRights, InMemoryVetKdApi> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    let (memory_id_encrypted_maps, memory_ids_key_manager) = random_unique_memory_ids(rng);
+    let domain_separator_len = rng.gen_range(0..32);
+    EncryptedMaps::init_with_vetkd_api(
+        &random_utf8_string(rng, domain_separator_len),
+        memory_manager.get(MemoryId::new(memory_id_encrypted_maps)),
+        memory_manager.get(MemoryId::new(memory_ids_key_manager[0])),
+        memory_manager.get(MemoryId::new(memory_ids_key_manager[1])),
+        memory_manager.get(MemoryId::new(memory_ids_key_manager[2])),
+        InMemoryVetKdApi::new(
+            random_self_authenticating_principal(rng),
+            MockVetKd::new(rng.gen()),
+        ),
+    )
+}
@@ -1,17 +1,27 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use assert_matches::assert_matches;
+use candid::Principal;
+use futures::executor::block_on;
 use ic_stable_structures::{
     memory_manager::{MemoryId, MemoryManager},
-    DefaultMemoryImpl,
+    DefaultMemoryImpl, Storable,
 };
-use ic_vetkeys::key_manager::KeyManager;
-use ic_vetkeys::types::AccessRights;
+use ic_vetkeys::key_manager::{key_id_to_vetkd_input, KeyId, KeyManager};
+use ic_vetkeys::types::{
+    AccessControl, AccessGrant, AccessRights, ByteBuf, CorruptEntry, StoredValue, TransportKey,
+    TryFromBytes,
+};
+use ic_vetkeys::{
+    DerivedPublicKey, DeserializationError, EncryptedVetKey, TransportSecretKey, VetKey,
+};
+use ic_vetkeys_test_utils::mock_vetkd::{Fault, InMemoryVetKdApi, MockVetKd, VetKdMethod};
 use ic_vetkeys_test_utils::{
     random_access_rights, random_name, random_self_authenticating_principal,
     random_unique_memory_ids, random_utf8_string, reproducible_rng,
 };
 use rand::{CryptoRng, Rng};
+use strum::IntoEnumIterator;
 
 #[test]
 fn can_init_memory() {
@@ -54,13 +64,13 @@ fn can_get_shared_user_access_for_key() {
     let name = random_name(rng);
     let mut key_manager = random_key_manager(rng);
 
-    let mut shared_access = BTreeSet::new();
+    let mut shared_access = BTreeMap::new();
 
     for _ in 0..10 {
         let user_to_be_added = random_self_authenticating_principal(rng);
         let access_rights = random_access_rights(rng);
 
-        let computed_shared_access: BTreeSet<_> = key_manager
+        let computed_shared_access: BTreeMap<_, _> = key_manager
             .get_shared_user_access_for_key(caller, (caller, name))
             .unwrap()
             .into_iter()
@@ -73,7 +83,7 @@ fn can_get_shared_user_access_for_key() {
             Ok(None)
         );
 
-        shared_access.insert((user_to_be_added, access_rights));
+        shared_access.insert(user_to_be_added, access_rights);
     }
 }
 
@@ -173,10 +183,731 @@ fn other_user_can_manage_key() {
         .set_user_rights(owner, key_id, user2, AccessRights::ReadWriteManage)
         .unwrap();
 
-    key_manager.remove_user(user2, key_id, user1).unwrap();
+    let user3 = random_self_authenticating_principal(rng);
+    key_manager
+        .set_user_rights(user2, key_id, user3, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_user_rights(user2, key_id, user3, AccessRights::Read)
+        .unwrap();
+    key_manager.remove_user(user2, key_id, user3).unwrap();
     key_manager.remove_user(user2, key_id, user2).unwrap();
 }
 
+#[test]
+fn manager_cannot_modify_or_remove_manager_granted_by_someone_else() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user1 = random_self_authenticating_principal(rng);
+    let user2 = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user1, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_user_rights(owner, key_id, user2, AccessRights::ReadWriteManage)
+        .unwrap();
+
+    assert_eq!(
+        key_manager.set_user_rights(user2, key_id, user1, AccessRights::Read),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        key_manager.remove_user(user2, key_id, user1),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        key_manager.set_user_rights(user2, key_id, owner, AccessRights::Read),
+        Err("cannot change key owner's user rights".to_string())
+    );
+    assert_eq!(
+        key_manager.remove_user(user2, key_id, owner),
+        Err("cannot remove key owner".to_string())
+    );
+    assert_eq!(
+        key_manager.get_user_rights(owner, key_id, user1),
+        Ok(Some(AccessRights::ReadWriteManage))
+    );
+
+    assert_eq!(
+        key_manager.remove_user(owner, key_id, user1),
+        Ok(Some(AccessRights::ReadWriteManage))
+    );
+}
+
+#[test]
+fn manager_can_modify_and_remove_users_with_strictly_lower_rights() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let manager = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, manager, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::ReadShare)
+        .unwrap();
+
+    assert_eq!(
+        key_manager.set_user_rights(manager, key_id, user, AccessRights::Read),
+        Ok(Some(AccessRights::ReadShare))
+    );
+    assert_eq!(
+        key_manager.remove_user(manager, key_id, user),
+        Ok(Some(AccessRights::Read))
+    );
+}
+
+#[test]
+fn regranting_same_rights_does_not_take_over_grant() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user1 = random_self_authenticating_principal(rng);
+    let user2 = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user1, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_user_rights(owner, key_id, user2, AccessRights::ReadWriteManage)
+        .unwrap();
+
+    assert_eq!(
+        key_manager.set_user_rights(user2, key_id, user1, AccessRights::ReadWriteManage),
+        Ok(Some(AccessRights::ReadWriteManage))
+    );
+    assert_eq!(
+        key_manager
+            .access_control
+            .get(&(user1, key_id))
+            .and_then(StoredValue::into_valid)
+            .map(|grant| grant.granted_by),
+        Some(owner)
+    );
+    assert_eq!(
+        key_manager.remove_user(user2, key_id, user1),
+        Err("unauthorized".to_string())
+    );
+}
+
+#[test]
+fn access_grant_records_granting_user() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let sharer = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, sharer, AccessRights::ReadShare)
+        .unwrap();
+    key_manager
+        .set_user_rights(sharer, key_id, user, AccessRights::Read)
+        .unwrap();
+
+    assert_eq!(
+        key_manager.access_control.get(&(sharer, key_id)),
+        Some(StoredValue::Valid(AccessGrant {
+            access_rights: AccessRights::ReadShare,
+            granted_by: owner
+        }))
+    );
+    assert_eq!(
+        key_manager.access_control.get(&(user, key_id)),
+        Some(StoredValue::Valid(AccessGrant {
+            access_rights: AccessRights::Read,
+            granted_by: sharer
+        }))
+    );
+}
+
+#[test]
+fn can_decode_access_rights_stored_without_granting_user() {
+    for access_rights in AccessRights::iter() {
+        let grant = AccessGrant::<AccessRights>::from_bytes(access_rights.to_bytes());
+        assert_eq!(grant.access_rights, access_rights);
+        assert_eq!(grant.granted_by, Principal::management_canister());
+
+        assert_eq!(AccessGrant::from_bytes(grant.to_bytes()), grant);
+        assert_eq!(
+            StoredValue::from_bytes(access_rights.to_bytes()),
+            StoredValue::Valid(grant)
+        );
+    }
+}
+
+#[test]
+fn access_rights_decoding_rejects_invalid_encodings() {
+    for access_rights in AccessRights::iter() {
+        assert_eq!(
+            AccessRights::try_from_bytes(&access_rights.to_bytes()),
+            Ok(access_rights)
+        );
+    }
+
+    assert_eq!(
+        AccessRights::try_from_bytes(&[]),
+        Err(DeserializationError::InvalidLength)
+    );
+    assert_eq!(
+        AccessRights::try_from_bytes(&[0, 0]),
+        Err(DeserializationError::InvalidLength)
+    );
+    let first_invalid = AccessRights::iter().count() as u8;
+    for v in first_invalid..=u8::MAX {
+        assert_eq!(
+            AccessRights::try_from_bytes(&[v]),
+            Err(DeserializationError::InvalidValue)
+        );
+    }
+}
+
+#[test]
+fn byte_buf_decoding_rejects_invalid_encodings() {
+    let rng = &mut reproducible_rng();
+    let buf = ByteBuf::from(rng.gen::<[u8; 32]>().to_vec());
+    assert_eq!(ByteBuf::try_from_bytes(&buf.to_bytes()), Ok(buf.clone()));
+
+    let bytes = buf.to_bytes();
+    assert_eq!(
+        ByteBuf::try_from_bytes(&bytes[..bytes.len() - 1]),
+        Err(DeserializationError::InvalidValue)
+    );
+    assert_eq!(
+        ByteBuf::try_from_bytes(&[]),
+        Err(DeserializationError::InvalidValue)
+    );
+    assert_eq!(
+        ByteBuf::try_from_bytes(&AccessRights::Read.to_bytes()),
+        Err(DeserializationError::InvalidValue)
+    );
+}
+
+#[test]
+fn corrupt_access_grant_grants_no_access_and_is_removed() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let other_user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_user_rights(owner, key_id, other_user, AccessRights::Read)
+        .unwrap();
+    let corrupt_grant = StoredValue::Corrupt(vec![42]);
+    key_manager
+        .access_control
+        .insert((user, key_id), corrupt_grant.clone());
+    assert_eq!(
+        key_manager.access_control.get(&(user, key_id)),
+        Some(corrupt_grant)
+    );
+
+    assert_eq!(
+        key_manager.ensure_user_can_read(user, key_id),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(key_manager.get_accessible_shared_key_ids(user), vec![]);
+    assert_eq!(
+        key_manager.get_user_rights(owner, key_id, user),
+        Err("corrupt access rights".to_string())
+    );
+    assert_eq!(
+        key_manager.get_shared_user_access_for_key(owner, key_id),
+        Ok(vec![(other_user, AccessRights::Read)])
+    );
+
+    let expected_corrupt_entries = vec![CorruptEntry::AccessGrant { user, key_id }];
+    assert_eq!(key_manager.find_corrupt_entries(), expected_corrupt_entries);
+    assert_eq!(
+        key_manager.remove_corrupt_entries(),
+        expected_corrupt_entries
+    );
+    assert_eq!(key_manager.find_corrupt_entries(), vec![]);
+    assert_eq!(key_manager.get_user_rights(owner, key_id, user), Ok(None));
+    assert_eq!(
+        key_manager.get_user_rights(owner, key_id, other_user),
+        Ok(Some(AccessRights::Read))
+    );
+}
+
+#[test]
+fn only_owner_can_overwrite_corrupt_access_grant() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let manager = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, manager, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::Read)
+        .unwrap();
+    key_manager
+        .access_control
+        .insert((user, key_id), StoredValue::Corrupt(vec![]));
+
+    assert_eq!(
+        key_manager.set_user_rights(manager, key_id, user, AccessRights::Read),
+        Err("corrupt access rights".to_string())
+    );
+    assert_eq!(
+        key_manager.remove_user(manager, key_id, user),
+        Err("corrupt access rights".to_string())
+    );
+
+    assert_eq!(
+        key_manager.set_user_rights(owner, key_id, user, AccessRights::Read),
+        Ok(None)
+    );
+    assert_eq!(key_manager.find_corrupt_entries(), vec![]);
+    assert_eq!(
+        key_manager.ensure_user_can_read(user, key_id),
+        Ok(AccessRights::Read)
+    );
+}
+
+#[test]
+fn can_repair_inconsistent_shared_keys() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let removed_user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::Read)
+        .unwrap();
+    key_manager.shared_keys.remove(&(key_id, user));
+    key_manager.shared_keys.insert((key_id, removed_user), ());
+
+    let expected_corrupt_entries = vec![
+        CorruptEntry::AccessGrantWithoutSharedKey { user, key_id },
+        CorruptEntry::SharedKeyWithoutAccessGrant {
+            key_id,
+            user: removed_user,
+        },
+    ];
+    assert_eq!(key_manager.find_corrupt_entries(), expected_corrupt_entries);
+    assert_eq!(
+        key_manager.get_shared_user_access_for_key(owner, key_id),
+        Ok(vec![])
+    );
+
+    assert_eq!(
+        key_manager.remove_corrupt_entries(),
+        expected_corrupt_entries
+    );
+    assert_eq!(key_manager.find_corrupt_entries(), vec![]);
+    assert_eq!(
+        key_manager.get_shared_user_access_for_key(owner, key_id),
+        Ok(vec![(user, AccessRights::Read)])
+    );
+}
+
+#[test]
+fn cannot_grant_rights_exceeding_own_rights() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let sharer = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, sharer, AccessRights::ReadShare)
+        .unwrap();
+
+    for access_rights in AccessRights::iter() {
+        let user = random_self_authenticating_principal(rng);
+        let result = key_manager.set_user_rights(sharer, key_id, user, access_rights);
+        if AccessRights::ReadShare.can_grant(&access_rights) {
+            assert_eq!(result, Ok(None));
+        } else {
+            assert_eq!(
+                result,
+                Err("cannot grant access rights exceeding own rights".to_string())
+            );
+            assert_eq!(key_manager.get_user_rights(owner, key_id, user), Ok(None));
+        }
+    }
+}
+
+#[test]
+fn share_only_user_cannot_revoke_or_modify_access() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let sharer = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, sharer, AccessRights::ReadShare)
+        .unwrap();
+    assert_eq!(
+        key_manager.set_user_rights(sharer, key_id, user, AccessRights::Read),
+        Ok(None)
+    );
+    assert_eq!(
+        key_manager.set_user_rights(sharer, key_id, user, AccessRights::Read),
+        Ok(Some(AccessRights::Read))
+    );
+    assert_eq!(
+        key_manager
+            .get_shared_user_access_for_key(sharer, key_id)
+            .unwrap()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+        BTreeMap::from([
+            (sharer, AccessRights::ReadShare),
+            (user, AccessRights::Read)
+        ])
+    );
+
+    assert_eq!(
+        key_manager.set_user_rights(sharer, key_id, user, AccessRights::ReadMetadata),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        key_manager.remove_user(sharer, key_id, user),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        key_manager.remove_user(owner, key_id, user),
+        Ok(Some(AccessRights::Read))
+    );
+}
+
+#[test]
+fn metadata_only_user_cannot_get_vetkey() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::ReadMetadata)
+        .unwrap();
+
+    assert_eq!(
+        key_manager.ensure_user_can_read(user, key_id),
+        Ok(AccessRights::ReadMetadata)
+    );
+    assert_eq!(
+        key_manager
+            .get_encrypted_vetkey(user, key_id, TransportKey::from(vec![0; 48]))
+            .err(),
+        Some("unauthorized".to_string())
+    );
+}
+
+#[test]
+fn owner_and_shared_user_obtain_same_verifiable_vetkey() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager_with_in_memory_vetkd(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::Read)
+        .unwrap();
+
+    let owner_vetkey = obtain_vetkey(rng, &key_manager, owner, key_id);
+    let user_vetkey = obtain_vetkey(rng, &key_manager, user, key_id);
+    assert_eq!(owner_vetkey, user_vetkey);
+
+    let other_key_id = (owner, random_name(rng));
+    assert_ne!(
+        obtain_vetkey(rng, &key_manager, owner, other_key_id),
+        owner_vetkey
+    );
+}
+
+#[test]
+#[should_panic(expected = "call to vetkd_derive_key failed")]
+fn get_encrypted_vetkey_panics_if_vetkd_call_fails() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let key_manager = random_key_manager_with_in_memory_vetkd(rng);
+
+    key_manager.vetkd_api.mock().inject_fault(
+        VetKdMethod::DeriveKey,
+        Fault::Reject("key unavailable".to_string()),
+    );
+
+    let transport_key = TransportSecretKey::from_seed(rng.gen::<[u8; 32]>().to_vec()).unwrap();
+    let future = key_manager
+        .get_encrypted_vetkey(
+            owner,
+            key_id,
+            TransportKey::from(transport_key.public_key()),
+        )
+        .unwrap();
+    block_on(future);
+}
+
+#[test]
+fn owner_rights_can_grant_all_access_rights() {
+    for access_rights in AccessRights::iter() {
+        assert!(AccessRights::owner_rights().can_grant(&access_rights));
+        assert!(access_rights.can_grant(&access_rights) == access_rights.can_set_user_rights());
+        for other in AccessRights::iter() {
+            if access_rights.can_grant(&other) {
+                assert!(access_rights.can_set_user_rights());
+                assert!(!other.can_read() || access_rights.can_read());
+                assert!(!other.can_derive_key() || access_rights.can_derive_key());
+                assert!(!other.can_write() || access_rights.can_write());
+                assert!(!other.can_remove_user() || access_rights.can_remove_user());
+            }
+        }
+    }
+}
+
+#[test]
+fn cannot_share_with_anonymous_principal_or_self() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let manager = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    for access_rights in AccessRights::iter() {
+        assert_eq!(
+            key_manager.set_user_rights(owner, key_id, Principal::anonymous(), access_rights),
+            Err("cannot share with the anonymous principal".to_string())
+        );
+    }
+
+    key_manager
+        .set_user_rights(owner, key_id, manager, AccessRights::ReadWriteManage)
+        .unwrap();
+    assert_eq!(
+        key_manager.set_user_rights(manager, key_id, Principal::anonymous(), AccessRights::Read),
+        Err("cannot share with the anonymous principal".to_string())
+    );
+    assert_eq!(
+        key_manager.set_user_rights(manager, key_id, manager, AccessRights::Read),
+        Err("cannot change own user rights".to_string())
+    );
+    assert_eq!(
+        key_manager.set_user_rights(owner, key_id, owner, AccessRights::Read),
+        Err("cannot change key owner's user rights".to_string())
+    );
+
+    assert_eq!(
+        key_manager.get_accessible_shared_key_ids(Principal::anonymous()),
+        vec![]
+    );
+}
+
+#[test]
+fn can_set_and_revoke_public_access() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    for caller in [Principal::anonymous(), user] {
+        assert_eq!(
+            key_manager.ensure_user_can_read(caller, key_id),
+            Err("unauthorized".to_string())
+        );
+    }
+
+    assert_eq!(
+        key_manager.set_public_access(owner, key_id, Some(AccessRights::Read)),
+        Ok(None)
+    );
+    for caller in [Principal::anonymous(), user] {
+        assert_eq!(
+            key_manager.ensure_user_can_read(caller, key_id),
+            Ok(AccessRights::Read)
+        );
+        assert_eq!(
+            key_manager.ensure_user_can_derive_key(caller, key_id),
+            Ok(AccessRights::Read)
+        );
+        assert_eq!(
+            key_manager.ensure_user_can_write(caller, key_id),
+            Err("unauthorized".to_string())
+        );
+    }
+    assert_eq!(
+        key_manager.get_shared_user_access_for_key(owner, key_id),
+        Ok(vec![(Principal::anonymous(), AccessRights::Read)])
+    );
+
+    assert_eq!(
+        key_manager.set_public_access(owner, key_id, Some(AccessRights::ReadWrite)),
+        Ok(Some(AccessRights::Read))
+    );
+    assert_eq!(
+        key_manager.ensure_user_can_write(user, key_id),
+        Ok(AccessRights::ReadWrite)
+    );
+
+    assert_eq!(
+        key_manager.set_public_access(owner, key_id, None),
+        Ok(Some(AccessRights::ReadWrite))
+    );
+    for caller in [Principal::anonymous(), user] {
+        assert_eq!(
+            key_manager.ensure_user_can_read(caller, key_id),
+            Err("unauthorized".to_string())
+        );
+    }
+    assert_eq!(
+        key_manager.get_shared_user_access_for_key(owner, key_id),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn cannot_revoke_public_access_via_remove_user() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let manager = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, manager, AccessRights::ReadWriteManage)
+        .unwrap();
+    key_manager
+        .set_public_access(owner, key_id, Some(AccessRights::Read))
+        .unwrap();
+
+    for caller in [owner, manager] {
+        assert_eq!(
+            key_manager.remove_user(caller, key_id, Principal::anonymous()),
+            Err("cannot remove public access, use set_public_access".to_string())
+        );
+    }
+    assert_eq!(
+        key_manager.ensure_user_can_read(Principal::anonymous(), key_id),
+        Ok(AccessRights::Read)
+    );
+}
+
+#[test]
+fn public_access_complements_user_rights() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::ReadWrite)
+        .unwrap();
+    key_manager
+        .set_public_access(owner, key_id, Some(AccessRights::ReadMetadata))
+        .unwrap();
+
+    assert_eq!(
+        key_manager.ensure_user_can_derive_key(user, key_id),
+        Ok(AccessRights::ReadWrite)
+    );
+    assert_eq!(
+        key_manager.ensure_user_can_derive_key(Principal::anonymous(), key_id),
+        Err("unauthorized".to_string())
+    );
+    assert_eq!(
+        key_manager
+            .get_shared_user_access_for_key(owner, key_id)
+            .unwrap()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>(),
+        BTreeMap::from([
+            (user, AccessRights::ReadWrite),
+            (Principal::anonymous(), AccessRights::ReadMetadata)
+        ])
+    );
+}
+
+#[test]
+fn get_user_rights_falls_back_to_public_access() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let user = random_self_authenticating_principal(rng);
+    let other_user = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    assert_eq!(key_manager.get_user_rights(owner, key_id, user), Ok(None));
+
+    key_manager
+        .set_public_access(owner, key_id, Some(AccessRights::ReadMetadata))
+        .unwrap();
+    key_manager
+        .set_user_rights(owner, key_id, user, AccessRights::ReadWrite)
+        .unwrap();
+
+    assert_eq!(
+        key_manager.get_user_rights(owner, key_id, user),
+        Ok(Some(AccessRights::ReadWrite))
+    );
+    for public_user in [other_user, Principal::anonymous()] {
+        assert_eq!(
+            key_manager.get_user_rights(owner, key_id, public_user),
+            Ok(Some(AccessRights::ReadMetadata))
+        );
+        assert_eq!(
+            key_manager.ensure_user_can_read(public_user, key_id),
+            Ok(AccessRights::ReadMetadata)
+        );
+    }
+}
+
+#[test]
+fn public_access_is_set_by_owner_and_excludes_management_rights() {
+    let rng = &mut reproducible_rng();
+    let owner = random_self_authenticating_principal(rng);
+    let manager = random_self_authenticating_principal(rng);
+    let key_id = (owner, random_name(rng));
+    let mut key_manager = random_key_manager(rng);
+
+    key_manager
+        .set_user_rights(owner, key_id, manager, AccessRights::ReadWriteManage)
+        .unwrap();
+    assert_eq!(
+        key_manager.set_public_access(manager, key_id, Some(AccessRights::Read)),
+        Err("unauthorized".to_string())
+    );
+
+    for access_rights in AccessRights::iter() {
+        let result = key_manager.set_public_access(owner, key_id, Some(access_rights));
+        if access_rights.can_set_user_rights() {
+            assert_eq!(
+                result,
+                Err("public access cannot include management rights".to_string())
+            );
+        } else {
+            assert!(result.is_ok());
+        }
+    }
+}
+
 #[test]
 fn can_remove_user_from_key() {
     let rng = &mut reproducible_rng();
@@ -269,3 +1000,49 @@ fn random_key_manager<R: Rng + CryptoRng>(rng: &mut R) -> KeyManager<AccessRight
         memory_manager.get(MemoryId::new(memory_ids_key_manager[2])),
     )
 }
+
+fn random_key_manager_with_in_memory_vetkd<R: Rng + CryptoRng>(
+    rng: &mut R,
+) -> KeyManager<AccessRights, InMemoryVetKdApi> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    let (_memory_id_encrypted_maps, memory_ids_key_manager) = random_unique_memory_ids(rng);
+    let domain_separator_len = rng.gen_range(0..32);
+    KeyManager::init_with_vetkd_api(
+        &random_utf8_string(rng, domain_separator_len),
+        memory_manager.get(MemoryId::new(memory_ids_key_manager[0])),
+        memory_manager.get(MemoryId::new(memory_ids_key_manager[1])),
+        memory_manager.get(MemoryId::new(memory_ids_key_manager[2])),
+        InMemoryVetKdApi::new(
+            random_self_authenticating_principal(rng),
+            MockVetKd::new(rng.gen()),
+        ),
+    )
+}
+
+fn obtain_vetkey<R: Rng + CryptoRng>(
+    rng: &mut R,
+    key_manager: &KeyManager<AccessRights, InMemoryVetKdApi>,
+    caller: Principal,
+    key_id: KeyId,
+) -> VetKey {
+    let transport_key = TransportSecretKey::from_seed(rng.gen::<[u8; 32]>().to_vec()).unwrap();
+    let verification_key = block_on(key_manager.get_vetkey_verification_key());
+    let encrypted_vetkey = block_on(
+        key_manager
+            .get_encrypted_vetkey(
+                caller,
+                key_id,
+                TransportKey::from(transport_key.public_key()),
+            )
+            .unwrap(),
+    );
+
+    EncryptedVetKey::deserialize(encrypted_vetkey.as_ref())
+        .unwrap()
+        .decrypt_and_verify(
+            &transport_key,
+            &DerivedPublicKey::deserialize(verification_key.as_ref()).unwrap(),
+            &key_id_to_vetkd_input(key_id.0, key_id.1.as_ref()),
+        )
+        .unwrap()
+}
@@ -0,0 +1,265 @@
+use candid::Principal;
+use ic_vetkeys::vetkd_api_types::{VetKDDeriveKeyRequest, VetKDPublicKeyRequest};
+use ic_vetkeys::*;
+use ic_vetkeys_test_utils::mock_vetkd::{Fault, MockVetKd, VetKdMethod};
+use ic_vetkeys_test_utils::{random_self_authenticating_principal, reproducible_rng};
+use rand::{CryptoRng, Rng};
+
+struct Setup {
+    mock: MockVetKd,
+    caller: Principal,
+    context: Vec<u8>,
+    input: Vec<u8>,
+    transport_secret_key: TransportSecretKey,
+}
+
+impl Setup {
+    fn new<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        Self {
+            mock: MockVetKd::new(rng.gen()),
+            caller: random_self_authenticating_principal(rng),
+            context: rng.gen::<[u8; 16]>().to_vec(),
+            input: rng.gen::<[u8; 32]>().to_vec(),
+            transport_secret_key: TransportSecretKey::from_seed(rng.gen::<[u8; 32]>().to_vec())
+                .unwrap(),
+        }
+    }
+
+    fn public_key(&mut self) -> Result<Vec<u8>, String> {
+        let request = VetKDPublicKeyRequest {
+            canister_id: None,
+            context: self.context.clone(),
+            key_id: self.mock.key_id(),
+        };
+        self.mock
+            .vetkd_public_key(self.caller, request)
+            .map(|reply| reply.public_key)
+    }
+
+    fn encrypted_key(&mut self) -> Result<Vec<u8>, String> {
+        let request = VetKDDeriveKeyRequest {
+            input: self.input.clone(),
+            context: self.context.clone(),
+            transport_public_key: self.transport_secret_key.public_key(),
+            key_id: self.mock.key_id(),
+        };
+        self.mock
+            .vetkd_derive_key(self.caller, request)
+            .map(|reply| reply.encrypted_key)
+    }
+
+    fn decrypt_and_verify(&self, public_key: &[u8], encrypted_key: &[u8]) -> Result<VetKey, Error> {
+        let dpk = DerivedPublicKey::deserialize(public_key)?;
+        let ek = EncryptedVetKey::deserialize(encrypted_key)?;
+        Ok(ek.decrypt_and_verify(&self.transport_secret_key, &dpk, &self.input)?)
+    }
+}
+
+#[test]
+fn public_key_matches_offline_derivation_from_master_public_key() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    let mpk = MasterPublicKey::deserialize(&setup.mock.master_public_key()).unwrap();
+    let expected = mpk
+        .derive_canister_key(setup.caller.as_slice())
+        .derive_sub_key(&setup.context);
+
+    assert_eq!(setup.public_key().unwrap(), expected.serialize());
+
+    let canister_id = random_self_authenticating_principal(rng);
+    let request = VetKDPublicKeyRequest {
+        canister_id: Some(canister_id),
+        context: vec![],
+        key_id: setup.mock.key_id(),
+    };
+    assert_eq!(
+        setup
+            .mock
+            .vetkd_public_key(setup.caller, request)
+            .unwrap()
+            .public_key,
+        mpk.derive_canister_key(canister_id.as_slice()).serialize()
+    );
+}
+
+#[test]
+fn derived_keys_decrypt_and_verify() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    let public_key = setup.public_key().unwrap();
+    let first_encrypted_key = setup.encrypted_key().unwrap();
+    let second_encrypted_key = setup.encrypted_key().unwrap();
+    assert_ne!(first_encrypted_key, second_encrypted_key);
+
+    let first = setup
+        .decrypt_and_verify(&public_key, &first_encrypted_key)
+        .unwrap();
+    let second = setup
+        .decrypt_and_verify(&public_key, &second_encrypted_key)
+        .unwrap();
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn mock_is_deterministic_for_a_given_seed() {
+    let seed = [42; 32];
+    assert_eq!(
+        MockVetKd::new(seed).master_public_key(),
+        MockVetKd::new(seed).master_public_key()
+    );
+    assert_ne!(
+        MockVetKd::new(seed).master_public_key(),
+        MockVetKd::new([43; 32]).master_public_key()
+    );
+}
+
+#[test]
+fn should_reject_unknown_key_name() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+    setup.mock = MockVetKd::new(rng.gen()).with_key_name("test_key_1");
+
+    let mut request = VetKDPublicKeyRequest {
+        canister_id: None,
+        context: vec![],
+        key_id: setup.mock.key_id(),
+    };
+    request.key_id.name = "key_1".to_string();
+
+    assert!(setup.mock.vetkd_public_key(setup.caller, request).is_err());
+    assert!(setup.public_key().is_ok());
+}
+
+#[test]
+fn should_reject_invalid_transport_public_key() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    let request = VetKDDeriveKeyRequest {
+        input: setup.input.clone(),
+        context: setup.context.clone(),
+        transport_public_key: vec![0; 48],
+        key_id: setup.mock.key_id(),
+    };
+
+    assert_eq!(
+        setup
+            .mock
+            .vetkd_derive_key(setup.caller, request)
+            .map(|_| ()),
+        Err("invalid transport public key".to_string())
+    );
+}
+
+#[test]
+fn injected_faults_are_consumed_in_order() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    setup
+        .mock
+        .inject_fault(VetKdMethod::DeriveKey, Fault::Reject("first".to_string()));
+    setup
+        .mock
+        .inject_fault(VetKdMethod::DeriveKey, Fault::Reject("second".to_string()));
+
+    assert!(setup.public_key().is_ok());
+    assert_eq!(setup.encrypted_key(), Err("first".to_string()));
+    assert_eq!(setup.encrypted_key(), Err("second".to_string()));
+    assert!(setup.encrypted_key().is_ok());
+
+    setup
+        .mock
+        .inject_fault(VetKdMethod::PublicKey, Fault::Reject("cleared".to_string()));
+    setup.mock.clear_faults();
+    assert!(setup.public_key().is_ok());
+}
+
+#[test]
+fn invalid_requests_do_not_consume_faults() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    setup
+        .mock
+        .inject_fault(VetKdMethod::PublicKey, Fault::Reject("public".to_string()));
+    setup
+        .mock
+        .inject_fault(VetKdMethod::DeriveKey, Fault::Reject("derive".to_string()));
+
+    let mut unknown_key_id = setup.mock.key_id();
+    unknown_key_id.name = "unknown_key".to_string();
+    let request = VetKDPublicKeyRequest {
+        canister_id: None,
+        context: setup.context.clone(),
+        key_id: unknown_key_id,
+    };
+    assert_eq!(
+        setup
+            .mock
+            .vetkd_public_key(setup.caller, request)
+            .map(|_| ()),
+        Err("unknown vetKD key: unknown_key".to_string())
+    );
+    assert_eq!(setup.public_key(), Err("public".to_string()));
+
+    let request = VetKDDeriveKeyRequest {
+        input: setup.input.clone(),
+        context: setup.context.clone(),
+        transport_public_key: vec![0; 48],
+        key_id: setup.mock.key_id(),
+    };
+    assert_eq!(
+        setup
+            .mock
+            .vetkd_derive_key(setup.caller, request)
+            .map(|_| ()),
+        Err("invalid transport public key".to_string())
+    );
+    assert_eq!(setup.encrypted_key(), Err("derive".to_string()));
+}
+
+#[test]
+fn wrong_key_faults_fail_verification() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    let public_key = setup.public_key().unwrap();
+    setup
+        .mock
+        .inject_fault(VetKdMethod::DeriveKey, Fault::WrongKey);
+    let encrypted_key = setup.encrypted_key().unwrap();
+    assert_eq!(
+        setup.decrypt_and_verify(&public_key, &encrypted_key),
+        Err(Error::Verification(VerificationError::InvalidSignature))
+    );
+
+    setup
+        .mock
+        .inject_fault(VetKdMethod::PublicKey, Fault::WrongKey);
+    let wrong_public_key = setup.public_key().unwrap();
+    assert_ne!(wrong_public_key, public_key);
+    let encrypted_key = setup.encrypted_key().unwrap();
+    assert!(setup
+        .decrypt_and_verify(&wrong_public_key, &encrypted_key)
+        .is_err());
+}
+
+#[test]
+fn malformed_reply_faults_fail_deserialization() {
+    let rng = &mut reproducible_rng();
+    let mut setup = Setup::new(rng);
+
+    setup
+        .mock
+        .inject_fault(VetKdMethod::PublicKey, Fault::MalformedReply);
+    assert!(DerivedPublicKey::deserialize(&setup.public_key().unwrap()).is_err());
+
+    setup
+        .mock
+        .inject_fault(VetKdMethod::DeriveKey, Fault::MalformedReply);
+    assert!(EncryptedVetKey::deserialize(&setup.encrypted_key().unwrap()).is_err());
+}
@@ -0,0 +1,164 @@
+use futures::executor::block_on;
+use ic_bls12_381::{G1Affine, G2Affine};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    DefaultMemoryImpl,
+};
+use ic_vetkeys::random_beacon::{random_beacon_input, RandomBeacon};
+use ic_vetkeys::types::{ByteBuf, StoredValue};
+use ic_vetkeys::{DerivedPublicKey, VetKey, VrfOutput};
+use ic_vetkeys_test_utils::mock_vetkd::{InMemoryVetKdApi, MockVetKd};
+use ic_vetkeys_test_utils::{random_self_authenticating_principal, reproducible_rng};
+use rand::{CryptoRng, Rng};
+
+const PERIOD: u64 = 60_000_000_000;
+
+#[test]
+fn rounds_start_at_multiples_of_period() {
+    let beacon = random_beacon();
+
+    assert_eq!(beacon.current_round(0), 0);
+    assert_eq!(beacon.current_round(PERIOD - 1), 0);
+    assert_eq!(beacon.current_round(PERIOD), 1);
+    assert_eq!(beacon.current_round(u64::MAX), u64::MAX / PERIOD);
+}
+
+#[test]
+fn beacon_inputs_are_distinct_per_round() {
+    assert_ne!(random_beacon_input(1), random_beacon_input(2));
+    assert!(random_beacon_input(1).starts_with(b"ic-vetkeys-random-beacon"));
+}
+
+#[test]
+fn cannot_derive_or_publish_beacon_before_round_started() {
+    let mut beacon = random_beacon();
+    let output = dummy_vrf_output();
+    let verification_key = dummy_verification_key();
+
+    for now in [0, 5 * PERIOD - 1] {
+        assert_eq!(
+            beacon.derive_beacon(5, now).err(),
+            Some("round has not started yet".to_string())
+        );
+        assert_eq!(
+            beacon.publish_beacon(5, now, &output, &verification_key),
+            Err("round has not started yet".to_string())
+        );
+    }
+    assert_eq!(
+        beacon.publish_beacon(u64::MAX, u64::MAX, &output, &verification_key),
+        Err("round has not started yet".to_string())
+    );
+    assert_eq!(beacon.get_beacon(5), Ok(None));
+}
+
+#[test]
+fn published_beacon_can_be_retrieved_and_not_replaced() {
+    let rng = &mut reproducible_rng();
+    let mut beacon = random_beacon_with_in_memory_vetkd(rng);
+    let verification_key = verification_key(&beacon);
+    let output = block_on(beacon.derive_beacon(5, 5 * PERIOD).unwrap()).unwrap();
+
+    let published = beacon
+        .publish_beacon(5, 5 * PERIOD, &output, &verification_key)
+        .unwrap();
+    assert_eq!(published.round, 5);
+    assert_eq!(published.proof.as_ref(), output.proof());
+    assert_eq!(published.randomness.as_ref(), output.randomness());
+
+    assert_eq!(beacon.get_beacon(5), Ok(Some(published)));
+    assert_eq!(beacon.get_beacon(4), Ok(None));
+    assert_eq!(
+        beacon.publish_beacon(5, 6 * PERIOD, &output, &verification_key),
+        Err("beacon already published".to_string())
+    );
+}
+
+#[test]
+fn cannot_publish_beacon_of_different_round() {
+    let rng = &mut reproducible_rng();
+    let mut beacon = random_beacon_with_in_memory_vetkd(rng);
+    let verification_key = verification_key(&beacon);
+    let output = block_on(beacon.derive_beacon(5, 5 * PERIOD).unwrap()).unwrap();
+
+    for (round, output) in [(6, &output), (5, &dummy_vrf_output())] {
+        assert_eq!(
+            beacon.publish_beacon(round, 6 * PERIOD, output, &verification_key),
+            Err("invalid beacon for this round".to_string())
+        );
+        assert_eq!(beacon.get_beacon(round), Ok(None));
+    }
+}
+
+#[test]
+fn corrupt_beacon_is_reported_as_error() {
+    let mut beacon = random_beacon();
+
+    beacon
+        .beacons
+        .insert(5, StoredValue::Corrupt(vec![1, 2, 3]));
+    beacon
+        .beacons
+        .insert(6, StoredValue::Valid(ByteBuf::from(vec![0; 48])));
+
+    for round in [5, 6] {
+        assert_eq!(beacon.get_beacon(round), Err("corrupt beacon".to_string()));
+    }
+}
+
+#[test]
+fn can_derive_and_publish_verifiable_beacon_with_in_memory_vetkd() {
+    let rng = &mut reproducible_rng();
+    let mut beacon = random_beacon_with_in_memory_vetkd(rng);
+    let dpk = verification_key(&beacon);
+
+    let output = block_on(beacon.derive_beacon(5, 5 * PERIOD).unwrap()).unwrap();
+    let published = beacon.publish_beacon(5, 5 * PERIOD, &output, &dpk).unwrap();
+
+    let verified = VrfOutput::verify(&dpk, &random_beacon_input(5), published.proof.as_ref())
+        .expect("beacon should verify against the verification key");
+    assert_eq!(verified.randomness(), output.randomness());
+    assert!(VrfOutput::verify(&dpk, &random_beacon_input(6), published.proof.as_ref()).is_err());
+
+    let other_output = block_on(beacon.derive_beacon(6, 6 * PERIOD).unwrap()).unwrap();
+    assert_ne!(other_output.randomness(), output.randomness());
+}
+
+fn random_beacon() -> RandomBeacon {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    RandomBeacon::init(
+        "random beacon",
+        PERIOD,
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+    )
+}
+
+fn dummy_vrf_output() -> VrfOutput {
+    VrfOutput::from_vetkey(VetKey::deserialize(&G1Affine::generator().to_compressed()).unwrap())
+}
+
+fn dummy_verification_key() -> DerivedPublicKey {
+    DerivedPublicKey::deserialize(&G2Affine::generator().to_compressed()).unwrap()
+}
+
+fn verification_key(beacon: &RandomBeacon<InMemoryVetKdApi>) -> DerivedPublicKey {
+    let verification_key = block_on(beacon.get_verification_key());
+    DerivedPublicKey::deserialize(verification_key.as_ref()).unwrap()
+}
+
+fn random_beacon_with_in_memory_vetkd<R: Rng + CryptoRng>(
+    rng: &mut R,
+) -> RandomBeacon<InMemoryVetKdApi> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    RandomBeacon::init_with_vetkd_api(
+        "random beacon",
+        PERIOD,
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+        InMemoryVetKdApi::new(
+            random_self_authenticating_principal(rng),
+            MockVetKd::new(rng.gen()),
+        ),
+    )
+}
@@ -0,0 +1,111 @@
+use ic_vetkeys::*;
+use ic_vetkeys_test_utils::test_vectors::TestVectors;
+
+fn bytes(hex: &str) -> Vec<u8> {
+    hex::decode(hex).unwrap()
+}
+
+#[test]
+fn committed_test_vectors_are_up_to_date() {
+    assert!(
+        TestVectors::load() == TestVectors::generate(),
+        "test vectors are outdated, regenerate them with \
+         `cargo run -p ic-vetkeys-test-utils --bin generate_test_vectors`"
+    );
+}
+
+#[test]
+fn key_derivation_matches_test_vectors() {
+    for v in TestVectors::load().key_derivation {
+        let mpk = MasterPublicKey::deserialize(&bytes(&v.master_public_key)).unwrap();
+        let canister_key = mpk.derive_canister_key(&bytes(&v.canister_id));
+        assert_eq!(hex::encode(canister_key.serialize()), v.canister_key);
+        let derived_key = canister_key.derive_sub_key(&bytes(&v.context));
+        assert_eq!(hex::encode(derived_key.serialize()), v.derived_public_key);
+    }
+}
+
+#[test]
+fn encrypted_vetkeys_match_test_vectors() {
+    for v in TestVectors::load().encrypted_vetkeys {
+        let tsk = v.transport_secret_key();
+        assert_eq!(hex::encode(tsk.public_key()), v.transport_public_key);
+        let dpk = DerivedPublicKey::deserialize(&bytes(&v.derived_public_key)).unwrap();
+        let ek = EncryptedVetKey::deserialize(&bytes(&v.encrypted_vetkey)).unwrap();
+        let vetkey = ek.decrypt_and_verify(&tsk, &dpk, &bytes(&v.input)).unwrap();
+        assert_eq!(hex::encode(vetkey.signature_bytes()), v.vetkey);
+    }
+}
+
+#[test]
+fn ibe_ciphertexts_match_test_vectors() {
+    for v in TestVectors::load().ibe_ciphertexts {
+        let dpk = DerivedPublicKey::deserialize(&bytes(&v.derived_public_key)).unwrap();
+        let identity = IbeIdentity::from_bytes(&bytes(&v.identity));
+        let seed = IbeSeed::from_bytes(&bytes(&v.seed)).unwrap();
+        let message = bytes(&v.message);
+        let vetkey = VetKey::deserialize(&bytes(&v.vetkey)).unwrap();
+
+        let (ciphertext, plaintext) = match &v.associated_data {
+            None => {
+                let ciphertext = IbeCiphertext::encrypt(&dpk, &identity, &message, &seed);
+                let plaintext = IbeCiphertext::deserialize(&bytes(&v.ciphertext))
+                    .unwrap()
+                    .decrypt(&vetkey);
+                (ciphertext, plaintext)
+            }
+            Some(ad) => {
+                let ad = bytes(ad);
+                let ciphertext = IbeCiphertext::encrypt_with_associated_data(
+                    &dpk, &identity, &message, &ad, &seed,
+                );
+                let plaintext = IbeCiphertext::deserialize(&bytes(&v.ciphertext))
+                    .unwrap()
+                    .decrypt_with_associated_data(&vetkey, &ad);
+                (ciphertext, plaintext)
+            }
+        };
+        assert_eq!(hex::encode(ciphertext.serialize()), v.ciphertext);
+        assert_eq!(plaintext, Ok(message));
+    }
+}
+
+#[test]
+fn symmetric_keys_match_test_vectors() {
+    let vectors = TestVectors::load();
+    for v in vectors.symmetric_keys {
+        let output_len = v.output.len() / 2;
+        let key = derive_symmetric_key(&bytes(&v.input), &v.domain_separator, output_len);
+        assert_eq!(hex::encode(key), v.output);
+    }
+    for v in vectors.vetkey_symmetric_keys {
+        let vetkey = VetKey::deserialize(&bytes(&v.vetkey)).unwrap();
+        let key = vetkey.derive_symmetric_key(&v.domain_separator, v.output.len() / 2);
+        assert_eq!(hex::encode(key), v.output);
+    }
+}
+
+#[test]
+fn invalid_test_vectors_are_rejected() {
+    let vectors = TestVectors::load();
+    for v in vectors.invalid_derived_public_keys {
+        assert!(
+            DerivedPublicKey::deserialize(&bytes(&v.bytes)).is_err(),
+            "{}",
+            v.description
+        );
+    }
+    for v in vectors.invalid_encrypted_vetkeys {
+        let dpk = DerivedPublicKey::deserialize(&bytes(&v.derived_public_key)).unwrap();
+        let result = EncryptedVetKey::deserialize(&bytes(&v.encrypted_vetkey))
+            .map_err(Error::from)
+            .and_then(|ek| {
+                ek.decrypt_and_verify(&v.transport_secret_key(), &dpk, &bytes(&v.input))
+                    .map_err(Error::from)
+            });
+        assert!(result.is_err(), "{}", v.description);
+    }
+    for v in vectors.invalid_ibe_ciphertexts {
+        assert_eq!(v.try_decrypt(), None, "{}", v.description);
+    }
+}
@@ -0,0 +1,102 @@
+use futures::executor::block_on;
+use ic_bls12_381::G1Affine;
+use ic_vetkeys::threshold_bls::ThresholdBlsSigner;
+use ic_vetkeys::vetkd_api::{zero_knowledge_transport_secret_key, VetKdApi};
+use ic_vetkeys::vetkd_api_types::VetKDDeriveKeyRequest;
+use ic_vetkeys::{verify_bls_signature, DerivedPublicKey, EncryptedVetKey};
+use ic_vetkeys_test_utils::mock_vetkd::{Fault, InMemoryVetKdApi, MockVetKd, VetKdMethod};
+use ic_vetkeys_test_utils::{random_self_authenticating_principal, reproducible_rng};
+use rand::Rng;
+
+#[test]
+fn signatures_verify_against_verification_key() {
+    let rng = &mut reproducible_rng();
+    let vetkd_api = InMemoryVetKdApi::new(
+        random_self_authenticating_principal(rng),
+        MockVetKd::new(rng.gen()),
+    );
+    let signer = ThresholdBlsSigner::new_with_vetkd_api("signer", vetkd_api.clone());
+    let other_signer = ThresholdBlsSigner::new_with_vetkd_api("other signer", vetkd_api);
+
+    let verification_key = block_on(signer.get_verification_key());
+    let dpk = DerivedPublicKey::deserialize(verification_key.as_ref()).unwrap();
+    let message = b"message";
+    let signature = block_on(signer.sign(message)).unwrap();
+
+    assert!(verify_bls_signature(&dpk, message, signature.as_ref()));
+    assert!(!verify_bls_signature(
+        &dpk,
+        b"other message",
+        signature.as_ref()
+    ));
+
+    let other_signature = block_on(other_signer.sign(message)).unwrap();
+    assert!(!verify_bls_signature(
+        &dpk,
+        message,
+        other_signature.as_ref()
+    ));
+}
+
+#[test]
+fn sign_fails_if_vetkd_call_fails() {
+    let rng = &mut reproducible_rng();
+    let vetkd_api = InMemoryVetKdApi::new(
+        random_self_authenticating_principal(rng),
+        MockVetKd::new(rng.gen()),
+    );
+    let signer = ThresholdBlsSigner::new_with_vetkd_api("signer", vetkd_api.clone());
+
+    vetkd_api.mock().inject_fault(
+        VetKdMethod::DeriveKey,
+        Fault::Reject("vetKD unavailable".to_string()),
+    );
+    assert_eq!(
+        block_on(signer.sign(b"message")),
+        Err("vetKD unavailable".to_string())
+    );
+
+    vetkd_api
+        .mock()
+        .inject_fault(VetKdMethod::DeriveKey, Fault::WrongKey);
+    assert_eq!(
+        block_on(signer.sign(b"message")),
+        Err("invalid encrypted key: invalid signature".to_string())
+    );
+}
+
+#[test]
+fn signatures_are_fetched_with_publicly_known_transport_key() {
+    let rng = &mut reproducible_rng();
+    let vetkd_api = InMemoryVetKdApi::new(
+        random_self_authenticating_principal(rng),
+        MockVetKd::new(rng.gen()),
+    );
+    let signer = ThresholdBlsSigner::new_with_vetkd_api("signer", vetkd_api.clone());
+    let transport_secret_key = zero_knowledge_transport_secret_key();
+    assert_eq!(
+        transport_secret_key.public_key(),
+        G1Affine::generator().to_compressed().to_vec()
+    );
+
+    // anyone who sees the encrypted key of a signature can decrypt it
+    let message = b"message";
+    let request = VetKDDeriveKeyRequest {
+        input: message.to_vec(),
+        context: b"signer".to_vec(),
+        key_id: vetkd_api.mock().key_id(),
+        transport_public_key: transport_secret_key.public_key(),
+    };
+    let reply = block_on(vetkd_api.vetkd_derive_key(request)).unwrap();
+    let verification_key = block_on(signer.get_verification_key());
+    let dpk = DerivedPublicKey::deserialize(verification_key.as_ref()).unwrap();
+    let vetkey = EncryptedVetKey::deserialize(&reply.encrypted_key)
+        .unwrap()
+        .decrypt_and_verify(&transport_secret_key, &dpk, message)
+        .unwrap();
+
+    assert_eq!(
+        block_on(signer.sign(message)).unwrap().as_ref(),
+        vetkey.signature_bytes()
+    );
+}
@@ -0,0 +1,220 @@
+use futures::executor::block_on;
+use ic_bls12_381::{G1Affine, G2Affine};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    DefaultMemoryImpl,
+};
+use ic_vetkeys::timelock::Timelock;
+use ic_vetkeys::types::{ByteBuf, StoredValue};
+use ic_vetkeys::{DerivedPublicKey, IbeSeed, TimelockCiphertext, VetKey};
+use ic_vetkeys_test_utils::mock_vetkd::{Fault, InMemoryVetKdApi, MockVetKd, VetKdMethod};
+use ic_vetkeys_test_utils::{random_self_authenticating_principal, reproducible_rng};
+use rand::{CryptoRng, Rng};
+
+const GRANULARITY: u64 = 1_000_000_000;
+
+#[test]
+fn unlock_times_are_rounded_up_to_granularity() {
+    let timelock = timelock();
+
+    assert_eq!(timelock.rounded_unlock_time(0), Some(0));
+    assert_eq!(timelock.rounded_unlock_time(1), Some(GRANULARITY));
+    assert_eq!(timelock.rounded_unlock_time(GRANULARITY), Some(GRANULARITY));
+    assert_eq!(timelock.rounded_unlock_time(u64::MAX), None);
+}
+
+#[test]
+fn cannot_derive_or_publish_key_before_time_lock_expired() {
+    let mut timelock = timelock();
+    let unlock_time = 5 * GRANULARITY + 1;
+
+    for now in [0, 5 * GRANULARITY, 6 * GRANULARITY - 1] {
+        assert_eq!(
+            timelock.derive_decryption_key(unlock_time, now).err(),
+            Some("time lock has not expired yet".to_string())
+        );
+        assert_eq!(
+            timelock.publish_decryption_key(unlock_time, now, &dummy_vetkey(), &dummy_public_key()),
+            Err("time lock has not expired yet".to_string())
+        );
+    }
+
+    assert_eq!(
+        timelock.publish_decryption_key(u64::MAX, u64::MAX, &dummy_vetkey(), &dummy_public_key()),
+        Err("time lock has not expired yet".to_string())
+    );
+    assert_eq!(timelock.get_decryption_key(unlock_time), Ok(None));
+}
+
+#[test]
+fn published_key_is_returned_for_all_unlock_times_in_round() {
+    let rng = &mut reproducible_rng();
+    let mut timelock = timelock_with_in_memory_vetkd(rng);
+    let public_key = timelock_public_key(&timelock);
+    let now = 6 * GRANULARITY;
+    let key = block_on(
+        timelock
+            .derive_decryption_key(5 * GRANULARITY + 1, now)
+            .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        timelock.publish_decryption_key(5 * GRANULARITY + 1, now, &key, &public_key),
+        Ok(())
+    );
+
+    for unlock_time in [5 * GRANULARITY + 1, 6 * GRANULARITY] {
+        assert_eq!(
+            timelock
+                .get_decryption_key(unlock_time)
+                .map(|key| key.map(Vec::from)),
+            Ok(Some(key.signature_bytes().to_vec()))
+        );
+    }
+    for unlock_time in [5 * GRANULARITY, 6 * GRANULARITY + 1] {
+        assert_eq!(timelock.get_decryption_key(unlock_time), Ok(None));
+    }
+}
+
+#[test]
+fn cannot_publish_key_of_different_round() {
+    let rng = &mut reproducible_rng();
+    let mut timelock = timelock_with_in_memory_vetkd(rng);
+    let public_key = timelock_public_key(&timelock);
+    let now = 7 * GRANULARITY;
+    let key = block_on(
+        timelock
+            .derive_decryption_key(5 * GRANULARITY + 1, now)
+            .unwrap(),
+    )
+    .unwrap();
+
+    for (unlock_time, key) in [
+        (6 * GRANULARITY + 1, &key),
+        (5 * GRANULARITY + 1, &dummy_vetkey()),
+    ] {
+        assert_eq!(
+            timelock.publish_decryption_key(unlock_time, now, key, &public_key),
+            Err("invalid decryption key for this round".to_string())
+        );
+        assert_eq!(timelock.get_decryption_key(unlock_time), Ok(None));
+    }
+}
+
+#[test]
+fn corrupt_decryption_key_is_reported_as_error() {
+    let mut timelock = timelock();
+
+    timelock
+        .published_keys
+        .insert((GRANULARITY, 5), StoredValue::Corrupt(vec![1, 2, 3]));
+    timelock.published_keys.insert(
+        (GRANULARITY, 6),
+        StoredValue::Valid(ByteBuf::from(vec![0; 47])),
+    );
+
+    for unlock_time in [5 * GRANULARITY, 6 * GRANULARITY] {
+        assert_eq!(
+            timelock.get_decryption_key(unlock_time),
+            Err("corrupt decryption key".to_string())
+        );
+    }
+}
+
+#[test]
+fn can_derive_and_publish_decryption_key_with_in_memory_vetkd() {
+    let rng = &mut reproducible_rng();
+    let mut timelock = timelock_with_in_memory_vetkd(rng);
+    let unlock_time = 5 * GRANULARITY + 1;
+    let now = 6 * GRANULARITY;
+
+    let dpk = timelock_public_key(&timelock);
+    let message = b"sealed bid";
+    let ciphertext = TimelockCiphertext::encrypt(
+        &dpk,
+        unlock_time,
+        GRANULARITY,
+        message,
+        &IbeSeed::random(rng),
+    );
+
+    let key = block_on(timelock.derive_decryption_key(unlock_time, now).unwrap()).unwrap();
+    assert_eq!(
+        timelock.publish_decryption_key(unlock_time, now, &key, &dpk),
+        Ok(())
+    );
+
+    let published_key = timelock.get_decryption_key(unlock_time).unwrap().unwrap();
+    let published_key = VetKey::deserialize(published_key.as_ref()).unwrap();
+    assert_eq!(ciphertext.decrypt(&published_key).unwrap(), message);
+}
+
+#[test]
+fn derived_decryption_key_is_verified() {
+    let rng = &mut reproducible_rng();
+    let timelock = timelock_with_in_memory_vetkd(rng);
+
+    timelock
+        .vetkd_api
+        .mock()
+        .inject_fault(VetKdMethod::DeriveKey, Fault::WrongKey);
+    let result = block_on(timelock.derive_decryption_key(0, 0).unwrap());
+    assert_eq!(
+        result.err(),
+        Some("invalid encrypted key: invalid signature".to_string())
+    );
+}
+
+#[test]
+fn rejected_public_key_call_is_reported_as_error() {
+    let rng = &mut reproducible_rng();
+    let timelock = timelock_with_in_memory_vetkd(rng);
+
+    timelock.vetkd_api.mock().inject_fault(
+        VetKdMethod::PublicKey,
+        Fault::Reject("out of cycles".to_string()),
+    );
+    assert_eq!(
+        block_on(timelock.get_timelock_public_key()),
+        Err("call to vetkd_public_key failed: out of cycles".to_string())
+    );
+    assert!(block_on(timelock.get_timelock_public_key()).is_ok());
+}
+
+fn timelock() -> Timelock {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    Timelock::init(
+        "timelock",
+        GRANULARITY,
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+    )
+}
+
+fn dummy_vetkey() -> VetKey {
+    VetKey::deserialize(&G1Affine::generator().to_compressed()).unwrap()
+}
+
+fn dummy_public_key() -> DerivedPublicKey {
+    DerivedPublicKey::deserialize(&G2Affine::generator().to_compressed()).unwrap()
+}
+
+fn timelock_public_key(timelock: &Timelock<InMemoryVetKdApi>) -> DerivedPublicKey {
+    let public_key = block_on(timelock.get_timelock_public_key()).unwrap();
+    DerivedPublicKey::deserialize(public_key.as_ref()).unwrap()
+}
+
+fn timelock_with_in_memory_vetkd<R: Rng + CryptoRng>(rng: &mut R) -> Timelock<InMemoryVetKdApi> {
+    let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+    Timelock::init_with_vetkd_api(
+        "timelock",
+        GRANULARITY,
+        memory_manager.get(MemoryId::new(0)),
+        memory_manager.get(MemoryId::new(1)),
+        InMemoryVetKdApi::new(
+            random_self_authenticating_principal(rng),
+            MockVetKd::new(rng.gen()),
+        ),
+    )
+}
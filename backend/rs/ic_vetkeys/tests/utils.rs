@@ -131,6 +131,19 @@ fn derivation_matches_expected_value() {
     );
 }
 
+#[test]
+fn vetkey_is_redacted_in_debug_output_and_compared_by_value() {
+    let bytes = hex::decode("ad19676dd92f116db11f326ff0822f295d87cc00cf65d9f132b5a618bb7381e5b0c3cb814f15e4a0f015359dcfa8a1da").unwrap();
+    let vetkey = VetKey::deserialize(&bytes).unwrap();
+
+    assert_eq!(format!("{:?}", vetkey), "VetKey(REDACTED)");
+    assert_eq!(vetkey, VetKey::deserialize(&bytes).unwrap());
+    assert_ne!(
+        vetkey,
+        VetKey::deserialize(&G1Affine::generator().to_compressed()).unwrap()
+    );
+}
+
 #[test]
 fn protocol_flow_with_fixed_rng_has_expected_outputs() {
     let tsk = TransportSecretKey::from_seed(vec![0x42; 32]).unwrap();
@@ -182,3 +195,785 @@ fn protocol_flow_with_fixed_rng_has_expected_outputs() {
     let ptext = ctext.decrypt(&vetkey).expect("IBE decryption failed");
     assert_eq!(ptext, msg);
 }
+
+#[test]
+fn timelock_identities_are_canonical_and_round_up() {
+    let identity = IbeIdentity::from_timestamp(11, 10);
+    assert_eq!(
+        hex::encode(identity.value()),
+        format!(
+            "{}{:016x}{:016x}",
+            hex::encode("ic-vetkeys-timelock"),
+            10,
+            2
+        )
+    );
+
+    assert_eq!(identity, IbeIdentity::from_timestamp(20, 10));
+    assert_ne!(identity, IbeIdentity::from_timestamp(10, 10));
+    assert_ne!(identity, IbeIdentity::from_timestamp(21, 10));
+    assert_ne!(identity, IbeIdentity::from_timestamp(11, 11));
+}
+
+#[test]
+fn timelock_encryption_roundtrips_and_requires_key_of_round() {
+    let rng = &mut reproducible_rng();
+    let granularity = 1_000_000_000;
+    let unlock_time = 1_700_000_000_123_456_789;
+
+    let identity = IbeIdentity::from_timestamp(unlock_time, granularity);
+    let next_identity = IbeIdentity::from_timestamp(unlock_time + granularity, granularity);
+    let (dpk, vetkeys) = emulated_vetkeys(rng, &[identity.value(), next_identity.value()]);
+
+    let msg = rng.gen::<[u8; 32]>();
+    let seed = IbeSeed::random(rng);
+    let ctext = TimelockCiphertext::encrypt(&dpk, unlock_time, granularity, &msg, &seed);
+
+    assert_eq!(ctext.unlock_time(), Some(1_700_000_001_000_000_000));
+    assert_eq!(ctext.granularity(), granularity);
+    assert_eq!(ctext.identity(), identity);
+
+    let ctext = TimelockCiphertext::deserialize(&ctext.serialize()).unwrap();
+    assert_eq!(ctext.decrypt(&vetkeys[0]).unwrap(), msg);
+    assert!(ctext.decrypt(&vetkeys[1]).is_err());
+
+    let mut zero_granularity = ctext.serialize();
+    zero_granularity[8..16].copy_from_slice(&[0; 8]);
+    assert!(TimelockCiphertext::deserialize(&zero_granularity).is_err());
+}
+
+#[test]
+fn timelock_encryption_has_expected_output() {
+    let dpk = DerivedPublicKey::deserialize(&hex::decode("972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f").unwrap()).unwrap();
+
+    let msg = hex::decode("f00f11").unwrap();
+    let seed = IbeSeed::from_bytes(&[0u8; 32]).unwrap();
+    let ctext =
+        TimelockCiphertext::encrypt(&dpk, 1_700_000_000_123_456_789, 1_000_000_000, &msg, &seed);
+
+    assert_eq!(hex::encode(ctext.serialize()),
+               "494320544c450001000000003b9aca00000000006553f1014943204942450001a9937528bda5826cf5c7da77a5f5e46719a9748f4ea0aa491c8fba92081e5d55457ab36ec4f6335954c6d87987d0b28301bd8da166493bb537c842d20396da5a68cc9e9672fadedf1e311e0057fc906dfd37d1077ca027954c45336405e66e5efff442d0612cb08a719191919ab89a1a215971e0b4d60f88b63abe1e93739d039f7f98");
+}
+
+#[test]
+fn bls_signature_verifier_accepts_valid_signatures() {
+    let rng = &mut reproducible_rng();
+    let inputs: Vec<Vec<u8>> = (0..10)
+        .map(|i| format!("message {i}").into_bytes())
+        .collect();
+    let input_refs: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+    let (dpk, vetkeys) = emulated_vetkeys(rng, &input_refs);
+
+    let verifier = BlsSignatureVerifier::new(&dpk);
+    assert_eq!(verifier.public_key(), &dpk);
+
+    let batch: Vec<(&[u8], &[u8])> = input_refs
+        .iter()
+        .zip(&vetkeys)
+        .map(|(input, vetkey)| (*input, vetkey.signature_bytes().as_slice()))
+        .collect();
+
+    for (input, signature) in &batch {
+        assert!(verifier.verify(input, signature));
+        assert!(verify_bls_signature(&dpk, input, signature));
+    }
+    assert!(verifier.verify_batch(&batch));
+    assert!(verifier.verify_batch(&batch[..1]));
+    assert!(verifier.verify_batch(&[]));
+}
+
+#[test]
+fn bls_signature_verifier_rejects_invalid_signatures() {
+    let rng = &mut reproducible_rng();
+    let inputs: Vec<Vec<u8>> = (0..10)
+        .map(|i| format!("message {i}").into_bytes())
+        .collect();
+    let input_refs: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+    let (dpk, vetkeys) = emulated_vetkeys(rng, &input_refs);
+    let (other_dpk, _) = emulated_vetkeys(rng, &input_refs);
+
+    let verifier = BlsSignatureVerifier::new(&dpk);
+
+    let mut batch: Vec<(&[u8], &[u8])> = input_refs
+        .iter()
+        .zip(&vetkeys)
+        .map(|(input, vetkey)| (*input, vetkey.signature_bytes().as_slice()))
+        .collect();
+
+    assert!(!BlsSignatureVerifier::new(&other_dpk).verify_batch(&batch));
+
+    let mut swapped = batch.clone();
+    swapped[3].1 = batch[7].1;
+    swapped[7].1 = batch[3].1;
+    assert!(!verifier.verify(swapped[3].0, swapped[3].1));
+    assert!(!verifier.verify_batch(&swapped));
+
+    let invalid_signature = [0u8; 48];
+    batch[5].1 = &invalid_signature;
+    assert!(!verifier.verify(batch[5].0, batch[5].1));
+    assert!(!verifier.verify_batch(&batch));
+    assert!(!verifier.verify_batch(&batch[5..6]));
+}
+
+#[test]
+fn bls_signature_batch_verification_identifies_invalid_entries() {
+    let rng = &mut reproducible_rng();
+    let inputs: Vec<Vec<u8>> = (0..50)
+        .map(|i| format!("message {i}").into_bytes())
+        .collect();
+    let input_refs: Vec<&[u8]> = inputs.iter().map(|input| input.as_slice()).collect();
+    let (dpk, vetkeys) = emulated_vetkeys(rng, &input_refs);
+
+    let mut batch: Vec<(&[u8], &[u8])> = input_refs
+        .iter()
+        .zip(&vetkeys)
+        .map(|(input, vetkey)| (*input, vetkey.signature_bytes().as_slice()))
+        .collect();
+
+    assert_eq!(verify_bls_signatures_batch(&dpk, &batch), Ok(()));
+    assert_eq!(verify_bls_signatures_batch(&dpk, &[]), Ok(()));
+
+    let malformed_signature = [0u8; 47];
+    batch[3].1 = batch[4].1;
+    batch[17].1 = &malformed_signature;
+    batch[49].0 = b"another message";
+    assert_eq!(
+        verify_bls_signatures_batch(&dpk, &batch),
+        Err(vec![3, 17, 49])
+    );
+    assert_eq!(
+        BlsSignatureVerifier::new(&dpk).find_invalid_signatures(&batch[4..17]),
+        Ok(())
+    );
+}
+
+#[test]
+fn vrf_output_can_be_verified() {
+    let rng = &mut reproducible_rng();
+    let input = b"round 1";
+    let (dpk, vetkey) = emulated_vetkey(rng, input);
+
+    let output = VrfOutput::from_vetkey(vetkey.clone());
+    assert_eq!(output.proof(), vetkey.signature_bytes());
+    assert_eq!(
+        VrfOutput::verify(&dpk, input, output.proof()),
+        Ok(output.clone())
+    );
+
+    assert!(VrfOutput::verify(&dpk, b"round 2", output.proof()).is_err());
+    assert!(VrfOutput::verify(&dpk, input, &output.proof()[1..]).is_err());
+
+    let (_, other_vetkey) = emulated_vetkey(rng, input);
+    assert_ne!(
+        VrfOutput::from_vetkey(other_vetkey).randomness(),
+        output.randomness()
+    );
+}
+
+#[test]
+fn vrf_output_has_expected_randomness() {
+    let dpk = DerivedPublicKey::deserialize(&hex::decode("972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f").unwrap()).unwrap();
+    let proof = hex::decode("987db5406ce297e729c8564a106dc896943b00216a095fe9c5d32a16a330c02eb80e6f468ede83cde5462b5145b58f65").unwrap();
+
+    let output = VrfOutput::verify(&dpk, b"message", &proof).unwrap();
+
+    assert_eq!(
+        hex::encode(output.randomness()),
+        "dc2ec559b31810d34c1d00be2d2c720973269aba41dd64f3f0e64bedd3ac37eb"
+    );
+}
+
+fn emulated_vetkey<R: rand::RngCore + rand::CryptoRng>(
+    rng: &mut R,
+    identity: &[u8],
+) -> (DerivedPublicKey, VetKey) {
+    let (dpk, mut vetkeys) = emulated_vetkeys(rng, &[identity]);
+    (dpk, vetkeys.remove(0))
+}
+
+fn emulated_vetkeys<R: rand::RngCore + rand::CryptoRng>(
+    rng: &mut R,
+    identities: &[&[u8]],
+) -> (DerivedPublicKey, Vec<VetKey>) {
+    let derivation_context = DerivationContext::new(b"canister-id", b"context");
+
+    let tsk = TransportSecretKey::from_seed(rng.gen::<[u8; 32]>().to_vec()).unwrap();
+    let tpk = G1Affine::from_compressed(&tsk.public_key().try_into().unwrap()).unwrap();
+
+    let master_sk = random_scalar(rng);
+    let master_pk = G2Affine::from(G2Affine::generator() * master_sk);
+    let (derived_public_key, _delta) = derivation_context.derive_key(&master_pk);
+    let dpk = DerivedPublicKey::deserialize(&derived_public_key.to_compressed()).unwrap();
+
+    let vetkeys = identities
+        .iter()
+        .map(|identity| {
+            let ek_bytes = create_encrypted_key(
+                rng,
+                &master_pk,
+                &master_sk,
+                &tpk,
+                &derivation_context,
+                identity,
+            );
+            let ek = EncryptedVetKey::deserialize(&ek_bytes).unwrap();
+            ek.decrypt_and_verify(&tsk, &dpk, identity).unwrap()
+        })
+        .collect();
+
+    (dpk, vetkeys)
+}
+
+#[test]
+fn hybrid_ibe_encryption_roundtrips_for_various_lengths() {
+    let mut rng = reproducible_rng();
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    for len in [
+        0,
+        1,
+        IBE_HYBRID_CHUNK_BYTES - 1,
+        IBE_HYBRID_CHUNK_BYTES,
+        IBE_HYBRID_CHUNK_BYTES + 1,
+        3 * IBE_HYBRID_CHUNK_BYTES + 17,
+    ] {
+        let msg: Vec<u8> = (0..len).map(|_| rng.gen::<u8>()).collect();
+        let seed = IbeSeed::random(&mut rng);
+        let ctext = ibe_hybrid_encrypt(&dpk, &IbeIdentity::from_bytes(&identity), &msg, &seed);
+
+        assert_eq!(&ctext[..8], b"IC IBE\x00\x02");
+        assert_eq!(ibe_hybrid_decrypt(&ctext, &vetkey), Ok(msg));
+        assert!(IbeCiphertext::deserialize(&ctext).is_err());
+    }
+}
+
+#[test]
+fn hybrid_ibe_streaming_matches_one_shot_encryption() {
+    use std::io::{Read, Write};
+
+    let mut rng = reproducible_rng();
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    let msg: Vec<u8> = (0..2 * IBE_HYBRID_CHUNK_BYTES + 1000)
+        .map(|_| rng.gen::<u8>())
+        .collect();
+    let seed_bytes = rng.gen::<[u8; 32]>();
+    let seed = IbeSeed::from_bytes(&seed_bytes).unwrap();
+
+    let mut encryptor =
+        IbeHybridEncryptor::new(vec![], &dpk, &IbeIdentity::from_bytes(&identity), &seed).unwrap();
+    for part in msg.chunks(999) {
+        encryptor.write_all(part).unwrap();
+    }
+    let ctext = encryptor.finish().unwrap();
+
+    let seed = IbeSeed::from_bytes(&seed_bytes).unwrap();
+    assert_eq!(
+        ctext,
+        ibe_hybrid_encrypt(&dpk, &IbeIdentity::from_bytes(&identity), &msg, &seed)
+    );
+
+    let mut decryptor = IbeHybridDecryptor::new(ctext.as_slice(), &vetkey).unwrap();
+    let mut ptext = vec![];
+    let mut buf = [0u8; 777];
+    loop {
+        let len = decryptor.read(&mut buf).unwrap();
+        if len == 0 {
+            break;
+        }
+        ptext.extend_from_slice(&buf[..len]);
+    }
+    assert_eq!(ptext, msg);
+}
+
+#[test]
+fn hybrid_ibe_decryption_fails_for_modified_ciphertexts() {
+    let mut rng = reproducible_rng();
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+    let (_other_dpk, other_vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    let msg: Vec<u8> = (0..2 * IBE_HYBRID_CHUNK_BYTES + 5)
+        .map(|_| rng.gen::<u8>())
+        .collect();
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = ibe_hybrid_encrypt(&dpk, &IbeIdentity::from_bytes(&identity), &msg, &seed);
+
+    assert!(ibe_hybrid_decrypt(&ctext, &other_vetkey).is_err());
+
+    for _ in 0..10 {
+        let mut modified = ctext.clone();
+        let index = rng.gen_range(0..modified.len());
+        modified[index] ^= 1 << rng.gen_range(0..8);
+        assert!(ibe_hybrid_decrypt(&modified, &vetkey).is_err());
+    }
+
+    let encapsulated_key_bytes = 8 + 96 + 32 + 32;
+    let full_chunk_bytes = IBE_HYBRID_CHUNK_BYTES + 16;
+    for truncated_len in [
+        0,
+        encapsulated_key_bytes - 1,
+        encapsulated_key_bytes,
+        encapsulated_key_bytes + full_chunk_bytes,
+        encapsulated_key_bytes + 2 * full_chunk_bytes,
+        ctext.len() - 1,
+    ] {
+        assert!(ibe_hybrid_decrypt(&ctext[..truncated_len], &vetkey).is_err());
+    }
+
+    let mut extended = ctext.clone();
+    extended.push(0);
+    assert!(ibe_hybrid_decrypt(&extended, &vetkey).is_err());
+
+    let mut swapped = ctext.clone();
+    swapped.copy_within(
+        encapsulated_key_bytes..encapsulated_key_bytes + full_chunk_bytes,
+        encapsulated_key_bytes + full_chunk_bytes,
+    );
+    assert!(ibe_hybrid_decrypt(&swapped, &vetkey).is_err());
+}
+
+#[test]
+fn ibe_encryption_with_associated_data_roundtrips() {
+    let mut rng = reproducible_rng();
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    let msg = rng.gen::<[u8; 16]>().to_vec();
+    let associated_data = b"lot 1 bidder A".to_vec();
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = IbeCiphertext::encrypt_with_associated_data(
+        &dpk,
+        &IbeIdentity::from_bytes(&identity),
+        &msg,
+        &associated_data,
+        &seed,
+    );
+
+    let ctext = IbeCiphertext::deserialize(&ctext.serialize()).unwrap();
+    assert!(ctext.has_associated_data());
+    assert_eq!(
+        ctext.decrypt_with_associated_data(&vetkey, &associated_data),
+        Ok(msg)
+    );
+    assert!(ctext
+        .decrypt_with_associated_data(&vetkey, b"lot 2 bidder A")
+        .is_err());
+    assert!(ctext.decrypt_with_associated_data(&vetkey, b"").is_err());
+    assert!(ctext.decrypt(&vetkey).is_err());
+}
+
+#[test]
+fn ibe_encryption_without_associated_data_cannot_be_bound_to_associated_data() {
+    let mut rng = reproducible_rng();
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    let msg = rng.gen::<[u8; 16]>().to_vec();
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = IbeCiphertext::encrypt(&dpk, &IbeIdentity::from_bytes(&identity), &msg, &seed);
+
+    assert!(!ctext.has_associated_data());
+    assert_eq!(ctext.decrypt_with_associated_data(&vetkey, b""), Ok(msg));
+    assert_eq!(
+        ctext.decrypt_with_associated_data(&vetkey, b"associated data"),
+        Err(DecryptionError::AssociatedDataNotSupported)
+    );
+}
+
+#[test]
+fn ibe_encryption_with_associated_data_has_expected_output() {
+    let dpk = DerivedPublicKey::deserialize(&hex::decode("972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f").unwrap()).unwrap();
+
+    let identity = hex::decode("6d657373616765").unwrap();
+    let msg = hex::decode("f00f11").unwrap();
+    let associated_data = hex::decode("6c6f742031").unwrap();
+    let seed = IbeSeed::from_bytes(&[0u8; 32]).unwrap();
+    let ctext = IbeCiphertext::encrypt_with_associated_data(
+        &dpk,
+        &IbeIdentity::from_bytes(&identity),
+        &msg,
+        &associated_data,
+        &seed,
+    );
+
+    assert_eq!(hex::encode(ctext.serialize()),
+               "49432049424500038606da2563b5c47a35661f1a7a7d55164b37867b923bfa3bbb8f7e0af0023242df83df9d3ef1ab5aba4e4ce3dae75fe606e677132244a2515b035fd71540b3fe49c0e8658531fa80c52637160aff2ed71dcb479f7395159703d74bcaa0ac97bf9155cd85cce2fff173bd1cb62fa867eaf95cb3a5baa28a3a948f20f59afbcec89f7f98");
+
+    let vetkey = VetKey::deserialize(&hex::decode("987db5406ce297e729c8564a106dc896943b00216a095fe9c5d32a16a330c02eb80e6f468ede83cde5462b5145b58f65").unwrap()).unwrap();
+
+    assert_eq!(
+        ctext.decrypt_with_associated_data(&vetkey, &associated_data),
+        Ok(msg)
+    );
+}
+
+#[test]
+fn multi_recipient_ibe_encryption_can_be_decrypted_by_each_recipient() {
+    let mut rng = reproducible_rng();
+    let identities: Vec<[u8; 29]> = (0..5).map(|_| rng.gen::<[u8; 29]>()).collect();
+    let identity_refs: Vec<&[u8]> = identities.iter().map(|id| id.as_slice()).collect();
+    let (dpk, vetkeys) = emulated_vetkeys(&mut rng, &identity_refs);
+
+    let recipients: Vec<IbeIdentity> = identities[..4]
+        .iter()
+        .map(|id| IbeIdentity::from_bytes(id))
+        .collect();
+    let msg: Vec<u8> = (0..100).map(|_| rng.gen::<u8>()).collect();
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = IbeMultiRecipientCiphertext::encrypt(&dpk, &recipients, &msg, &seed);
+
+    let ctext_bytes = ctext.serialize();
+    assert_eq!(&ctext_bytes[..8], b"IC IBE\x00\x04");
+    assert_eq!(ctext_bytes.len(), 8 + 96 + 4 + 4 * 48 + msg.len());
+    let ctext = IbeMultiRecipientCiphertext::deserialize(&ctext_bytes).unwrap();
+    assert_eq!(ctext.recipient_count(), 4);
+
+    for vetkey in &vetkeys[..4] {
+        assert_eq!(ctext.decrypt(vetkey), Ok(msg.clone()));
+    }
+    assert_eq!(
+        ctext.decrypt(&vetkeys[4]),
+        Err(DecryptionError::NoMatchingRecipient)
+    );
+
+    assert!(IbeCiphertext::deserialize(&ctext_bytes).is_err());
+    assert!(IbeMultiRecipientCiphertext::deserialize(&ctext_bytes[..8 + 96 + 4 + 100]).is_err());
+
+    let mut modified = ctext_bytes.clone();
+    *modified.last_mut().unwrap() ^= 1;
+    let modified = IbeMultiRecipientCiphertext::deserialize(&modified).unwrap();
+    for vetkey in &vetkeys[..4] {
+        assert!(modified.decrypt(vetkey).is_err());
+    }
+}
+
+#[test]
+fn multi_recipient_ibe_encryption_deduplicates_identities() {
+    let mut rng = reproducible_rng();
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    let recipients = vec![IbeIdentity::from_bytes(&identity); 3];
+    let msg = rng.gen::<[u8; 16]>().to_vec();
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = IbeMultiRecipientCiphertext::encrypt(&dpk, &recipients, &msg, &seed);
+
+    assert_eq!(ctext.recipient_count(), 1);
+    assert_eq!(ctext.decrypt(&vetkey), Ok(msg));
+}
+
+#[test]
+fn multi_recipient_ibe_encryption_has_expected_output() {
+    let dpk = DerivedPublicKey::deserialize(&hex::decode("972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f").unwrap()).unwrap();
+
+    let recipients = [
+        IbeIdentity::from_bytes(&hex::decode("6d657373616765").unwrap()),
+        IbeIdentity::from_bytes(&hex::decode("6f74686572").unwrap()),
+    ];
+    let msg = hex::decode("f00f11").unwrap();
+    let seed = IbeSeed::from_bytes(&[0u8; 32]).unwrap();
+    let ctext = IbeMultiRecipientCiphertext::encrypt(&dpk, &recipients, &msg, &seed);
+
+    assert_eq!(hex::encode(ctext.serialize()),
+               "49432049424500048e021207f692c9a992b453ef52f85735b2e2dac7d776a0cb45ef053e5afbff6677b7675ffcefeb13bb51f7ffdeb10e160c0ff16d3d8e48e88462d63138ef2c037dece64e28e06ce6fd5870297cce1dcd3a73b019ba08ab7626f27ce53f6c7598000000024526604d27b14206a8f1d68dc9c78ffdb9a4a54898710307f3f40fcc3a6b8401c60ae5e20059050e6861bc7872e14fbde5f9a114bc3312a8746690b66924edd6873e41aa3c2308fbed53a07db3badb4697b692644b1ac84c1a5f5dd6e1a228ba9f7f98");
+
+    let vetkey = VetKey::deserialize(&hex::decode("987db5406ce297e729c8564a106dc896943b00216a095fe9c5d32a16a330c02eb80e6f468ede83cde5462b5145b58f65").unwrap()).unwrap();
+
+    assert_eq!(ctext.decrypt(&vetkey), Ok(msg));
+}
+
+#[test]
+fn errors_identify_the_kind_of_failure() {
+    let mut rng = reproducible_rng();
+
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+    let (_, other_vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    assert_eq!(
+        TransportSecretKey::from_seed(vec![0; 31]).err(),
+        Some(InvalidSeedError::InvalidLength)
+    );
+    assert_eq!(
+        IbeSeed::from_bytes(&[0; 15]).err(),
+        Some(InvalidSeedError::InsufficientInput)
+    );
+
+    assert_eq!(
+        DerivedPublicKey::deserialize(&[0; 95]),
+        Err(DeserializationError::InvalidLength)
+    );
+    assert_eq!(
+        DerivedPublicKey::deserialize(&[0xff; 96]),
+        Err(DeserializationError::InvalidPoint)
+    );
+    assert_eq!(
+        TransportSecretKey::deserialize(&[0xff; 32]).err(),
+        Some(DeserializationError::InvalidScalar)
+    );
+
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = IbeCiphertext::encrypt(&dpk, &IbeIdentity::from_bytes(&identity), b"msg", &seed);
+    let mut ctext_bytes = ctext.serialize();
+    ctext_bytes[7] = 0xff;
+    assert_eq!(
+        IbeCiphertext::deserialize(&ctext_bytes),
+        Err(DeserializationError::UnknownHeader)
+    );
+    assert_eq!(
+        ctext.decrypt(&other_vetkey),
+        Err(DecryptionError::DecryptionFailed)
+    );
+
+    let proof = vetkey.signature_bytes();
+    assert_eq!(
+        VrfOutput::verify(&dpk, b"other input", proof),
+        Err(VerificationError::InvalidSignature)
+    );
+
+    let hybrid_ctext = ibe_hybrid_encrypt(&dpk, &IbeIdentity::from_bytes(&identity), b"msg", &seed);
+    assert_eq!(
+        ibe_hybrid_decrypt(&hybrid_ctext[..100], &vetkey),
+        Err(Error::Deserialization(DeserializationError::InvalidLength))
+    );
+    assert_eq!(
+        ibe_hybrid_decrypt(&hybrid_ctext, &other_vetkey),
+        Err(Error::Decryption(DecryptionError::DecryptionFailed))
+    );
+
+    let error: Error = DecryptionError::NoMatchingRecipient.into();
+    assert_eq!(
+        error.to_string(),
+        "decryption failed: no matching recipient"
+    );
+    assert!(std::error::Error::source(&error).is_some());
+}
+
+#[test]
+fn random_transport_secret_keys_are_distinct() {
+    let mut rng = reproducible_rng();
+
+    let tsk1 = TransportSecretKey::random(&mut rng);
+    let tsk2 = TransportSecretKey::random(&mut rng);
+
+    assert_ne!(tsk1.serialize(), tsk2.serialize());
+    assert_ne!(tsk1.public_key(), tsk2.public_key());
+    assert!(is_valid_transport_public_key_encoding(&tsk1.public_key()));
+    assert_eq!(
+        TransportSecretKey::deserialize(&tsk1.serialize())
+            .unwrap()
+            .public_key(),
+        tsk1.public_key()
+    );
+}
+
+fn assert_byte_encoding_roundtrips<T>(value: &T, bytes: &[u8])
+where
+    T: candid::CandidType
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + ic_vetkeys::types::TryFromBytes
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let candid_bytes = candid::encode_one(value).unwrap();
+    assert_eq!(
+        candid_bytes,
+        candid::encode_one(serde_bytes::Bytes::new(bytes)).unwrap()
+    );
+    assert_eq!(&candid::decode_one::<T>(&candid_bytes).unwrap(), value);
+
+    let cbor_bytes = serde_cbor::to_vec(value).unwrap();
+    assert_eq!(
+        cbor_bytes,
+        serde_cbor::to_vec(&serde_bytes::Bytes::new(bytes)).unwrap()
+    );
+    assert_eq!(&serde_cbor::from_slice::<T>(&cbor_bytes).unwrap(), value);
+
+    assert_eq!(value.to_bytes().as_ref(), bytes);
+    assert_eq!(&T::from_bytes(value.to_bytes()), value);
+    assert_eq!(T::try_from_bytes(bytes).as_ref(), Ok(value));
+    assert!(T::try_from_bytes(&[]).is_err());
+}
+
+#[test]
+fn crypto_types_are_encoded_as_bytes() {
+    let mut rng = reproducible_rng();
+
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    let mpk_bytes = G2Affine::from(G2Affine::generator() * random_scalar(&mut rng)).to_compressed();
+    let mpk = MasterPublicKey::deserialize(&mpk_bytes).unwrap();
+    assert_byte_encoding_roundtrips(&mpk, &mpk_bytes);
+
+    assert_byte_encoding_roundtrips(&dpk, &dpk.serialize());
+    assert_byte_encoding_roundtrips(&vetkey, vetkey.signature_bytes());
+
+    let tsk = TransportSecretKey::random(&mut rng);
+    let tpk = G1Affine::from_compressed(&tsk.public_key().try_into().unwrap()).unwrap();
+    let master_sk = random_scalar(&mut rng);
+    let master_pk = G2Affine::from(G2Affine::generator() * master_sk);
+    let ek_bytes = create_encrypted_key(
+        &mut rng,
+        &master_pk,
+        &master_sk,
+        &tpk,
+        &DerivationContext::new(b"canister-id", b"context"),
+        &identity,
+    );
+    let ek = EncryptedVetKey::deserialize(&ek_bytes).unwrap();
+    assert_eq!(ek.serialize(), ek_bytes);
+    assert_byte_encoding_roundtrips(&ek, &ek_bytes);
+
+    let seed = IbeSeed::random(&mut rng);
+    let ctext = IbeCiphertext::encrypt(&dpk, &IbeIdentity::from_bytes(&identity), b"msg", &seed);
+    assert_byte_encoding_roundtrips(&ctext, &ctext.serialize());
+
+    let invalid = candid::encode_one(serde_bytes::Bytes::new(&[0xff; 48])).unwrap();
+    assert!(candid::decode_one::<VetKey>(&invalid).is_err());
+}
+
+#[test]
+fn unchecked_deserialization_accepts_both_encodings() {
+    let mut rng = reproducible_rng();
+
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    for bytes in [dpk.serialize(), dpk.serialize_uncompressed()] {
+        assert_eq!(
+            DerivedPublicKey::deserialize_unchecked(&bytes),
+            Ok(dpk.clone())
+        );
+    }
+    assert_eq!(
+        DerivedPublicKey::deserialize(&dpk.serialize_uncompressed()),
+        Err(DeserializationError::InvalidLength)
+    );
+
+    for bytes in [
+        vetkey.signature_bytes().to_vec(),
+        vetkey.serialize_uncompressed().to_vec(),
+    ] {
+        assert_eq!(VetKey::deserialize_unchecked(&bytes), Ok(vetkey.clone()));
+    }
+
+    let tsk = TransportSecretKey::random(&mut rng);
+    let tpk = G1Affine::from_compressed(&tsk.public_key().try_into().unwrap()).unwrap();
+    let master_sk = random_scalar(&mut rng);
+    let master_pk = G2Affine::from(G2Affine::generator() * master_sk);
+    let ek_bytes = create_encrypted_key(
+        &mut rng,
+        &master_pk,
+        &master_sk,
+        &tpk,
+        &DerivationContext::new(b"canister-id", b"context"),
+        &identity,
+    );
+    let ek = EncryptedVetKey::deserialize(&ek_bytes).unwrap();
+    for bytes in [ek.serialize(), ek.serialize_uncompressed()] {
+        assert_eq!(
+            EncryptedVetKey::deserialize_unchecked(&bytes),
+            Ok(ek.clone())
+        );
+    }
+
+    let mut off_curve = dpk.serialize_uncompressed();
+    *off_curve.last_mut().unwrap() ^= 1;
+    assert_eq!(
+        DerivedPublicKey::deserialize_unchecked(&off_curve),
+        Err(DeserializationError::InvalidPoint)
+    );
+    assert_eq!(
+        EncryptedVetKey::deserialize_unchecked(&ek_bytes[1..]),
+        Err(DeserializationError::InvalidLength)
+    );
+}
+
+#[test]
+fn stored_crypto_types_are_decoded_with_checks() {
+    use ic_vetkeys::types::TryFromBytes;
+
+    let mut rng = reproducible_rng();
+
+    let identity = rng.gen::<[u8; 32]>();
+    let (dpk, vetkey) = emulated_vetkey(&mut rng, &identity);
+
+    assert_eq!(
+        DerivedPublicKey::try_from_bytes(&dpk.serialize_uncompressed()),
+        Err(DeserializationError::InvalidLength)
+    );
+    assert_eq!(
+        VetKey::try_from_bytes(&vetkey.serialize_uncompressed()),
+        Err(DeserializationError::InvalidLength)
+    );
+
+    // a point on the curve that is not in the prime order subgroup
+    let mut x_is_zero = [0u8; 48];
+    x_is_zero[0] = 0x80;
+    let not_in_subgroup = G1Affine::from_compressed_unchecked(&x_is_zero).unwrap();
+    assert!(bool::from(not_in_subgroup.is_on_curve()));
+    assert!(!bool::from(not_in_subgroup.is_torsion_free()));
+    assert!(VetKey::deserialize_unchecked(&not_in_subgroup.to_compressed()).is_ok());
+    assert_eq!(
+        VetKey::try_from_bytes(&not_in_subgroup.to_compressed()),
+        Err(DeserializationError::InvalidPoint)
+    );
+}
+
+#[test]
+fn prepared_derived_public_key_gives_same_results() {
+    let mut rng = reproducible_rng();
+
+    let identities: Vec<[u8; 32]> = (0..3).map(|_| rng.gen()).collect();
+    let identity_refs: Vec<&[u8]> = identities.iter().map(|id| id.as_slice()).collect();
+    let (dpk, vetkeys) = emulated_vetkeys(&mut rng, &identity_refs);
+    let prepared = dpk.prepared();
+    assert_eq!(prepared.public_key(), &dpk);
+
+    for (identity, vetkey) in identities.iter().zip(&vetkeys) {
+        assert!(prepared.verify_bls_signature(identity, vetkey.signature_bytes()));
+        assert!(!prepared.verify_bls_signature(&identity[1..], vetkey.signature_bytes()));
+        assert!(!prepared.verify_bls_signature(identity, &vetkey.signature_bytes()[1..]));
+
+        let identity = IbeIdentity::from_bytes(identity);
+        let context = prepared.encryption_context(&identity);
+        assert_eq!(context.identity(), &identity);
+        assert_eq!(context.public_key(), &dpk);
+
+        for msg in [&b""[..], b"bid: 42", &[0x17; 100]] {
+            let seed_bytes = rng.gen::<[u8; 32]>();
+            let seed = IbeSeed::from_bytes(&seed_bytes).unwrap();
+
+            let ctext = context.encrypt(msg, &seed);
+            assert_eq!(ctext, IbeCiphertext::encrypt(&dpk, &identity, msg, &seed));
+            assert_eq!(
+                ctext,
+                IbeEncryptionContext::new(&dpk, &identity).encrypt(msg, &seed)
+            );
+            assert_eq!(ctext.decrypt(vetkey), Ok(msg.to_vec()));
+
+            let ctext = context.encrypt_with_associated_data(msg, b"lot 7", &seed);
+            assert_eq!(
+                ctext,
+                IbeCiphertext::encrypt_with_associated_data(&dpk, &identity, msg, b"lot 7", &seed)
+            );
+            assert_eq!(
+                ctext.decrypt_with_associated_data(vetkey, b"lot 7"),
+                Ok(msg.to_vec())
+            );
+        }
+    }
+
+    let verifier = BlsSignatureVerifier::from(prepared);
+    let batch: Vec<(&[u8], &[u8])> = identity_refs
+        .iter()
+        .zip(&vetkeys)
+        .map(|(identity, vetkey)| (*identity, vetkey.signature_bytes().as_slice()))
+        .collect();
+    assert!(verifier.verify_batch(&batch));
+}
@@ -0,0 +1,819 @@
+//! A state-machine test harness for the access control of
+//! [`EncryptedMaps`] and the underlying [`ic_vetkeys::key_manager::KeyManager`].
+//!
+//! [`AccessControlModel`] is a simple reference model of the permission
+//! semantics of [`AccessRights`]. [`EncryptedMapsStateMachine`] generates
+//! random sequences of [`Transition`]s with
+//! [`proptest_state_machine`], applies them both to the model and to an
+//! [`EncryptedMaps`] instance, and checks after every transition that both
+//! return the same [`Outcome`] and hold the same grants and values. The model
+//! states the permission rules as predicates rather than mirroring the checks
+//! of the implementation, and does not model error messages, so only whether
+//! an operation is rejected is compared.
+//!
+//! The transitions operate on a small fixed set of users, maps and map keys,
+//! so that random sequences frequently act on the same entries.
+//!
+//! ```rust,ignore
+//! use ic_vetkeys_test_utils::access_control_model::EncryptedMapsStateMachine;
+//! use proptest_state_machine::prop_state_machine;
+//!
+//! prop_state_machine! {
+//!     #[test]
+//!     fn encrypted_maps_follow_model(sequential 1..50 => EncryptedMapsStateMachine);
+//! }
+//! ```
+
+use crate::mock_vetkd::{InMemoryVetKdApi, MockVetKd};
+use candid::Principal;
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::storable::Blob;
+use ic_stable_structures::DefaultMemoryImpl;
+use ic_vetkeys::encrypted_maps::EncryptedMaps;
+use ic_vetkeys::types::{
+    AccessGrant, AccessRights, ByteBuf, EncryptedMapValue, MapId, MapKey, RemovedMapValues,
+};
+use proptest::prelude::*;
+use proptest::sample::select;
+use proptest_state_machine::{ReferenceStateMachine, StateMachineTest};
+use std::collections::BTreeMap;
+use strum::IntoEnumIterator;
+
+/// The number of users, excluding the anonymous principal.
+const NUM_USERS: u8 = 3;
+/// The number of users that own maps.
+const NUM_MAP_OWNERS: u8 = 2;
+/// The number of maps per owner.
+const NUM_MAPS_PER_OWNER: u8 = 1;
+/// The number of distinct keys per map.
+const NUM_MAP_KEYS: u8 = 2;
+
+/// The users acting on the maps, including the anonymous principal.
+pub fn users() -> Vec<Principal> {
+    (0..NUM_USERS)
+        .map(|i| Principal::self_authenticating([i; 32]))
+        .chain(std::iter::once(Principal::anonymous()))
+        .collect()
+}
+
+/// The maps that transitions act on.
+pub fn map_ids() -> Vec<MapId> {
+    let users = users();
+    (0..NUM_MAP_OWNERS)
+        .flat_map(|owner| {
+            (0..NUM_MAPS_PER_OWNER)
+                .map(move |name| (owner, Blob::try_from([b'm', name].as_slice()).unwrap()))
+        })
+        .map(|(owner, name)| (users[owner as usize], name))
+        .collect()
+}
+
+/// The map keys that transitions act on.
+pub fn map_keys() -> Vec<MapKey> {
+    (0..NUM_MAP_KEYS)
+        .map(|key| Blob::try_from([b'k', key].as_slice()).unwrap())
+        .collect()
+}
+
+/// An operation of a user on [`EncryptedMaps`].
+#[derive(Clone, Debug)]
+pub enum Transition {
+    SetUserRights {
+        caller: Principal,
+        map_id: MapId,
+        user: Principal,
+        access_rights: AccessRights,
+    },
+    SetPublicAccess {
+        caller: Principal,
+        map_id: MapId,
+        access_rights: Option<AccessRights>,
+    },
+    RemoveUser {
+        caller: Principal,
+        map_id: MapId,
+        user: Principal,
+    },
+    InsertEncryptedValue {
+        caller: Principal,
+        map_id: MapId,
+        key: MapKey,
+        value: EncryptedMapValue,
+    },
+    RemoveEncryptedValue {
+        caller: Principal,
+        map_id: MapId,
+        key: MapKey,
+    },
+    RemoveMapValues {
+        caller: Principal,
+        map_id: MapId,
+    },
+    GetEncryptedValue {
+        caller: Principal,
+        map_id: MapId,
+        key: MapKey,
+    },
+    GetEncryptedValuesForMap {
+        caller: Principal,
+        map_id: MapId,
+    },
+    GetAllAccessibleEncryptedValues {
+        caller: Principal,
+    },
+    GetAccessibleSharedMapNames {
+        caller: Principal,
+    },
+    GetUserRights {
+        caller: Principal,
+        map_id: MapId,
+        user: Principal,
+    },
+    GetSharedUserAccessForMap {
+        caller: Principal,
+        map_id: MapId,
+    },
+    GetEncryptedVetkey {
+        caller: Principal,
+        map_id: MapId,
+    },
+}
+
+/// The error of a rejected [`Transition`], regardless of the error message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rejected;
+
+fn rejected(_error: String) -> Rejected {
+    Rejected
+}
+
+/// The result of a [`Transition`]. Lists are sorted, so that outcomes can be
+/// compared independently of the iteration order of the implementation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    AccessRights(Result<Option<AccessRights>, Rejected>),
+    Value(Result<Option<EncryptedMapValue>, Rejected>),
+    RemovedValues(Result<RemovedMapValues, Rejected>),
+    Values(Result<Vec<(MapKey, EncryptedMapValue)>, Rejected>),
+    AllValues(Vec<(MapId, Vec<(MapKey, EncryptedMapValue)>)>),
+    MapIds(Vec<MapId>),
+    UserAccess(Result<Vec<(Principal, AccessRights)>, Rejected>),
+    Authorized(Result<(), Rejected>),
+}
+
+/// The permissions of [`AccessRights`] as documented for its variants.
+mod permission {
+    pub const READ: u8 = 1 << 0;
+    pub const DERIVE_KEY: u8 = 1 << 1;
+    pub const WRITE: u8 = 1 << 2;
+    pub const APPEND: u8 = 1 << 3;
+    pub const DELETE: u8 = 1 << 4;
+    pub const GET_USER_RIGHTS: u8 = 1 << 5;
+    pub const SET_USER_RIGHTS: u8 = 1 << 6;
+    pub const REMOVE_USER: u8 = 1 << 7;
+}
+
+fn permissions(access_rights: AccessRights) -> u8 {
+    use permission::*;
+    match access_rights {
+        AccessRights::Read => READ | DERIVE_KEY,
+        AccessRights::ReadWrite => READ | DERIVE_KEY | WRITE | APPEND | DELETE,
+        AccessRights::ReadWriteManage => u8::MAX,
+        AccessRights::ReadShare => READ | DERIVE_KEY | GET_USER_RIGHTS | SET_USER_RIGHTS,
+        AccessRights::ReadMetadata => READ,
+        AccessRights::ReadAppend => READ | DERIVE_KEY | APPEND,
+        AccessRights::Delete => DELETE,
+    }
+}
+
+fn has(access_rights: AccessRights, permission: u8) -> bool {
+    permissions(access_rights) & permission == permission
+}
+
+/// Users can only grant subsets of their own permissions.
+fn can_grant(granter: AccessRights, target: AccessRights) -> bool {
+    has(granter, permission::SET_USER_RIGHTS) && has(granter, permissions(target))
+}
+
+/// Whether `access_rights` has a strict subset of the permissions of `other`.
+fn has_strictly_fewer_permissions(access_rights: AccessRights, other: AccessRights) -> bool {
+    has(other, permissions(access_rights)) && permissions(access_rights) != permissions(other)
+}
+
+/// Reference model of the access control and the values of [`EncryptedMaps`].
+#[derive(Clone, Debug, Default)]
+pub struct AccessControlModel {
+    /// Access rights and the granting user per (user, map). Public access is
+    /// a grant to the anonymous principal.
+    pub grants: BTreeMap<(Principal, MapId), AccessGrant<AccessRights>>,
+    pub values: BTreeMap<(MapId, MapKey), EncryptedMapValue>,
+    /// The outcome of the last transition.
+    pub last_outcome: Option<Outcome>,
+}
+
+impl AccessControlModel {
+    /// Applies `transition` to the model and returns its outcome.
+    pub fn execute(&mut self, transition: &Transition) -> Outcome {
+        use permission::*;
+        match transition.clone() {
+            Transition::SetUserRights {
+                caller,
+                map_id,
+                user,
+                access_rights,
+            } => Outcome::AccessRights(self.set_user_rights(caller, map_id, user, access_rights)),
+            Transition::SetPublicAccess {
+                caller,
+                map_id,
+                access_rights,
+            } => Outcome::AccessRights(self.set_public_access(caller, map_id, access_rights)),
+            Transition::RemoveUser {
+                caller,
+                map_id,
+                user,
+            } => Outcome::AccessRights(self.remove_user(caller, map_id, user)),
+            Transition::InsertEncryptedValue {
+                caller,
+                map_id,
+                key,
+                value,
+            } => {
+                let required = if self.values.contains_key(&(map_id, key)) {
+                    WRITE
+                } else {
+                    APPEND
+                };
+                Outcome::Value(
+                    self.rights(caller, map_id, required)
+                        .map(|_| self.values.insert((map_id, key), value)),
+                )
+            }
+            Transition::RemoveEncryptedValue {
+                caller,
+                map_id,
+                key,
+            } => Outcome::Value(
+                self.rights(caller, map_id, DELETE)
+                    .map(|_| self.values.remove(&(map_id, key))),
+            ),
+            Transition::RemoveMapValues { caller, map_id } => {
+                Outcome::RemovedValues(self.rights(caller, map_id, DELETE).map(|_| {
+                    let keys: Vec<_> = self
+                        .map_values(map_id)
+                        .into_iter()
+                        .map(|(k, _)| k)
+                        .collect();
+                    for key in keys.iter() {
+                        self.values.remove(&(map_id, *key));
+                    }
+                    // only callers that can read the map learn the removed keys
+                    if self.rights(caller, map_id, READ).is_ok() {
+                        RemovedMapValues::Keys(keys)
+                    } else {
+                        RemovedMapValues::Count(keys.len() as u64)
+                    }
+                }))
+            }
+            Transition::GetEncryptedValue {
+                caller,
+                map_id,
+                key,
+            } => Outcome::Value(
+                self.rights(caller, map_id, READ)
+                    .map(|_| self.values.get(&(map_id, key)).cloned()),
+            ),
+            Transition::GetEncryptedValuesForMap { caller, map_id } => Outcome::Values(
+                self.rights(caller, map_id, READ)
+                    .map(|_| self.map_values(map_id)),
+            ),
+            Transition::GetAllAccessibleEncryptedValues { caller } => {
+                let mut accessible_map_ids = self.shared_map_ids(caller);
+                accessible_map_ids.extend(
+                    self.values
+                        .keys()
+                        .map(|(map_id, _)| *map_id)
+                        .filter(|map_id| map_id.0 == caller),
+                );
+                accessible_map_ids.sort();
+                accessible_map_ids.dedup();
+                Outcome::AllValues(
+                    accessible_map_ids
+                        .into_iter()
+                        .filter(|map_id| self.rights(caller, *map_id, READ).is_ok())
+                        .map(|map_id| (map_id, self.map_values(map_id)))
+                        .collect(),
+                )
+            }
+            Transition::GetAccessibleSharedMapNames { caller } => {
+                Outcome::MapIds(self.shared_map_ids(caller))
+            }
+            Transition::GetUserRights {
+                caller,
+                map_id,
+                user,
+            } => Outcome::AccessRights(self.rights(caller, map_id, GET_USER_RIGHTS).map(|_| {
+                if user == map_id.0 {
+                    Some(AccessRights::ReadWriteManage)
+                } else {
+                    self.access_rights(user, map_id)
+                        .or_else(|| self.access_rights(Principal::anonymous(), map_id))
+                }
+            })),
+            Transition::GetSharedUserAccessForMap { caller, map_id } => {
+                Outcome::UserAccess(self.rights(caller, map_id, GET_USER_RIGHTS).map(|_| {
+                    self.grants
+                        .iter()
+                        .filter(|((_, id), _)| id == &map_id)
+                        .map(|((user, _), grant)| (*user, grant.access_rights))
+                        .collect()
+                }))
+            }
+            Transition::GetEncryptedVetkey { caller, map_id } => {
+                Outcome::Authorized(self.rights(caller, map_id, DERIVE_KEY).map(|_| ()))
+            }
+        }
+    }
+
+    /// The owner has all permissions. Other users have the permissions of
+    /// their own grant or of public access, whichever includes `required`.
+    fn rights(
+        &self,
+        user: Principal,
+        map_id: MapId,
+        required: u8,
+    ) -> Result<AccessRights, Rejected> {
+        if user == map_id.0 {
+            return Ok(AccessRights::ReadWriteManage);
+        }
+        [user, Principal::anonymous()]
+            .into_iter()
+            .filter_map(|grantee| self.grants.get(&(grantee, map_id)))
+            .map(|grant| grant.access_rights)
+            .find(|access_rights| has(*access_rights, required))
+            .ok_or(Rejected)
+    }
+
+    /// The access rights granted to `user`, excluding public access.
+    fn access_rights(&self, user: Principal, map_id: MapId) -> Option<AccessRights> {
+        self.grants
+            .get(&(user, map_id))
+            .map(|grant| grant.access_rights)
+    }
+
+    fn set_user_rights(
+        &mut self,
+        caller: Principal,
+        map_id: MapId,
+        user: Principal,
+        access_rights: AccessRights,
+    ) -> Result<Option<AccessRights>, Rejected> {
+        if !self.may_set_user_rights(caller, map_id, user, access_rights) {
+            return Err(Rejected);
+        }
+        let previous_rights = self.access_rights(user, map_id);
+        // re-granting the same rights keeps the original grant
+        if previous_rights != Some(access_rights) {
+            let grant = AccessGrant {
+                access_rights,
+                granted_by: caller,
+            };
+            self.grants.insert((user, map_id), grant);
+        }
+        Ok(previous_rights)
+    }
+
+    fn set_public_access(
+        &mut self,
+        caller: Principal,
+        map_id: MapId,
+        access_rights: Option<AccessRights>,
+    ) -> Result<Option<AccessRights>, Rejected> {
+        if !self.may_set_public_access(caller, map_id, access_rights) {
+            return Err(Rejected);
+        }
+        let key = (Principal::anonymous(), map_id);
+        let previous_grant = match access_rights {
+            Some(access_rights) => {
+                let grant = AccessGrant {
+                    access_rights,
+                    granted_by: caller,
+                };
+                self.grants.insert(key, grant)
+            }
+            None => self.grants.remove(&key),
+        };
+        Ok(previous_grant.map(|grant| grant.access_rights))
+    }
+
+    fn remove_user(
+        &mut self,
+        caller: Principal,
+        map_id: MapId,
+        user: Principal,
+    ) -> Result<Option<AccessRights>, Rejected> {
+        if !self.may_remove_user(caller, map_id, user) {
+            return Err(Rejected);
+        }
+        Ok(self
+            .grants
+            .remove(&(user, map_id))
+            .map(|grant| grant.access_rights))
+    }
+
+    /// Users with the permission to set user rights may grant a subset of
+    /// their permissions to anybody but the owner, themselves, and the
+    /// anonymous principal. Changing an existing grant to different rights
+    /// additionally requires the permission to remove users, and the caller
+    /// must be allowed to modify the existing grant.
+    fn may_set_user_rights(
+        &self,
+        caller: Principal,
+        map_id: MapId,
+        user: Principal,
+        access_rights: AccessRights,
+    ) -> bool {
+        let Ok(caller_rights) = self.rights(caller, map_id, permission::SET_USER_RIGHTS) else {
+            return false;
+        };
+        let may_change_existing_grant = match self.grants.get(&(user, map_id)) {
+            None => true,
+            Some(grant) => {
+                grant.access_rights == access_rights
+                    || (has(caller_rights, permission::REMOVE_USER)
+                        && may_modify(caller, caller_rights, map_id, grant))
+            }
+        };
+        ![map_id.0, caller, Principal::anonymous()].contains(&user)
+            && can_grant(caller_rights, access_rights)
+            && may_change_existing_grant
+    }
+
+    /// Only the owner may set public access, and public access never includes
+    /// management permissions.
+    fn may_set_public_access(
+        &self,
+        caller: Principal,
+        map_id: MapId,
+        access_rights: Option<AccessRights>,
+    ) -> bool {
+        caller == map_id.0
+            && access_rights.is_none_or(|access_rights| {
+                !has(access_rights, permission::SET_USER_RIGHTS)
+                    && !has(access_rights, permission::REMOVE_USER)
+            })
+    }
+
+    /// Users with the permission to remove users may remove themselves and
+    /// any grant they may modify, except for the owner and public access.
+    fn may_remove_user(&self, caller: Principal, map_id: MapId, user: Principal) -> bool {
+        let Ok(caller_rights) = self.rights(caller, map_id, permission::REMOVE_USER) else {
+            return false;
+        };
+        ![map_id.0, Principal::anonymous()].contains(&user)
+            && (user == caller
+                || self
+                    .grants
+                    .get(&(user, map_id))
+                    .is_none_or(|grant| may_modify(caller, caller_rights, map_id, grant)))
+    }
+
+    fn shared_map_ids(&self, user: Principal) -> Vec<MapId> {
+        self.grants
+            .keys()
+            .filter(|(grantee, _)| grantee == &user)
+            .map(|(_, map_id)| *map_id)
+            .collect()
+    }
+
+    fn map_values(&self, map_id: MapId) -> Vec<(MapKey, EncryptedMapValue)> {
+        self.values
+            .iter()
+            .filter(|((id, _), _)| id == &map_id)
+            .map(|((_, key), value)| (*key, value.clone()))
+            .collect()
+    }
+}
+
+/// The owner may modify any grant, other users only grants that they created
+/// or grants with strictly fewer permissions than their own.
+fn may_modify(
+    caller: Principal,
+    caller_rights: AccessRights,
+    map_id: MapId,
+    grant: &AccessGrant<AccessRights>,
+) -> bool {
+    caller == map_id.0
+        || caller == grant.granted_by
+        || has_strictly_fewer_permissions(grant.access_rights, caller_rights)
+}
+
+impl ReferenceStateMachine for AccessControlModel {
+    type State = Self;
+    type Transition = Transition;
+
+    fn init_state() -> BoxedStrategy<Self::State> {
+        Just(Self::default()).boxed()
+    }
+
+    /// Callers are mostly chosen among the owner and the users with access to
+    /// the map, so that sequences often exercise shared and delegated rights.
+    fn transitions(state: &Self::State) -> BoxedStrategy<Self::Transition> {
+        let maps_with_users: Vec<(MapId, Vec<Principal>)> = map_ids()
+            .into_iter()
+            .map(|map_id| {
+                let users_with_access = std::iter::once(map_id.0)
+                    .chain(
+                        state
+                            .grants
+                            .keys()
+                            .filter(|(_, id)| id == &map_id)
+                            .map(|(user, _)| *user),
+                    )
+                    .collect();
+                (map_id, users_with_access)
+            })
+            .collect();
+        let caller = || select(users());
+        let caller_map_and_user = move || {
+            select(maps_with_users.clone()).prop_flat_map(|(map_id, users_with_access)| {
+                let caller =
+                    prop_oneof![3 => select(users_with_access.clone()), 1 => select(users())];
+                let user = prop_oneof![select(users_with_access), select(users())];
+                (caller, Just(map_id), user)
+            })
+        };
+        let caller_and_map = {
+            let caller_map_and_user = caller_map_and_user.clone();
+            move || caller_map_and_user().prop_map(|(caller, map_id, _)| (caller, map_id))
+        };
+        let key = || select(map_keys());
+        let access_rights = || select(AccessRights::iter().collect::<Vec<_>>());
+        let value = proptest::collection::vec(any::<u8>(), 0..4).prop_map(ByteBuf::from);
+
+        prop_oneof![
+            4 => (caller_map_and_user(), access_rights()).prop_map(
+                |((caller, map_id, user), access_rights)| Transition::SetUserRights {
+                    caller,
+                    map_id,
+                    user,
+                    access_rights,
+                }
+            ),
+            1 => (caller_and_map(), proptest::option::of(access_rights())).prop_map(
+                |((caller, map_id), access_rights)| Transition::SetPublicAccess {
+                    caller,
+                    map_id,
+                    access_rights,
+                }
+            ),
+            2 => caller_map_and_user().prop_map(|(caller, map_id, user)| {
+                Transition::RemoveUser {
+                    caller,
+                    map_id,
+                    user,
+                }
+            }),
+            4 => (caller_and_map(), key(), value).prop_map(|((caller, map_id), key, value)| {
+                Transition::InsertEncryptedValue {
+                    caller,
+                    map_id,
+                    key,
+                    value,
+                }
+            }),
+            1 => (caller_and_map(), key()).prop_map(|((caller, map_id), key)| {
+                Transition::RemoveEncryptedValue {
+                    caller,
+                    map_id,
+                    key,
+                }
+            }),
+            1 => caller_and_map()
+                .prop_map(|(caller, map_id)| Transition::RemoveMapValues { caller, map_id }),
+            2 => (caller_and_map(), key()).prop_map(|((caller, map_id), key)| {
+                Transition::GetEncryptedValue {
+                    caller,
+                    map_id,
+                    key,
+                }
+            }),
+            1 => caller_and_map().prop_map(|(caller, map_id)| {
+                Transition::GetEncryptedValuesForMap { caller, map_id }
+            }),
+            1 => caller().prop_map(|caller| Transition::GetAllAccessibleEncryptedValues { caller }),
+            1 => caller().prop_map(|caller| Transition::GetAccessibleSharedMapNames { caller }),
+            1 => caller_map_and_user().prop_map(|(caller, map_id, user)| {
+                Transition::GetUserRights {
+                    caller,
+                    map_id,
+                    user,
+                }
+            }),
+            1 => caller_and_map().prop_map(|(caller, map_id)| {
+                Transition::GetSharedUserAccessForMap { caller, map_id }
+            }),
+            1 => caller_and_map()
+                .prop_map(|(caller, map_id)| Transition::GetEncryptedVetkey { caller, map_id }),
+        ]
+        .boxed()
+    }
+
+    fn apply(mut state: Self::State, transition: &Self::Transition) -> Self::State {
+        state.last_outcome = Some(state.execute(transition));
+        state
+    }
+}
+
+/// Runs [`Transition`]s against [`EncryptedMaps`] and compares the outcomes
+/// and the stored state with the [`AccessControlModel`].
+pub struct EncryptedMapsStateMachine;
+
+impl EncryptedMapsStateMachine {
+    /// Applies `transition` to `encrypted_maps` and returns its outcome.
+    pub fn execute(
+        encrypted_maps: &mut EncryptedMaps<AccessRights, InMemoryVetKdApi>,
+        transition: Transition,
+    ) -> Outcome {
+        match transition {
+            Transition::SetUserRights {
+                caller,
+                map_id,
+                user,
+                access_rights,
+            } => Outcome::AccessRights(
+                encrypted_maps
+                    .set_user_rights(caller, map_id, user, access_rights)
+                    .map_err(rejected),
+            ),
+            Transition::SetPublicAccess {
+                caller,
+                map_id,
+                access_rights,
+            } => Outcome::AccessRights(
+                encrypted_maps
+                    .set_public_access(caller, map_id, access_rights)
+                    .map_err(rejected),
+            ),
+            Transition::RemoveUser {
+                caller,
+                map_id,
+                user,
+            } => Outcome::AccessRights(
+                encrypted_maps
+                    .remove_user(caller, map_id, user)
+                    .map_err(rejected),
+            ),
+            Transition::InsertEncryptedValue {
+                caller,
+                map_id,
+                key,
+                value,
+            } => Outcome::Value(
+                encrypted_maps
+                    .insert_encrypted_value(caller, map_id, key, value)
+                    .map_err(rejected),
+            ),
+            Transition::RemoveEncryptedValue {
+                caller,
+                map_id,
+                key,
+            } => Outcome::Value(
+                encrypted_maps
+                    .remove_encrypted_value(caller, map_id, key)
+                    .map_err(rejected),
+            ),
+            Transition::RemoveMapValues { caller, map_id } => Outcome::RemovedValues(
+                encrypted_maps
+                    .remove_map_values(caller, map_id)
+                    .map(|removed| match removed {
+                        RemovedMapValues::Keys(keys) => RemovedMapValues::Keys(sorted(keys)),
+                        count @ RemovedMapValues::Count(_) => count,
+                    })
+                    .map_err(rejected),
+            ),
+            Transition::GetEncryptedValue {
+                caller,
+                map_id,
+                key,
+            } => Outcome::Value(
+                encrypted_maps
+                    .get_encrypted_value(caller, map_id, key)
+                    .map_err(rejected),
+            ),
+            Transition::GetEncryptedValuesForMap { caller, map_id } => Outcome::Values(
+                encrypted_maps
+                    .get_encrypted_values_for_map(caller, map_id)
+                    .map(sorted)
+                    .map_err(rejected),
+            ),
+            Transition::GetAllAccessibleEncryptedValues { caller } => Outcome::AllValues(sorted(
+                encrypted_maps
+                    .get_all_accessible_encrypted_values(caller)
+                    .into_iter()
+                    .map(|(map_id, values)| (map_id, sorted(values)))
+                    .collect(),
+            )),
+            Transition::GetAccessibleSharedMapNames { caller } => Outcome::MapIds(sorted(
+                encrypted_maps
+                    .key_manager
+                    .get_accessible_shared_key_ids(caller),
+            )),
+            Transition::GetUserRights {
+                caller,
+                map_id,
+                user,
+            } => Outcome::AccessRights(
+                encrypted_maps
+                    .get_user_rights(caller, map_id, user)
+                    .map_err(rejected),
+            ),
+            Transition::GetSharedUserAccessForMap { caller, map_id } => Outcome::UserAccess(
+                encrypted_maps
+                    .get_shared_user_access_for_map(caller, map_id)
+                    .map(sorted_by_user)
+                    .map_err(rejected),
+            ),
+            Transition::GetEncryptedVetkey { caller, map_id } => {
+                // only authorization is compared, so the returned future is
+                // dropped and an invalid transport key avoids deriving a key
+                let transport_key = ByteBuf::from(vec![]);
+                Outcome::Authorized(
+                    encrypted_maps
+                        .get_encrypted_vetkey(caller, map_id, transport_key)
+                        .map(|_| ())
+                        .map_err(rejected),
+                )
+            }
+        }
+    }
+}
+
+fn sorted<T: Ord>(mut values: Vec<T>) -> Vec<T> {
+    values.sort();
+    values
+}
+
+/// Sorts by user only, since users are unique and access rights are not ordered.
+fn sorted_by_user(mut values: Vec<(Principal, AccessRights)>) -> Vec<(Principal, AccessRights)> {
+    values.sort_by_key(|(user, _)| *user);
+    values
+}
+
+impl StateMachineTest for EncryptedMapsStateMachine {
+    type SystemUnderTest = EncryptedMaps<AccessRights, InMemoryVetKdApi>;
+    type Reference = AccessControlModel;
+
+    fn init_test(_ref_state: &AccessControlModel) -> Self::SystemUnderTest {
+        let memory_manager = MemoryManager::init(DefaultMemoryImpl::default());
+        EncryptedMaps::init_with_vetkd_api(
+            "access control model",
+            memory_manager.get(MemoryId::new(0)),
+            memory_manager.get(MemoryId::new(1)),
+            memory_manager.get(MemoryId::new(2)),
+            memory_manager.get(MemoryId::new(3)),
+            InMemoryVetKdApi::new(
+                Principal::self_authenticating([u8::MAX; 32]),
+                MockVetKd::new([0; 32]),
+            ),
+        )
+    }
+
+    fn apply(
+        mut state: Self::SystemUnderTest,
+        ref_state: &AccessControlModel,
+        transition: Transition,
+    ) -> Self::SystemUnderTest {
+        let outcome = Self::execute(&mut state, transition);
+        assert_eq!(Some(outcome), ref_state.last_outcome);
+        state
+    }
+
+    fn check_invariants(state: &Self::SystemUnderTest, ref_state: &AccessControlModel) {
+        assert_eq!(state.find_corrupt_entries(), vec![]);
+
+        let grants: BTreeMap<_, _> = state
+            .key_manager
+            .access_control
+            .iter()
+            .map(|(key, grant)| (key, grant.into_valid().unwrap()))
+            .collect();
+        assert_eq!(grants, ref_state.grants);
+
+        let shared_keys: Vec<_> = state.key_manager.shared_keys.keys().collect();
+        let expected_shared_keys = sorted(
+            ref_state
+                .grants
+                .keys()
+                .map(|(user, map_id)| (*map_id, *user))
+                .collect(),
+        );
+        assert_eq!(sorted(shared_keys), expected_shared_keys);
+
+        let values: BTreeMap<_, _> = state
+            .mapkey_vals
+            .iter()
+            .map(|(key, value)| (key, value.into_valid().unwrap()))
+            .collect();
+        assert_eq!(values, ref_state.values);
+    }
+}
@@ -0,0 +1,13 @@
+//! Regenerates the test vectors shared by the Rust and TypeScript test suites
+//!
+//! See [`ic_vetkeys_test_utils::test_vectors`].
+
+use ic_vetkeys_test_utils::git_root_dir;
+use ic_vetkeys_test_utils::test_vectors::{TestVectors, TEST_VECTORS_PATH};
+
+fn main() {
+    let path = format!("{}/{TEST_VECTORS_PATH}", git_root_dir());
+    std::fs::write(&path, TestVectors::generate().to_json())
+        .unwrap_or_else(|e| panic!("failed to write {path}: {e}"));
+    println!("Wrote {path}");
+}
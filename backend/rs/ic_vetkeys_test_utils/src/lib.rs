@@ -1,5 +1,10 @@
 use std::{convert::TryInto, ops::Range};
 
+#[cfg(feature = "proptest")]
+pub mod access_control_model;
+pub mod mock_vetkd;
+pub mod test_vectors;
+
 use candid::Principal;
 use ic_bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
 use ic_bls12_381::*;
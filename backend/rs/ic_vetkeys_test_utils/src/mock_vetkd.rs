@@ -0,0 +1,255 @@
+//! An in-process emulation of the vetKD management canister API.
+//!
+//! [`MockVetKd`] implements `vetkd_public_key` and `vetkd_derive_key` with the
+//! same request and reply types as the management canister, but with a master
+//! key that is derived from a caller-provided seed. This allows code that uses
+//! vetKD to be tested deterministically without replica vetKD support. Faults
+//! can be injected per method to exercise error handling on the caller side.
+//!
+//! [`InMemoryVetKdApi`] wraps a [`MockVetKd`] as an
+//! [`ic_vetkeys::vetkd_api::VetKdApi`], so that `KeyManager`,
+//! `EncryptedMaps`, `Timelock`, `RandomBeacon` and `ThresholdBlsSigner` can
+//! be tested natively.
+
+use crate::{create_encrypted_key, random_scalar, DerivationContext};
+use candid::{CandidType, Deserialize, Principal};
+use ic_bls12_381::{G1Affine, G2Affine, Scalar};
+use ic_vetkeys::vetkd_api::{VetKdApi, VetKdApiFuture};
+use ic_vetkeys::vetkd_api_types::{
+    VetKDCurve, VetKDDeriveKeyReply, VetKDDeriveKeyRequest, VetKDKeyId, VetKDPublicKeyReply,
+    VetKDPublicKeyRequest,
+};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The key name accepted by a [`MockVetKd`] unless configured otherwise.
+pub const DEFAULT_KEY_NAME: &str = "dfx_test_key";
+
+/// The vetKD methods of the management canister.
+#[derive(CandidType, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VetKdMethod {
+    #[serde(rename = "vetkd_public_key")]
+    PublicKey,
+    #[serde(rename = "vetkd_derive_key")]
+    DeriveKey,
+}
+
+/// A fault that is injected into the reply of a single call.
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Reject the call with the given message.
+    Reject(String),
+    /// Reply with a well-formed key that is derived from a different master
+    /// key, so that it fails verification.
+    WrongKey,
+    /// Reply with bytes that are not a valid encoding of the expected key.
+    MalformedReply,
+}
+
+/// Native implementation of the vetKD management canister API.
+pub struct MockVetKd {
+    key_name: String,
+    master_secret_key: Scalar,
+    master_public_key: G2Affine,
+    wrong_master_secret_key: Scalar,
+    wrong_master_public_key: G2Affine,
+    rng: ChaCha20Rng,
+    public_key_faults: VecDeque<Fault>,
+    derive_key_faults: VecDeque<Fault>,
+}
+
+impl MockVetKd {
+    /// Creates a mock whose master key and encryption randomness are
+    /// deterministically derived from `seed`.
+    pub fn new(seed: [u8; 32]) -> Self {
+        let mut rng = ChaCha20Rng::from_seed(seed);
+        let master_secret_key = random_scalar(&mut rng);
+        let wrong_master_secret_key = random_scalar(&mut rng);
+        Self {
+            key_name: DEFAULT_KEY_NAME.to_string(),
+            master_secret_key,
+            master_public_key: G2Affine::from(G2Affine::generator() * master_secret_key),
+            wrong_master_secret_key,
+            wrong_master_public_key: G2Affine::from(
+                G2Affine::generator() * wrong_master_secret_key,
+            ),
+            rng,
+            public_key_faults: VecDeque::new(),
+            derive_key_faults: VecDeque::new(),
+        }
+    }
+
+    /// Sets the name of the only key that this mock accepts.
+    pub fn with_key_name(mut self, key_name: &str) -> Self {
+        self.key_name = key_name.to_string();
+        self
+    }
+
+    /// The id of the key that this mock accepts.
+    pub fn key_id(&self) -> VetKDKeyId {
+        VetKDKeyId {
+            curve: VetKDCurve::Bls12_381_G2,
+            name: self.key_name.clone(),
+        }
+    }
+
+    /// The serialized master public key, as accepted by
+    /// `ic_vetkeys::MasterPublicKey::deserialize`.
+    pub fn master_public_key(&self) -> Vec<u8> {
+        self.master_public_key.to_compressed().to_vec()
+    }
+
+    /// Injects a fault into the next valid call of `method`. Faults injected
+    /// into the same method are consumed in the order in which they were
+    /// injected. Calls that fail validation, e.g. because of an unknown key id
+    /// or an invalid transport public key, do not consume a fault.
+    pub fn inject_fault(&mut self, method: VetKdMethod, fault: Fault) {
+        match method {
+            VetKdMethod::PublicKey => self.public_key_faults.push_back(fault),
+            VetKdMethod::DeriveKey => self.derive_key_faults.push_back(fault),
+        }
+    }
+
+    /// Removes all faults that have not been consumed yet.
+    pub fn clear_faults(&mut self) {
+        self.public_key_faults.clear();
+        self.derive_key_faults.clear();
+    }
+
+    /// Emulates `vetkd_public_key` called by `caller`.
+    pub fn vetkd_public_key(
+        &mut self,
+        caller: Principal,
+        request: VetKDPublicKeyRequest,
+    ) -> Result<VetKDPublicKeyReply, String> {
+        self.ensure_known_key_id(&request.key_id)?;
+        let fault = self.public_key_faults.pop_front();
+
+        let canister_id = request.canister_id.unwrap_or(caller);
+        let context = DerivationContext::new(canister_id.as_slice(), &request.context);
+
+        let public_key = match fault {
+            None => context
+                .derive_key(&self.master_public_key)
+                .0
+                .to_compressed(),
+            Some(Fault::Reject(message)) => return Err(message),
+            Some(Fault::WrongKey) => context
+                .derive_key(&self.wrong_master_public_key)
+                .0
+                .to_compressed(),
+            Some(Fault::MalformedReply) => [0; 96],
+        };
+
+        Ok(VetKDPublicKeyReply {
+            public_key: public_key.to_vec(),
+        })
+    }
+
+    /// Emulates `vetkd_derive_key` called by `caller`.
+    pub fn vetkd_derive_key(
+        &mut self,
+        caller: Principal,
+        request: VetKDDeriveKeyRequest,
+    ) -> Result<VetKDDeriveKeyReply, String> {
+        self.ensure_known_key_id(&request.key_id)?;
+
+        let transport_public_key = <[u8; 48]>::try_from(request.transport_public_key.as_slice())
+            .ok()
+            .and_then(|bytes| Option::from(G1Affine::from_compressed(&bytes)))
+            .ok_or_else(|| "invalid transport public key".to_string())?;
+        let fault = self.derive_key_faults.pop_front();
+
+        let context = DerivationContext::new(caller.as_slice(), &request.context);
+
+        let (master_public_key, master_secret_key) = match fault {
+            None => (self.master_public_key, self.master_secret_key),
+            Some(Fault::Reject(message)) => return Err(message),
+            Some(Fault::WrongKey) => (self.wrong_master_public_key, self.wrong_master_secret_key),
+            Some(Fault::MalformedReply) => {
+                return Ok(VetKDDeriveKeyReply {
+                    encrypted_key: vec![0; 192],
+                })
+            }
+        };
+
+        Ok(VetKDDeriveKeyReply {
+            encrypted_key: create_encrypted_key(
+                &mut self.rng,
+                &master_public_key,
+                &master_secret_key,
+                &transport_public_key,
+                &context,
+                &request.input,
+            ),
+        })
+    }
+
+    /// Emulates `raw_rand`, returning bytes from the mock's deterministic
+    /// randomness.
+    pub fn raw_rand(&mut self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn ensure_known_key_id(&self, key_id: &VetKDKeyId) -> Result<(), String> {
+        if key_id.name == self.key_name {
+            Ok(())
+        } else {
+            Err(format!("unknown vetKD key: {}", key_id.name))
+        }
+    }
+}
+
+/// A [`VetKdApi`] that answers calls of the canister `canister_id` in-process
+/// with a [`MockVetKd`]. Clones share the same [`MockVetKd`].
+#[derive(Clone)]
+pub struct InMemoryVetKdApi {
+    canister_id: Principal,
+    mock: Arc<Mutex<MockVetKd>>,
+}
+
+impl InMemoryVetKdApi {
+    pub fn new(canister_id: Principal, mock: MockVetKd) -> Self {
+        Self {
+            canister_id,
+            mock: Arc::new(Mutex::new(mock)),
+        }
+    }
+
+    /// The id of the canister on whose behalf vetKD is called.
+    pub fn canister_id(&self) -> Principal {
+        self.canister_id
+    }
+
+    /// The underlying mock, e.g. for injecting faults.
+    pub fn mock(&self) -> MutexGuard<'_, MockVetKd> {
+        self.mock.lock().expect("mock vetKD poisoned")
+    }
+}
+
+impl VetKdApi for InMemoryVetKdApi {
+    fn vetkd_public_key(
+        &self,
+        request: VetKDPublicKeyRequest,
+    ) -> VetKdApiFuture<VetKDPublicKeyReply> {
+        let reply = self.mock().vetkd_public_key(self.canister_id, request);
+        Box::pin(std::future::ready(reply))
+    }
+
+    fn vetkd_derive_key(
+        &self,
+        request: VetKDDeriveKeyRequest,
+    ) -> VetKdApiFuture<VetKDDeriveKeyReply> {
+        let reply = self.mock().vetkd_derive_key(self.canister_id, request);
+        Box::pin(std::future::ready(reply))
+    }
+
+    fn raw_rand(&self) -> VetKdApiFuture<[u8; 32]> {
+        let bytes = self.mock().raw_rand();
+        Box::pin(std::future::ready(Ok(bytes)))
+    }
+}
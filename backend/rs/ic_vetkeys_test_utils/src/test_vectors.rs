@@ -0,0 +1,540 @@
+//! Test vectors shared by the Rust and TypeScript test suites
+//!
+//! The test vectors are generated by [`TestVectors::generate`] and committed
+//! to [`TEST_VECTORS_PATH`], from where both test suites read them. To
+//! regenerate the file after a change, run
+//!
+//! ```bash
+//! cargo run -p ic-vetkeys-test-utils --bin generate_test_vectors
+//! ```
+//!
+//! All byte strings are hex encoded. Transport secret keys are encoded as
+//! big-endian scalars, which is the encoding used by the TypeScript library;
+//! [`ic_vetkeys::TransportSecretKey::serialize`] uses little-endian instead.
+
+use crate::{create_encrypted_key, git_root_dir, random_scalar, DerivationContext};
+use ic_bls12_381::{G1Affine, G2Affine};
+use ic_vetkeys::{
+    derive_symmetric_key, DerivedPublicKey, EncryptedVetKey, IbeCiphertext, IbeIdentity, IbeSeed,
+    MasterPublicKey, TransportSecretKey, VetKey,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+/// Path of the test vector file, relative to the root of the repository
+pub const TEST_VECTORS_PATH: &str = "test_vectors/vetkeys.json";
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+/// The complete set of test vectors
+pub struct TestVectors {
+    /// Derivation of canister keys and sub keys from a master public key
+    pub key_derivation: Vec<KeyDerivationVector>,
+    /// Decryption and verification of encrypted VetKeys
+    pub encrypted_vetkeys: Vec<EncryptedVetKeyVector>,
+    /// IBE ciphertexts of messages of various lengths
+    pub ibe_ciphertexts: Vec<IbeCiphertextVector>,
+    /// Symmetric keys derived from arbitrary input using HKDF
+    pub symmetric_keys: Vec<SymmetricKeyVector>,
+    /// Symmetric keys derived from a VetKey
+    pub vetkey_symmetric_keys: Vec<VetKeySymmetricKeyVector>,
+    /// Encodings that must be rejected as derived public keys
+    pub invalid_derived_public_keys: Vec<InvalidEncodingVector>,
+    /// Encrypted VetKeys that must fail decryption or verification
+    pub invalid_encrypted_vetkeys: Vec<InvalidEncryptedVetKeyVector>,
+    /// IBE ciphertexts that must fail deserialization or decryption
+    pub invalid_ibe_ciphertexts: Vec<InvalidIbeCiphertextVector>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct KeyDerivationVector {
+    pub master_public_key: String,
+    pub canister_id: String,
+    pub canister_key: String,
+    pub context: String,
+    pub derived_public_key: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedVetKeyVector {
+    pub transport_secret_key: String,
+    pub transport_public_key: String,
+    pub derived_public_key: String,
+    pub input: String,
+    pub encrypted_vetkey: String,
+    pub vetkey: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct IbeCiphertextVector {
+    pub derived_public_key: String,
+    pub identity: String,
+    pub seed: String,
+    pub message: String,
+    pub associated_data: Option<String>,
+    pub ciphertext: String,
+    pub vetkey: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SymmetricKeyVector {
+    pub input: String,
+    pub domain_separator: String,
+    pub output: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct VetKeySymmetricKeyVector {
+    pub vetkey: String,
+    pub domain_separator: String,
+    pub output: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct InvalidEncodingVector {
+    pub description: String,
+    pub bytes: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct InvalidEncryptedVetKeyVector {
+    pub description: String,
+    pub transport_secret_key: String,
+    pub derived_public_key: String,
+    pub input: String,
+    pub encrypted_vetkey: String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct InvalidIbeCiphertextVector {
+    pub description: String,
+    pub vetkey: String,
+    pub ciphertext: String,
+    pub associated_data: Option<String>,
+}
+
+impl TestVectors {
+    /// Read the committed test vectors
+    pub fn load() -> Self {
+        let path = format!("{}/{TEST_VECTORS_PATH}", git_root_dir());
+        let json =
+            std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {path}: {e}"));
+        serde_json::from_str(&json).expect("invalid test vectors")
+    }
+
+    /// Encode the test vectors in the format of the committed file
+    pub fn to_json(&self) -> String {
+        let mut json = serde_json::to_string_pretty(self).expect("failed to encode test vectors");
+        json.push('\n');
+        json
+    }
+
+    /// Generate the test vectors
+    ///
+    /// The generation is deterministic, so that the output only changes if
+    /// the behavior of the library (or of this function) changes.
+    pub fn generate() -> Self {
+        let rng = &mut ChaCha20Rng::seed_from_u64(0);
+
+        // Values generated by an independent implementation, which are also
+        // used by the unit tests of both libraries
+        let known_master_public_key = "9183b871aa141d15ba2efc5bc58a49cb6a167741364804617f48dfe11e0285696b7018f172dad1a87ed81abf27ea4c320995041e2ee4a47b2226a2439d92a38557a7e2acc72fd157283b20f1f37ba872be235214c6a9cbba1eb2ef39deec72a5";
+        let known_encrypted_vetkey = EncryptedVetKeyInputs {
+            transport_secret_key: hex_decode(
+                "167b736e44a1c134bd46ca834220c75c186768612568ac264a01554c46633e76",
+            ),
+            derived_public_key: hex_decode("972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f"),
+            input: b"message".to_vec(),
+            encrypted_vetkey: hex_decode("b1a13757eaae15a3c8884fc1a3453f8a29b88984418e65f1bd21042ce1d6809b2f8a49f7326c1327f2a3921e8ff1d6c3adde2a801f1f88de98ccb40c62e366a279e7aec5875a0ce2f2a9f3e109d9cb193f0197eadb2c5f5568ee4d6a87e115910662e01e604087246be8b081fc6b8a06b4b0100ed1935d8c8d18d9f70d61718c5dba23a641487e72b3b25884eeede8feb3c71599bfbcebe60d29408795c85b4bdf19588c034d898e7fc513be8dbd04cac702a1672f5625f5833d063b05df7503"),
+        };
+
+        let master_secret_key = random_scalar(rng);
+        let master_public_key = G2Affine::from(G2Affine::generator() * master_secret_key);
+        let generated_master_public_key = hex::encode(master_public_key.to_compressed());
+
+        let key_derivation = [
+            (
+                known_master_public_key,
+                &b"test-canister-id"[..],
+                &b"test-context"[..],
+            ),
+            (&generated_master_public_key, b"test-canister-id", b""),
+            (&generated_master_public_key, &[0u8; 10], b"context"),
+            (&generated_master_public_key, b"", &[0xff; 100]),
+        ]
+        .into_iter()
+        .map(|(master_public_key, canister_id, context)| {
+            let mpk = MasterPublicKey::deserialize(&hex_decode(master_public_key)).unwrap();
+            let canister_key = mpk.derive_canister_key(canister_id);
+            KeyDerivationVector {
+                master_public_key: master_public_key.to_string(),
+                canister_id: hex::encode(canister_id),
+                canister_key: hex::encode(canister_key.serialize()),
+                context: hex::encode(context),
+                derived_public_key: hex::encode(canister_key.derive_sub_key(context).serialize()),
+            }
+        })
+        .collect();
+
+        let derivation_context = DerivationContext::new(b"canister-id", b"context");
+        let (derived_public_key, _delta) = derivation_context.derive_key(&master_public_key);
+        let derived_public_key = derived_public_key.to_compressed().to_vec();
+
+        let mut encrypted_vetkey_inputs = vec![known_encrypted_vetkey];
+        for input in [
+            b"".to_vec(),
+            b"message".to_vec(),
+            rng.gen::<[u8; 32]>().to_vec(),
+            vec![0x42; 100],
+        ] {
+            let transport_secret_key = TransportSecretKey::random(rng);
+            let transport_public_key =
+                G1Affine::from_compressed(&transport_secret_key.public_key().try_into().unwrap())
+                    .unwrap();
+            let encrypted_vetkey = create_encrypted_key(
+                rng,
+                &master_public_key,
+                &master_secret_key,
+                &transport_public_key,
+                &derivation_context,
+                &input,
+            );
+            encrypted_vetkey_inputs.push(EncryptedVetKeyInputs {
+                transport_secret_key: big_endian(&transport_secret_key.serialize()),
+                derived_public_key: derived_public_key.clone(),
+                input,
+                encrypted_vetkey,
+            });
+        }
+
+        let encrypted_vetkeys: Vec<_> = encrypted_vetkey_inputs
+            .iter()
+            .map(|inputs| {
+                let tsk = inputs.transport_secret_key();
+                EncryptedVetKeyVector {
+                    transport_secret_key: hex::encode(&inputs.transport_secret_key),
+                    transport_public_key: hex::encode(tsk.public_key()),
+                    derived_public_key: hex::encode(&inputs.derived_public_key),
+                    input: hex::encode(&inputs.input),
+                    encrypted_vetkey: hex::encode(&inputs.encrypted_vetkey),
+                    vetkey: hex::encode(inputs.decrypt_and_verify().signature_bytes()),
+                }
+            })
+            .collect();
+
+        let mut ibe_ciphertexts = vec![];
+        for (inputs, vector) in encrypted_vetkey_inputs
+            .iter()
+            .zip(&encrypted_vetkeys)
+            .take(2)
+        {
+            let dpk = DerivedPublicKey::deserialize(&inputs.derived_public_key).unwrap();
+            let identity = IbeIdentity::from_bytes(&inputs.input);
+            for msg_len in [0, 1, 3, 32, 33, 100, 1000] {
+                for associated_data in [None, Some(b"lot 1".to_vec())] {
+                    let seed = rng.gen::<[u8; 32]>();
+                    let message: Vec<u8> = (0..msg_len).map(|_| rng.gen()).collect();
+                    let ibe_seed = IbeSeed::from_bytes(&seed).unwrap();
+                    let ciphertext = match &associated_data {
+                        None => IbeCiphertext::encrypt(&dpk, &identity, &message, &ibe_seed),
+                        Some(ad) => IbeCiphertext::encrypt_with_associated_data(
+                            &dpk, &identity, &message, ad, &ibe_seed,
+                        ),
+                    };
+                    ibe_ciphertexts.push(IbeCiphertextVector {
+                        derived_public_key: vector.derived_public_key.clone(),
+                        identity: vector.input.clone(),
+                        seed: hex::encode(seed),
+                        message: hex::encode(message),
+                        associated_data: associated_data.map(hex::encode),
+                        ciphertext: hex::encode(ciphertext.serialize()),
+                        vetkey: vector.vetkey.clone(),
+                    });
+                }
+            }
+        }
+
+        let symmetric_keys = [
+            ("", "", 32),
+            ("0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b", "", 42),
+            ("f00f11", "ic-test-domain-sep", 16),
+            ("f00f11", "ic-test-domain-sep", 32),
+            ("f00f11", "ic-test-domain-sep-2", 32),
+            ("f00f11", "ic-test-domain-sep", 255),
+        ]
+        .into_iter()
+        .map(|(input, domain_separator, len)| SymmetricKeyVector {
+            input: input.to_string(),
+            domain_separator: domain_separator.to_string(),
+            output: hex::encode(derive_symmetric_key(
+                &hex_decode(input),
+                domain_separator,
+                len,
+            )),
+        })
+        .collect();
+
+        let vetkey_symmetric_keys = encrypted_vetkeys
+            .iter()
+            .take(2)
+            .flat_map(|vector| {
+                let vetkey = VetKey::deserialize(&hex_decode(&vector.vetkey)).unwrap();
+                [("ic-test-domain-sep", 32), ("password-manager", 16)].map(
+                    |(domain_separator, len)| VetKeySymmetricKeyVector {
+                        vetkey: vector.vetkey.clone(),
+                        domain_separator: domain_separator.to_string(),
+                        output: hex::encode(vetkey.derive_symmetric_key(domain_separator, len)),
+                    },
+                )
+            })
+            .collect();
+
+        let valid_dpk = hex_decode(&encrypted_vetkeys[1].derived_public_key);
+        let invalid_derived_public_keys = [
+            ("empty", vec![]),
+            ("too short", valid_dpk[..95].to_vec()),
+            ("too long", [&valid_dpk[..], &[0]].concat()),
+            ("compression flag not set", {
+                let mut bytes = valid_dpk.clone();
+                bytes[0] &= 0x7f;
+                bytes
+            }),
+            ("not a point on the curve", point_not_on_curve(&valid_dpk)),
+        ]
+        .into_iter()
+        .map(|(description, bytes)| {
+            assert!(DerivedPublicKey::deserialize(&bytes).is_err());
+            InvalidEncodingVector {
+                description: description.to_string(),
+                bytes: hex::encode(bytes),
+            }
+        })
+        .collect();
+
+        let valid = &encrypted_vetkey_inputs[2];
+        let other = &encrypted_vetkey_inputs[3];
+        let invalid_encrypted_vetkeys = [
+            (
+                "wrong transport secret key",
+                EncryptedVetKeyInputs {
+                    transport_secret_key: other.transport_secret_key.clone(),
+                    ..valid.clone()
+                },
+            ),
+            (
+                "wrong input",
+                EncryptedVetKeyInputs {
+                    input: b"other message".to_vec(),
+                    ..valid.clone()
+                },
+            ),
+            (
+                "wrong derived public key",
+                EncryptedVetKeyInputs {
+                    derived_public_key: encrypted_vetkey_inputs[0].derived_public_key.clone(),
+                    ..valid.clone()
+                },
+            ),
+            (
+                "c1 inconsistent with c2",
+                EncryptedVetKeyInputs {
+                    encrypted_vetkey: [
+                        &other.encrypted_vetkey[..48],
+                        &valid.encrypted_vetkey[48..],
+                    ]
+                    .concat(),
+                    ..valid.clone()
+                },
+            ),
+            (
+                "c3 modified",
+                EncryptedVetKeyInputs {
+                    encrypted_vetkey: [
+                        &valid.encrypted_vetkey[..144],
+                        &other.encrypted_vetkey[144..],
+                    ]
+                    .concat(),
+                    ..valid.clone()
+                },
+            ),
+            (
+                "truncated",
+                EncryptedVetKeyInputs {
+                    encrypted_vetkey: valid.encrypted_vetkey[..191].to_vec(),
+                    ..valid.clone()
+                },
+            ),
+        ]
+        .into_iter()
+        .map(|(description, inputs)| {
+            assert!(inputs.try_decrypt_and_verify().is_none());
+            InvalidEncryptedVetKeyVector {
+                description: description.to_string(),
+                transport_secret_key: hex::encode(&inputs.transport_secret_key),
+                derived_public_key: hex::encode(&inputs.derived_public_key),
+                input: hex::encode(&inputs.input),
+                encrypted_vetkey: hex::encode(&inputs.encrypted_vetkey),
+            }
+        })
+        .collect();
+
+        let plain = ibe_ciphertexts
+            .iter()
+            .find(|v| v.associated_data.is_none() && v.message.len() == 2 * 32)
+            .unwrap();
+        let bound = ibe_ciphertexts
+            .iter()
+            .find(|v| v.associated_data.is_some() && v.message.len() == 2 * 32)
+            .unwrap();
+        let other_identity = ibe_ciphertexts
+            .iter()
+            .find(|v| v.identity != plain.identity)
+            .unwrap();
+        let plain_ciphertext = hex_decode(&plain.ciphertext);
+        let flip_byte = |index: usize| {
+            let mut bytes = plain_ciphertext.clone();
+            bytes[index] ^= 1;
+            bytes
+        };
+        let header_len = 8;
+        let lot_2 = Some(hex::encode(b"lot 2"));
+        let invalid_ibe_ciphertexts = [
+            ("truncated", plain, plain_ciphertext[..100].to_vec(), None),
+            ("unknown header", plain, flip_byte(4), None),
+            ("c2 modified", plain, flip_byte(header_len + 96), None),
+            (
+                "c3 modified",
+                plain,
+                flip_byte(plain_ciphertext.len() - 1),
+                None,
+            ),
+            (
+                "wrong vetkey",
+                other_identity,
+                plain_ciphertext.clone(),
+                None,
+            ),
+            (
+                "wrong associated data",
+                bound,
+                hex_decode(&bound.ciphertext),
+                lot_2.clone(),
+            ),
+            (
+                "missing associated data",
+                bound,
+                hex_decode(&bound.ciphertext),
+                None,
+            ),
+            (
+                "associated data not supported",
+                plain,
+                plain_ciphertext.clone(),
+                lot_2,
+            ),
+        ]
+        .into_iter()
+        .map(
+            |(description, vetkey_source, ciphertext, associated_data)| {
+                let vector = InvalidIbeCiphertextVector {
+                    description: description.to_string(),
+                    vetkey: vetkey_source.vetkey.clone(),
+                    ciphertext: hex::encode(ciphertext),
+                    associated_data,
+                };
+                assert!(vector.try_decrypt().is_none(), "{description}");
+                vector
+            },
+        )
+        .collect();
+
+        Self {
+            key_derivation,
+            encrypted_vetkeys,
+            ibe_ciphertexts,
+            symmetric_keys,
+            vetkey_symmetric_keys,
+            invalid_derived_public_keys,
+            invalid_encrypted_vetkeys,
+            invalid_ibe_ciphertexts,
+        }
+    }
+}
+
+impl EncryptedVetKeyVector {
+    /// Return the transport secret key of this test vector
+    pub fn transport_secret_key(&self) -> TransportSecretKey {
+        transport_secret_key_from_big_endian(&hex_decode(&self.transport_secret_key))
+    }
+}
+
+impl InvalidEncryptedVetKeyVector {
+    /// Return the transport secret key of this test vector
+    pub fn transport_secret_key(&self) -> TransportSecretKey {
+        transport_secret_key_from_big_endian(&hex_decode(&self.transport_secret_key))
+    }
+}
+
+impl InvalidIbeCiphertextVector {
+    /// Try to deserialize and decrypt the ciphertext of this test vector
+    pub fn try_decrypt(&self) -> Option<Vec<u8>> {
+        let vetkey = VetKey::deserialize(&hex_decode(&self.vetkey)).ok()?;
+        let ciphertext = IbeCiphertext::deserialize(&hex_decode(&self.ciphertext)).ok()?;
+        match &self.associated_data {
+            None => ciphertext.decrypt(&vetkey),
+            Some(ad) => ciphertext.decrypt_with_associated_data(&vetkey, &hex_decode(ad)),
+        }
+        .ok()
+    }
+}
+
+#[derive(Clone)]
+struct EncryptedVetKeyInputs {
+    transport_secret_key: Vec<u8>,
+    derived_public_key: Vec<u8>,
+    input: Vec<u8>,
+    encrypted_vetkey: Vec<u8>,
+}
+
+impl EncryptedVetKeyInputs {
+    fn transport_secret_key(&self) -> TransportSecretKey {
+        transport_secret_key_from_big_endian(&self.transport_secret_key)
+    }
+
+    fn try_decrypt_and_verify(&self) -> Option<VetKey> {
+        let dpk = DerivedPublicKey::deserialize(&self.derived_public_key).ok()?;
+        let ek = EncryptedVetKey::deserialize(&self.encrypted_vetkey).ok()?;
+        ek.decrypt_and_verify(&self.transport_secret_key(), &dpk, &self.input)
+            .ok()
+    }
+
+    fn decrypt_and_verify(&self) -> VetKey {
+        self.try_decrypt_and_verify()
+            .expect("test vector must decrypt and verify")
+    }
+}
+
+fn transport_secret_key_from_big_endian(bytes: &[u8]) -> TransportSecretKey {
+    TransportSecretKey::deserialize(&big_endian(bytes)).expect("invalid transport secret key")
+}
+
+/// Reverse the byte order, which converts between little- and big-endian
+fn big_endian(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().rev().copied().collect()
+}
+
+/// Modify the encoding of a point until it is no longer a point on the curve
+fn point_not_on_curve(valid: &[u8]) -> Vec<u8> {
+    let mut bytes = valid.to_vec();
+    loop {
+        let last = bytes.len() - 1;
+        bytes[last] = bytes[last].wrapping_add(1);
+        if DerivedPublicKey::deserialize(&bytes).is_err() {
+            return bytes;
+        }
+    }
+}
+
+fn hex_decode(hex: &str) -> Vec<u8> {
+    hex::decode(hex).expect("invalid hex in test vector")
+}
@@ -5,6 +5,7 @@ use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemor
 use ic_stable_structures::{BTreeMap as StableBTreeMap, DefaultMemoryImpl};
 use serde_bytes::ByteBuf;
 use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::str::FromStr;
 
 mod types;
@@ -54,6 +55,50 @@ fn send_message(request: SendMessageRequest) -> Result<(), String> {
     })
 }
 
+#[update]
+/// Sends the same encrypted message to the inboxes of multiple receivers.
+/// The message is expected to be a multi-recipient IBE ciphertext.
+fn send_group_message(request: SendGroupMessageRequest) -> Result<(), String> {
+    let sender = ic_cdk::caller();
+    let SendGroupMessageRequest {
+        receivers,
+        encrypted_message,
+    } = request;
+    let timestamp = ic_cdk::api::time();
+
+    let receivers: BTreeSet<Principal> = receivers.into_iter().collect();
+    if receivers.is_empty() {
+        return Err("At least one receiver is required".to_string());
+    }
+    if receivers.len() > MAX_RECEIVERS_PER_GROUP_MESSAGE {
+        return Err(format!(
+            "At most {} receivers are allowed",
+            MAX_RECEIVERS_PER_GROUP_MESSAGE
+        ));
+    }
+
+    INBOXES.with_borrow_mut(|inboxes| {
+        if let Some(receiver) = receivers.iter().find(|receiver| {
+            inboxes
+                .get(receiver)
+                .is_some_and(|inbox| inbox.messages.len() >= MAX_MESSAGES_PER_INBOX)
+        }) {
+            return Err(format!("Inbox for {} is full", receiver));
+        }
+
+        for receiver in receivers {
+            let mut inbox = inboxes.get(&receiver).unwrap_or_default();
+            inbox.messages.push(Message {
+                sender,
+                encrypted_message: encrypted_message.clone(),
+                timestamp,
+            });
+            inboxes.insert(receiver, inbox);
+        }
+        Ok(())
+    })
+}
+
 #[update]
 async fn get_root_ibe_public_key() -> VetKeyPublicKey {
     let request = VetKDPublicKeyRequest {
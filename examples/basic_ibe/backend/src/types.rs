@@ -5,6 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
 pub const MAX_MESSAGES_PER_INBOX: usize = 1000;
+pub const MAX_RECEIVERS_PER_GROUP_MESSAGE: usize = 100;
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Message {
@@ -20,7 +21,7 @@ pub struct Inbox {
 }
 
 impl Storable for Inbox {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -32,7 +33,7 @@ impl Storable for Inbox {
 }
 
 impl Storable for Message {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -50,6 +51,13 @@ pub struct SendMessageRequest {
     pub encrypted_message: Vec<u8>,
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SendGroupMessageRequest {
+    pub receivers: Vec<Principal>,
+    #[serde(with = "serde_bytes")]
+    pub encrypted_message: Vec<u8>,
+}
+
 #[derive(CandidType, Deserialize, Clone, Debug)]
 pub enum VetKDCurve {
     #[serde(rename = "bls12_381_g2")]
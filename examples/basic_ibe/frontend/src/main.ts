@@ -8,6 +8,7 @@ import {
     VetKey,
     IbeCiphertext,
     IbeIdentity,
+    IbeMultiRecipientCiphertext,
     IbeSeed,
 } from "@dfinity/vetkeys";
 import {
@@ -88,25 +89,42 @@ async function sendMessage() {
     const message = prompt("Enter your message:");
     if (!message) throw Error("Message is required");
 
-    const receiver = prompt("Enter receiver principal:");
-    if (!receiver) throw Error("Receiver is required");
+    const receivers = prompt(
+        "Enter receiver principal (or multiple principals separated by commas):",
+    );
+    if (!receivers) throw Error("Receiver is required");
 
-    const receiverPrincipal = Principal.fromText(receiver);
+    const receiverPrincipals = receivers
+        .split(",")
+        .map((receiver) => Principal.fromText(receiver.trim()));
 
     try {
         const publicKey = await getRootIbePublicKey();
-
-        const encryptedMessage = IbeCiphertext.encrypt(
-            publicKey,
-            IbeIdentity.fromPrincipal(receiverPrincipal),
-            new TextEncoder().encode(message),
-            IbeSeed.random(),
-        );
-
-        const result = await getBasicIbeCanister().send_message({
-            encrypted_message: encryptedMessage.serialize(),
-            receiver: receiverPrincipal,
-        });
+        const plaintext = new TextEncoder().encode(message);
+
+        // A single message is encrypted once for all receivers of a group
+        const result =
+            receiverPrincipals.length === 1
+                ? await getBasicIbeCanister().send_message({
+                      encrypted_message: IbeCiphertext.encrypt(
+                          publicKey,
+                          IbeIdentity.fromPrincipal(receiverPrincipals[0]),
+                          plaintext,
+                          IbeSeed.random(),
+                      ).serialize(),
+                      receiver: receiverPrincipals[0],
+                  })
+                : await getBasicIbeCanister().send_group_message({
+                      encrypted_message: IbeMultiRecipientCiphertext.encrypt(
+                          publicKey,
+                          receiverPrincipals.map((receiver) =>
+                              IbeIdentity.fromPrincipal(receiver),
+                          ),
+                          plaintext,
+                          IbeSeed.random(),
+                      ).serialize(),
+                      receivers: receiverPrincipals,
+                  });
 
         if ("Err" in result) {
             alert("Error sending message: " + result.Err);
@@ -125,8 +143,13 @@ async function showMessages() {
 
 async function decryptMessage(encryptedMessage: Uint8Array): Promise<string> {
     const ibeKey = await getMyIbePrivateKey();
-    const ciphertext = IbeCiphertext.deserialize(encryptedMessage);
-    const plaintext = ciphertext.decrypt(ibeKey);
+    // The last byte of the header distinguishes group messages
+    const plaintext =
+        encryptedMessage[7] === 0x04
+            ? IbeMultiRecipientCiphertext.deserialize(encryptedMessage).decrypt(
+                  ibeKey,
+              )
+            : IbeCiphertext.deserialize(encryptedMessage).decrypt(ibeKey);
     return new TextDecoder().decode(plaintext);
 }
 
@@ -10,6 +10,10 @@ export interface Message {
 }
 export type Result = { 'Ok' : null } |
   { 'Err' : string };
+export interface SendGroupMessageRequest {
+  'receivers' : Array<Principal>,
+  'encrypted_message' : Uint8Array | number[],
+}
 export interface SendMessageRequest {
   'encrypted_message' : Uint8Array | number[],
   'receiver' : Principal,
@@ -22,6 +26,7 @@ export interface _SERVICE {
   'get_my_messages' : ActorMethod<[], Inbox>,
   'get_root_ibe_public_key' : ActorMethod<[], Uint8Array | number[]>,
   'remove_my_message_by_index' : ActorMethod<[bigint], Result>,
+  'send_group_message' : ActorMethod<[SendGroupMessageRequest], Result>,
   'send_message' : ActorMethod<[SendMessageRequest], Result>,
 }
 export declare const idlFactory: IDL.InterfaceFactory;
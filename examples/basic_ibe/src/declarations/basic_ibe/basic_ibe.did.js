@@ -6,6 +6,10 @@ export const idlFactory = ({ IDL }) => {
   });
   const Inbox = IDL.Record({ 'messages' : IDL.Vec(Message) });
   const Result = IDL.Variant({ 'Ok' : IDL.Null, 'Err' : IDL.Text });
+  const SendGroupMessageRequest = IDL.Record({
+    'receivers' : IDL.Vec(IDL.Principal),
+    'encrypted_message' : IDL.Vec(IDL.Nat8),
+  });
   const SendMessageRequest = IDL.Record({
     'encrypted_message' : IDL.Vec(IDL.Nat8),
     'receiver' : IDL.Principal,
@@ -19,6 +23,7 @@ export const idlFactory = ({ IDL }) => {
     'get_my_messages' : IDL.Func([], [Inbox], ['query']),
     'get_root_ibe_public_key' : IDL.Func([], [IDL.Vec(IDL.Nat8)], []),
     'remove_my_message_by_index' : IDL.Func([IDL.Nat64], [Result], []),
+    'send_group_message' : IDL.Func([SendGroupMessageRequest], [Result], []),
     'send_message' : IDL.Func([SendMessageRequest], [Result], []),
   });
 };
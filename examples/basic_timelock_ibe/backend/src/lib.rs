@@ -2,13 +2,13 @@ use crate::types::{
     BidCounter, DecryptedBid, EncryptedBid, LotId, LotInformation, VetKeyPublicKey,
 };
 use candid::Principal;
-use ic_cdk::api::management_canister::provisional::CanisterId;
 use ic_cdk::{init, post_upgrade, query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{BTreeMap as StableBTreeMap, DefaultMemoryImpl};
-use ic_vetkeys::{DerivedPublicKey, EncryptedVetKey};
+use ic_vetkeys::timelock::Timelock;
+use ic_vetkeys::vetkd_api::derive_public_vetkey;
+use ic_vetkeys::{DerivedPublicKey, IbeCiphertext, VetKey};
 use std::cell::RefCell;
-use std::str::FromStr;
 
 mod types;
 use types::*;
@@ -27,8 +27,18 @@ thread_local! {
     static BIDS_ON_LOTS: RefCell<StableBTreeMap<(LotId, BidCounter, Principal), Bid, Memory>> = RefCell::new(StableBTreeMap::init(
         MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
     ));
-    static OPEN_LOTS_DEADLINES: RefCell<StableBTreeMap<u64, LotId, Memory>> = RefCell::new(StableBTreeMap::init(
-        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
+    /// Keyed by deadline and lot, so that several lots can end at the same
+    /// time. Memory 2 holds the previous map from a deadline to a single lot,
+    /// which is migrated in `post_upgrade`, see [`migrate_open_lots_deadlines`].
+    static OPEN_LOTS_DEADLINES: RefCell<StableBTreeMap<(u64, LotId), (), Memory>> = RefCell::new(StableBTreeMap::init(
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+    ));
+
+    static TIMELOCK: RefCell<Timelock> = RefCell::new(Timelock::init(
+        DOMAIN_SEPARATOR,
+        TIMELOCK_GRANULARITY,
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3))),
+        MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
     ));
 
     static VETKD_ROOT_IBE_PUBLIC_KEY: RefCell<Option<VetKeyPublicKey>> =  const { RefCell::new(None) };
@@ -37,7 +47,10 @@ thread_local! {
 }
 
 const DOMAIN_SEPARATOR: &str = "basic_timelock_ibe_example_dapp";
-const CANISTER_ID_VETKD_SYSTEM_API: &str = "aaaaa-aa";
+const NANOS_IN_SEC: u64 = 1_000_000_000;
+/// Lots end at full seconds so that all lots ending in the same second share
+/// one time-lock decryption key.
+const TIMELOCK_GRANULARITY: u64 = NANOS_IN_SEC;
 
 #[init]
 fn init() {
@@ -46,9 +59,33 @@ fn init() {
 
 #[post_upgrade]
 fn post_upgrade() {
+    migrate_open_lots_deadlines();
     start_with_interval_secs(5);
 }
 
+/// Moves the open lots from the map in memory 2, which was keyed by deadline
+/// only, to [`OPEN_LOTS_DEADLINES`]. The old map is empty afterwards, so this
+/// is a no-op on later upgrades and on canisters installed with this version.
+///
+/// The deadlines of these lots are rounded up to the time-lock granularity,
+/// so that the lots are closed once the decryption key of their round can be
+/// derived. Their bids placed before the upgrade are encrypted to the lot id
+/// and are decrypted with [`derive_legacy_decryption_key`].
+fn migrate_open_lots_deadlines() {
+    let mut legacy_open_lots_deadlines: StableBTreeMap<u64, LotId, Memory> =
+        StableBTreeMap::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))));
+
+    OPEN_LOTS_DEADLINES.with_borrow_mut(|open_lots_deadlines| {
+        for (deadline, lot_id) in legacy_open_lots_deadlines.iter() {
+            let deadline = TIMELOCK
+                .with_borrow(|timelock| timelock.rounded_unlock_time(deadline))
+                .expect("lot end time is not representable");
+            open_lots_deadlines.insert((deadline, lot_id), ());
+        }
+    });
+    legacy_open_lots_deadlines.clear_new();
+}
+
 #[update(guard = is_authenticated)]
 fn create_lot(name: String, description: String, duration_seconds: u16) -> Result<LotId, String> {
     let caller = ic_cdk::caller();
@@ -60,21 +97,25 @@ fn create_lot(name: String, description: String, duration_seconds: u16) -> Resul
     let lot_id = LOTS.with_borrow_mut(|lots| {
         let lot_id = lots.len() as u128;
 
-        const NANOS_IN_SEC: u64 = 1_000_000_000;
         let start_time = ic_cdk::api::time();
+        let end_time = TIMELOCK
+            .with_borrow(|timelock| {
+                timelock.rounded_unlock_time(start_time + duration_seconds as u64 * NANOS_IN_SEC)
+            })
+            .expect("lot end time is not representable");
 
         let lot = LotInformation {
             id: lot_id,
             name,
             description,
             start_time,
-            end_time: start_time + duration_seconds as u64 * NANOS_IN_SEC,
+            end_time,
             creator: caller,
             status: LotStatus::Open,
         };
 
         OPEN_LOTS_DEADLINES.with_borrow_mut(|open_lots_deadlines| {
-            open_lots_deadlines.insert(lot.end_time, lot_id);
+            open_lots_deadlines.insert((lot.end_time, lot_id), ());
         });
 
         lots.insert(lot_id, lot);
@@ -91,21 +132,12 @@ async fn get_root_ibe_public_key() -> VetKeyPublicKey {
         return key;
     }
 
-    let request = VetKDPublicKeyRequest {
-        canister_id: None,
-        context: DOMAIN_SEPARATOR.as_bytes().to_vec(),
-        key_id: bls12_381_dfx_test_key(),
-    };
+    let public_key = TIMELOCK
+        .with_borrow(|timelock| timelock.get_timelock_public_key())
+        .await
+        .expect("failed to get time-lock public key");
 
-    let (result,) = ic_cdk::api::call::call::<_, (VetKDPublicKeyReply,)>(
-        vetkd_system_api_canister_id(),
-        "vetkd_public_key",
-        (request,),
-    )
-    .await
-    .expect("call to vetkd_public_key failed");
-
-    VetKeyPublicKey::from(result.public_key)
+    VetKeyPublicKey::from(public_key.as_ref().to_vec())
 }
 
 #[query(guard = is_authenticated)]
@@ -171,6 +203,17 @@ fn place_bid(lot_id: u128, encrypted_amount: Vec<u8>) -> Result<(), String> {
         return Err("encrypted amount is too large to be valid".to_string());
     }
 
+    // bids of previous frontend versions are not bound to the lot and the
+    // bidder and would fail to decrypt, see `bid_associated_data`
+    let ciphertext = IbeCiphertext::deserialize(&encrypted_amount)
+        .map_err(|_| "encrypted amount is not a valid IBE ciphertext".to_string())?;
+    if !ciphertext.has_associated_data() {
+        return Err(
+            "encrypted amount is not bound to the lot and the bidder, please reload the page"
+                .to_string(),
+        );
+    }
+
     BIDS_ON_LOTS.with_borrow_mut(|bids| {
         if let Some((existing_bid_key, _existing_bid)) = bids
             .range((lot_id, 0, Principal::management_canister())..)
@@ -205,127 +248,182 @@ fn start_with_interval_secs(secs: u64) {
 }
 
 async fn close_one_lot_if_any_is_open() {
-    let root_ibe_public_key =
-        if let Some(key) = VETKD_ROOT_IBE_PUBLIC_KEY.with_borrow(|key| key.clone()) {
-            key
-        } else {
-            get_root_ibe_public_key().await
+    let now = ic_cdk::api::time();
+
+    if let Some((deadline, lot_id)) = take_lot_to_close(now) {
+        if let Err(e) = close_lot(deadline, lot_id, now).await {
+            ic_cdk::println!("Failed to close lot id {lot_id}, retrying later: {e}");
+            // the lot was taken off the open lots deadlines before the first
+            // await, so it is queued again to be closed by a later timer
+            OPEN_LOTS_DEADLINES.with_borrow_mut(|open_lots_deadlines| {
+                open_lots_deadlines.insert((deadline, lot_id), ())
+            });
         }
-        .into_vec();
+    }
+}
 
-    let now = ic_cdk::api::time();
-    let lot_to_close: Option<LotId> = OPEN_LOTS_DEADLINES.with_borrow_mut(|open_lots_deadlines| {
+async fn close_lot(deadline: u64, lot_id: LotId, now: u64) -> Result<(), String> {
+    let (bid_counters, encrypted_bids) = encrypted_bids_on_lot(lot_id);
+
+    let ibe_decryption_key = publish_decryption_key(deadline, now).await?;
+    let legacy_decryption_key = if encrypted_bids.iter().any(is_legacy_bid) {
+        Some(derive_legacy_decryption_key(lot_id).await?)
+    } else {
+        None
+    };
+    let decrypted_bids = decrypt_bids(
+        lot_id,
+        encrypted_bids,
+        &ibe_decryption_key,
+        legacy_decryption_key.as_ref(),
+    );
+
+    let status = match decrypted_bids
+        .iter()
+        .rev() // reverse the bids to get the *oldest* maximum bid
+        .max_by(|x, y| x.amount.cmp(&y.amount))
+    {
+        Some(winner_bid) => LotStatus::ClosedWithWinner(winner_bid.bidder),
+        None => LotStatus::ClosedNoBids,
+    };
+
+    BIDS_ON_LOTS.with_borrow_mut(|bids| {
+        for (bid_counter, decrypted_bid) in bid_counters.into_iter().zip(decrypted_bids) {
+            // replace the encrypted bid with the decrypted bid
+            bids.insert(
+                (lot_id, bid_counter, decrypted_bid.bidder),
+                Bid::Decrypted(decrypted_bid),
+            );
+        }
+    });
+
+    LOTS.with_borrow_mut(|lots| {
+        lots.insert(
+            lot_id,
+            LotInformation {
+                id: lot_id,
+                name: lots.get(&lot_id).unwrap().name,
+                description: lots.get(&lot_id).unwrap().description,
+                start_time: lots.get(&lot_id).unwrap().start_time,
+                end_time: lots.get(&lot_id).unwrap().end_time,
+                creator: lots.get(&lot_id).unwrap().creator,
+                status,
+            },
+        );
+    });
+
+    Ok(())
+}
+
+/// Removes the lot with the earliest deadline from the open lots deadlines if
+/// the deadline has passed at time `now`, to prevent double processing.
+fn take_lot_to_close(now: u64) -> Option<(u64, LotId)> {
+    OPEN_LOTS_DEADLINES.with_borrow_mut(|open_lots_deadlines| {
         open_lots_deadlines
             .iter()
-            .take_while(|(deadline, _)| *deadline <= now)
+            .take_while(|((deadline, _), _)| *deadline <= now)
             .next()
-            .map(|(deadline, lot_to_close)| {
-                // remove the lot from the open lots deadlines to prevent double processing
-                open_lots_deadlines.remove(&deadline);
-                lot_to_close
+            .map(|(deadline_and_lot, _)| {
+                open_lots_deadlines.remove(&deadline_and_lot);
+                deadline_and_lot
             })
-    });
+    })
+}
 
-    if let Some(lot_id) = lot_to_close {
-        let (bid_counters, encrypted_bids): (Vec<BidCounter>, Vec<EncryptedBid>) = BIDS_ON_LOTS
-            .with_borrow(|bids| {
-                bids.range((lot_id, 0, Principal::management_canister())..)
-                    .take_while(|((this_lot_id, _, _), _)| *this_lot_id == lot_id)
-                    .map(|((_, bid_counter, _), bid)| {
-                        let encrypted_bid = match bid {
-                            Bid::Encrypted(encrypted_bid) => encrypted_bid,
-                            Bid::Decrypted(_) => panic!("bug: decrypted bid in a closed lot"),
-                        };
-                        (bid_counter, encrypted_bid)
-                    })
-                    .collect()
-            });
+fn encrypted_bids_on_lot(lot_id: LotId) -> (Vec<BidCounter>, Vec<EncryptedBid>) {
+    BIDS_ON_LOTS.with_borrow(|bids| {
+        bids.range((lot_id, 0, Principal::management_canister())..)
+            .take_while(|((this_lot_id, _, _), _)| *this_lot_id == lot_id)
+            .map(|((_, bid_counter, _), bid)| {
+                let encrypted_bid = match bid {
+                    Bid::Encrypted(encrypted_bid) => encrypted_bid,
+                    Bid::Decrypted(_) => panic!("bug: decrypted bid in a closed lot"),
+                };
+                (bid_counter, encrypted_bid)
+            })
+            .collect()
+    })
+}
 
-        let decrypted_bids = decrypt_bids(lot_id, encrypted_bids, root_ibe_public_key).await;
+/// Derives the time-lock decryption key for lots ending at `deadline` and
+/// publishes it in the canister's stable memory, unless it was already
+/// published when closing another lot with the same deadline. A corrupt
+/// published key is derived again and overwritten.
+async fn publish_decryption_key(deadline: u64, now: u64) -> Result<VetKey, String> {
+    if let Ok(Some(key)) = TIMELOCK.with_borrow(|timelock| timelock.get_decryption_key(deadline)) {
+        return VetKey::deserialize_unchecked(key.as_ref())
+            .map_err(|e| format!("invalid published decryption key: {e:?}"));
+    }
 
-        let status = match decrypted_bids
-            .iter()
-            .rev() // reverse the bids to get the *oldest* maximum bid
-            .max_by(|x, y| x.amount.cmp(&y.amount))
-        {
-            Some(winner_bid) => LotStatus::ClosedWithWinner(winner_bid.bidder),
-            None => LotStatus::ClosedNoBids,
-        };
+    let ibe_decryption_key = TIMELOCK
+        .with_borrow(|timelock| timelock.derive_decryption_key(deadline, now))?
+        .await
+        .map_err(|e| format!("failed to derive time-lock decryption key: {e}"))?;
 
-        BIDS_ON_LOTS.with_borrow_mut(|bids| {
-            for (bid_counter, decrypted_bid) in
-                bid_counters.into_iter().zip(decrypted_bids.into_iter())
-            {
-                // replace the encrypted bid with the decrypted bid
-                bids.insert(
-                    (lot_id, bid_counter, decrypted_bid.bidder),
-                    Bid::Decrypted(decrypted_bid),
-                );
-            }
-        });
+    let timelock_public_key = TIMELOCK
+        .with_borrow(|timelock| timelock.get_timelock_public_key())
+        .await?;
+    let timelock_public_key = DerivedPublicKey::deserialize(timelock_public_key.as_ref())
+        .map_err(|e| format!("invalid time-lock public key: {e:?}"))?;
 
-        LOTS.with_borrow_mut(|lots| {
-            lots.insert(
-                lot_id,
-                LotInformation {
-                    id: lot_id,
-                    name: lots.get(&lot_id).unwrap().name,
-                    description: lots.get(&lot_id).unwrap().description,
-                    start_time: lots.get(&lot_id).unwrap().start_time,
-                    end_time: lots.get(&lot_id).unwrap().end_time,
-                    creator: lots.get(&lot_id).unwrap().creator,
-                    status,
-                },
-            );
-        });
-    }
+    TIMELOCK.with_borrow_mut(|timelock| {
+        timelock.publish_decryption_key(deadline, now, &ibe_decryption_key, &timelock_public_key)
+    })?;
+
+    Ok(ibe_decryption_key)
+}
+
+/// Derives the decryption key of the bids that were placed before bids were
+/// time-locked to the end of the lot. These bids were encrypted to the lot id
+/// without associated data, and only exist for lots that were open during
+/// the upgrade, see [`migrate_open_lots_deadlines`].
+async fn derive_legacy_decryption_key(lot_id: LotId) -> Result<VetKey, String> {
+    TIMELOCK
+        .with_borrow(|timelock| {
+            derive_public_vetkey(
+                &timelock.vetkd_api,
+                DOMAIN_SEPARATOR.as_bytes().to_vec(),
+                lot_id.to_le_bytes().to_vec(),
+            )
+        })
+        .await
+        .map_err(|e| format!("failed to derive legacy decryption key: {e}"))
+}
+
+/// Bids placed since bids are bound to the lot and the bidder always have
+/// associated data, see [`place_bid`].
+fn is_legacy_bid(encrypted_bid: &EncryptedBid) -> bool {
+    IbeCiphertext::deserialize(&encrypted_bid.encrypted_amount)
+        .is_ok_and(|c| !c.has_associated_data())
 }
 
-async fn decrypt_bids(
+/// Decrypts the bids on a lot with the time-lock decryption key of its
+/// deadline, or, for bids placed before the upgrade to time-lock encryption,
+/// with `legacy_decryption_key`, see [`derive_legacy_decryption_key`].
+fn decrypt_bids(
     lot_id: LotId,
     encrypted_bids: Vec<EncryptedBid>,
-    root_ibe_public_key_bytes: Vec<u8>,
+    ibe_decryption_key: &VetKey,
+    legacy_decryption_key: Option<&VetKey>,
 ) -> Vec<DecryptedBid> {
-    let dummy_seed = vec![0; 32];
-    let transport_secret_key = ic_vetkeys::TransportSecretKey::from_seed(dummy_seed.clone())
-        .expect("failed to create transport secret key");
-
-    let request = VetKDDeriveKeyRequest {
-        context: DOMAIN_SEPARATOR.as_bytes().to_vec(),
-        input: lot_id.to_le_bytes().to_vec(),
-        key_id: bls12_381_dfx_test_key(),
-        transport_public_key: transport_secret_key.public_key().to_vec(),
-    };
-
-    let (result,) = ic_cdk::api::call::call_with_payment128::<_, (VetKDDeriveKeyReply,)>(
-        vetkd_system_api_canister_id(),
-        "vetkd_derive_key",
-        (request,),
-        26_153_846_153,
-    )
-    .await
-    .expect("call to vetkd_derive_key failed");
-
-    let root_ibe_public_key = DerivedPublicKey::deserialize(&root_ibe_public_key_bytes).unwrap();
-    let encrypted_vetkey = EncryptedVetKey::deserialize(&result.encrypted_key).unwrap();
-
-    let ibe_decryption_key = encrypted_vetkey
-        .decrypt_and_verify(
-            &transport_secret_key,
-            &root_ibe_public_key,
-            lot_id.to_le_bytes().as_ref(),
-        )
-        .expect("failed to decrypt ibe key");
-
     let mut decrypted_bids = Vec::new();
 
     for encrypted_bid in encrypted_bids {
         let decrypted_bid: Result<u128, String> =
-            ic_vetkeys::IbeCiphertext::deserialize(&encrypted_bid.encrypted_amount)
+            IbeCiphertext::deserialize(&encrypted_bid.encrypted_amount)
                 .map_err(|e| format!("failed to deserialize ibe ciphertext: {e}"))
                 .and_then(|c| {
-                    c.decrypt(&ibe_decryption_key)
-                        .map_err(|_| "failed to decrypt ibe ciphertext".to_string())
+                    if c.has_associated_data() {
+                        c.decrypt_with_associated_data(
+                            ibe_decryption_key,
+                            &bid_associated_data(lot_id, encrypted_bid.bidder),
+                        )
+                    } else {
+                        let legacy_decryption_key = legacy_decryption_key
+                            .ok_or_else(|| "missing legacy decryption key".to_string())?;
+                        c.decrypt(legacy_decryption_key)
+                    }
+                    .map_err(|_| "failed to decrypt ibe ciphertext".to_string())
                 })
                 .and_then(|bytes| {
                     bytes
@@ -352,6 +450,14 @@ async fn decrypt_bids(
     decrypted_bids
 }
 
+/// The associated data that binds an encrypted bid to the lot and the bidder,
+/// so that a bid cannot be replayed in another lot or by another bidder.
+fn bid_associated_data(lot_id: LotId, bidder: Principal) -> Vec<u8> {
+    let mut associated_data = lot_id.to_le_bytes().to_vec();
+    associated_data.extend_from_slice(bidder.as_slice());
+    associated_data
+}
+
 fn is_authenticated() -> Result<(), String> {
     let caller = ic_cdk::caller();
     if caller != Principal::anonymous() {
@@ -361,17 +467,6 @@ fn is_authenticated() -> Result<(), String> {
     }
 }
 
-fn bls12_381_dfx_test_key() -> VetKDKeyId {
-    VetKDKeyId {
-        curve: VetKDCurve::Bls12_381_G2,
-        name: "dfx_test_key".to_string(),
-    }
-}
-
-fn vetkd_system_api_canister_id() -> CanisterId {
-    CanisterId::from_str(CANISTER_ID_VETKD_SYSTEM_API).expect("failed to create canister ID")
-}
-
 // In the following, we register a custom getrandom implementation because
 // otherwise getrandom (which is a dependency of some other dependencies) fails to compile.
 // This is necessary because getrandom by default fails to compile for the
@@ -394,3 +489,113 @@ fn always_fail(_buf: &mut [u8]) -> Result<(), getrandom::Error> {
 }
 
 ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use ic_vetkeys::vetkd_api::VetKdApi;
+    use ic_vetkeys::vetkd_api_types::VetKDPublicKeyRequest;
+    use ic_vetkeys::{IbeIdentity, IbeSeed};
+    use ic_vetkeys_test_utils::mock_vetkd::{InMemoryVetKdApi, MockVetKd};
+    use ic_vetkeys_test_utils::{random_self_authenticating_principal, reproducible_rng};
+    use rand::Rng;
+
+    #[test]
+    fn bids_on_lot_open_during_upgrade_are_decrypted() {
+        let rng = &mut reproducible_rng();
+        let vetkd_api = InMemoryVetKdApi::new(
+            random_self_authenticating_principal(rng),
+            MockVetKd::new(rng.gen()),
+        );
+        let context = DOMAIN_SEPARATOR.as_bytes().to_vec();
+        let public_key_request = VetKDPublicKeyRequest {
+            canister_id: None,
+            context: context.clone(),
+            key_id: vetkd_api.mock().key_id(),
+        };
+        let public_key = block_on(vetkd_api.vetkd_public_key(public_key_request)).unwrap();
+        let dpk = DerivedPublicKey::deserialize(&public_key.public_key).unwrap();
+
+        // a lot and a bid as stored by the version that encrypted bids to the lot id
+        let lot_id: LotId = 0;
+        let deadline = 5 * TIMELOCK_GRANULARITY + 1;
+        let rounded_deadline = 6 * TIMELOCK_GRANULARITY;
+        let legacy_bidder = random_self_authenticating_principal(rng);
+        let legacy_bid = EncryptedBid {
+            encrypted_amount: IbeCiphertext::encrypt(
+                &dpk,
+                &IbeIdentity::from_bytes(&lot_id.to_le_bytes()),
+                &7u128.to_le_bytes(),
+                &IbeSeed::random(rng),
+            )
+            .serialize(),
+            bidder: legacy_bidder,
+        };
+        StableBTreeMap::<u64, LotId, Memory>::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
+        )
+        .insert(deadline, lot_id);
+        BIDS_ON_LOTS.with_borrow_mut(|bids| {
+            bids.insert((lot_id, 0, legacy_bidder), Bid::Encrypted(legacy_bid))
+        });
+
+        migrate_open_lots_deadlines();
+
+        let bidder = random_self_authenticating_principal(rng);
+        let bid = EncryptedBid {
+            encrypted_amount: IbeCiphertext::encrypt_with_associated_data(
+                &dpk,
+                &IbeIdentity::from_timestamp(deadline, TIMELOCK_GRANULARITY),
+                &9u128.to_le_bytes(),
+                &bid_associated_data(lot_id, bidder),
+                &IbeSeed::random(rng),
+            )
+            .serialize(),
+            bidder,
+        };
+        BIDS_ON_LOTS.with_borrow_mut(|bids| bids.insert((lot_id, 1, bidder), Bid::Encrypted(bid)));
+
+        assert_eq!(take_lot_to_close(rounded_deadline - 1), None);
+        assert_eq!(
+            take_lot_to_close(rounded_deadline),
+            Some((rounded_deadline, lot_id))
+        );
+        assert_eq!(take_lot_to_close(u64::MAX), None);
+
+        let (_, encrypted_bids) = encrypted_bids_on_lot(lot_id);
+        assert_eq!(
+            encrypted_bids.iter().map(is_legacy_bid).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+
+        let ibe_decryption_key = block_on(derive_public_vetkey(
+            &vetkd_api,
+            context.clone(),
+            IbeIdentity::from_timestamp(rounded_deadline, TIMELOCK_GRANULARITY)
+                .value()
+                .to_vec(),
+        ))
+        .unwrap();
+        let legacy_decryption_key = block_on(derive_public_vetkey(
+            &vetkd_api,
+            context,
+            lot_id.to_le_bytes().to_vec(),
+        ))
+        .unwrap();
+
+        let decrypted_bids = decrypt_bids(
+            lot_id,
+            encrypted_bids,
+            &ibe_decryption_key,
+            Some(&legacy_decryption_key),
+        );
+        assert_eq!(
+            decrypted_bids
+                .into_iter()
+                .map(|bid| (bid.bidder, bid.amount))
+                .collect::<Vec<_>>(),
+            vec![(legacy_bidder, 7), (bidder, 9)]
+        );
+    }
+}
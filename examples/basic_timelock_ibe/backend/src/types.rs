@@ -1,5 +1,4 @@
 use candid::{CandidType, Principal};
-use ic_cdk::api::management_canister::main::CanisterId;
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
@@ -29,7 +28,7 @@ pub enum Bid {
 }
 
 impl Storable for EncryptedBid {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -41,7 +40,7 @@ impl Storable for EncryptedBid {
 }
 
 impl Storable for DecryptedBid {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -53,7 +52,7 @@ impl Storable for DecryptedBid {
 }
 
 impl Storable for Bid {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -98,7 +97,7 @@ pub struct ClosedLotsResponse {
 }
 
 impl Storable for LotInformation {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -108,47 +107,3 @@ impl Storable for LotInformation {
 
     const BOUND: Bound = Bound::Unbounded;
 }
-
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub enum VetKDCurve {
-    #[serde(rename = "bls12_381_g2")]
-    #[allow(non_camel_case_types)]
-    Bls12_381_G2,
-}
-
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub(crate) struct VetKDKeyId {
-    pub curve: VetKDCurve,
-    pub name: String,
-}
-
-#[derive(CandidType, Deserialize)]
-pub(crate) struct VetKDPublicKeyRequest {
-    pub canister_id: Option<CanisterId>,
-    #[serde(with = "serde_bytes")]
-    pub context: Vec<u8>,
-    pub key_id: VetKDKeyId,
-}
-
-#[derive(CandidType, Deserialize)]
-pub(crate) struct VetKDPublicKeyReply {
-    #[serde(with = "serde_bytes")]
-    pub public_key: Vec<u8>,
-}
-
-#[derive(CandidType, Deserialize, Clone, Debug)]
-pub(crate) struct VetKDDeriveKeyRequest {
-    #[serde(with = "serde_bytes")]
-    pub input: Vec<u8>,
-    #[serde(with = "serde_bytes")]
-    pub context: Vec<u8>,
-    #[serde(with = "serde_bytes")]
-    pub transport_public_key: Vec<u8>,
-    pub key_id: VetKDKeyId,
-}
-
-#[derive(CandidType, Deserialize)]
-pub(crate) struct VetKDDeriveKeyReply {
-    #[serde(with = "serde_bytes")]
-    pub encrypted_key: Vec<u8>,
-}
@@ -19,6 +19,9 @@ import {
 import { AuthClient } from "@dfinity/auth-client";
 import type { ActorSubclass } from "@dfinity/agent";
 
+// Must match the time-lock granularity of the backend canister.
+const TIMELOCK_GRANULARITY_NANOS = 1_000_000_000n;
+
 let ibePublicKey: DerivedPublicKey | undefined = undefined;
 let myPrincipal: Principal | undefined = undefined;
 let authClient: AuthClient | undefined;
@@ -352,7 +355,7 @@ async function listLots() {
                                     ) as HTMLInputElement
                                 ).value,
                             );
-                            void placeBid(lot.id, amount);
+                            void placeBid(lot.id, lot.end_time, amount);
                         });
                     }
                 }
@@ -424,19 +427,27 @@ async function listLots() {
     }
 }
 
-async function placeBid(lotId: bigint, amount: number) {
+async function placeBid(lotId: bigint, endTime: bigint, amount: number) {
     try {
         // Get the root IBE public key
         const rootIbePublicKey = await getRootIbePublicKey();
         const lotIdBytes = u128ToLeBytes(lotId);
         const amountBytes = u128ToLeBytes(BigInt(amount));
 
-        // Encrypt the bid amount using IBE
+        // Bind the bid to the lot and the bidder so that it cannot be replayed
+        if (!myPrincipal) throw new Error("not authenticated");
+        const associatedData = new Uint8Array([
+            ...lotIdBytes,
+            ...myPrincipal.toUint8Array(),
+        ]);
+
+        // Encrypt the bid amount using IBE, time-locked until the lot ends
         const encryptedAmount = IbeCiphertext.encrypt(
             rootIbePublicKey,
-            IbeIdentity.fromBytes(lotIdBytes),
+            IbeIdentity.fromTimestamp(endTime, TIMELOCK_GRANULARITY_NANOS),
             amountBytes,
             IbeSeed.random(),
+            associatedData,
         );
 
         // Place the bid
@@ -47,7 +47,7 @@ impl PasswordMetadata {
 }
 
 impl Storable for PasswordMetadata {
-    fn to_bytes(&self) -> Cow<[u8]> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
         Cow::Owned(serde_cbor::to_vec(self).expect("failed to serialize"))
     }
 
@@ -4,7 +4,11 @@ import type { IDL } from '@dfinity/candid';
 
 export type AccessRights = { 'Read' : null } |
   { 'ReadWrite' : null } |
-  { 'ReadWriteManage' : null };
+  { 'ReadMetadata' : null } |
+  { 'ReadAppend' : null } |
+  { 'Delete' : null } |
+  { 'ReadWriteManage' : null } |
+  { 'ReadShare' : null };
 export interface ByteBuf { 'inner' : Uint8Array | number[] }
 export interface PasswordMetadata {
   'url' : string,
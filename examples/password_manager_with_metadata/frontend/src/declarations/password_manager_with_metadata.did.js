@@ -16,7 +16,11 @@ export const idlFactory = ({ IDL }) => {
   const AccessRights = IDL.Variant({
     'Read' : IDL.Null,
     'ReadWrite' : IDL.Null,
+    'ReadMetadata' : IDL.Null,
+    'ReadAppend' : IDL.Null,
+    'Delete' : IDL.Null,
     'ReadWriteManage' : IDL.Null,
+    'ReadShare' : IDL.Null,
   });
   const Result_2 = IDL.Variant({
     'Ok' : IDL.Vec(IDL.Tuple(IDL.Principal, AccessRights)),
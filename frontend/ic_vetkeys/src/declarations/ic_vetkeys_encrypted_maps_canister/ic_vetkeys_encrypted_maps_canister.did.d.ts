@@ -4,14 +4,41 @@ import type { IDL } from '@dfinity/candid';
 
 export type AccessRights = { 'Read' : null } |
   { 'ReadWrite' : null } |
-  { 'ReadWriteManage' : null };
+  { 'ReadMetadata' : null } |
+  { 'ReadAppend' : null } |
+  { 'Delete' : null } |
+  { 'ReadWriteManage' : null } |
+  { 'ReadShare' : null };
 export interface ByteBuf { 'inner' : Uint8Array | number[] }
+export type CorruptEntry = {
+    'AccessGrant' : { 'key_id' : [Principal, ByteBuf], 'user' : Principal }
+  } |
+  {
+    'EncryptedMapValue' : {
+      'map_key' : ByteBuf,
+      'map_id' : [Principal, ByteBuf],
+    }
+  } |
+  {
+    'AccessGrantWithoutSharedKey' : {
+      'key_id' : [Principal, ByteBuf],
+      'user' : Principal,
+    }
+  } |
+  {
+    'SharedKeyWithoutAccessGrant' : {
+      'key_id' : [Principal, ByteBuf],
+      'user' : Principal,
+    }
+  };
 export interface EncryptedMapData {
   'access_control' : Array<[Principal, AccessRights]>,
   'keyvals' : Array<[ByteBuf, ByteBuf]>,
   'map_name' : ByteBuf,
   'map_owner' : Principal,
 }
+export type RemovedMapValues = { 'Keys' : Array<ByteBuf> } |
+  { 'Count' : bigint };
 export type Result = { 'Ok' : [] | [ByteBuf] } |
   { 'Err' : string };
 export type Result_1 = { 'Ok' : Array<[ByteBuf, ByteBuf]> } |
@@ -22,9 +49,10 @@ export type Result_3 = { 'Ok' : Array<[Principal, AccessRights]> } |
   { 'Err' : string };
 export type Result_4 = { 'Ok' : [] | [AccessRights] } |
   { 'Err' : string };
-export type Result_5 = { 'Ok' : Array<ByteBuf> } |
+export type Result_5 = { 'Ok' : RemovedMapValues } |
   { 'Err' : string };
 export interface _SERVICE {
+  'find_corrupt_entries' : ActorMethod<[], Array<CorruptEntry>>,
   'get_accessible_shared_map_names' : ActorMethod<
     [],
     Array<[Principal, ByteBuf]>
@@ -51,9 +79,22 @@ export interface _SERVICE {
     [Principal, ByteBuf, ByteBuf, ByteBuf],
     Result
   >,
+  'prefix_encrypted_values' : ActorMethod<
+    [Principal, ByteBuf, ByteBuf, [] | [bigint]],
+    Result_1
+  >,
+  'range_encrypted_values' : ActorMethod<
+    [Principal, ByteBuf, ByteBuf, [] | [ByteBuf], [] | [bigint]],
+    Result_1
+  >,
+  'remove_corrupt_entries' : ActorMethod<[], Array<CorruptEntry>>,
   'remove_encrypted_value' : ActorMethod<[Principal, ByteBuf, ByteBuf], Result>,
   'remove_map_values' : ActorMethod<[Principal, ByteBuf], Result_5>,
   'remove_user' : ActorMethod<[Principal, ByteBuf, Principal], Result_4>,
+  'set_public_access' : ActorMethod<
+    [Principal, ByteBuf, [] | [AccessRights]],
+    Result_4
+  >,
   'set_user_rights' : ActorMethod<
     [Principal, ByteBuf, Principal, AccessRights],
     Result_4
@@ -1,9 +1,31 @@
 export const idlFactory = ({ IDL }) => {
   const ByteBuf = IDL.Record({ 'inner' : IDL.Vec(IDL.Nat8) });
+  const CorruptEntry = IDL.Variant({
+    'AccessGrant' : IDL.Record({
+      'key_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+      'user' : IDL.Principal,
+    }),
+    'EncryptedMapValue' : IDL.Record({
+      'map_key' : ByteBuf,
+      'map_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+    }),
+    'AccessGrantWithoutSharedKey' : IDL.Record({
+      'key_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+      'user' : IDL.Principal,
+    }),
+    'SharedKeyWithoutAccessGrant' : IDL.Record({
+      'key_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+      'user' : IDL.Principal,
+    }),
+  });
   const AccessRights = IDL.Variant({
     'Read' : IDL.Null,
     'ReadWrite' : IDL.Null,
+    'ReadMetadata' : IDL.Null,
+    'ReadAppend' : IDL.Null,
+    'Delete' : IDL.Null,
     'ReadWriteManage' : IDL.Null,
+    'ReadShare' : IDL.Null,
   });
   const EncryptedMapData = IDL.Record({
     'access_control' : IDL.Vec(IDL.Tuple(IDL.Principal, AccessRights)),
@@ -25,8 +47,13 @@ export const idlFactory = ({ IDL }) => {
     'Ok' : IDL.Opt(AccessRights),
     'Err' : IDL.Text,
   });
-  const Result_5 = IDL.Variant({ 'Ok' : IDL.Vec(ByteBuf), 'Err' : IDL.Text });
+  const RemovedMapValues = IDL.Variant({
+    'Keys' : IDL.Vec(ByteBuf),
+    'Count' : IDL.Nat64,
+  });
+  const Result_5 = IDL.Variant({ 'Ok' : RemovedMapValues, 'Err' : IDL.Text });
   return IDL.Service({
+    'find_corrupt_entries' : IDL.Func([], [IDL.Vec(CorruptEntry)], ['query']),
     'get_accessible_shared_map_names' : IDL.Func(
         [],
         [IDL.Vec(IDL.Tuple(IDL.Principal, ByteBuf))],
@@ -85,6 +112,17 @@ export const idlFactory = ({ IDL }) => {
         [Result],
         [],
       ),
+    'prefix_encrypted_values' : IDL.Func(
+        [IDL.Principal, ByteBuf, ByteBuf, IDL.Opt(IDL.Nat64)],
+        [Result_1],
+        ['query'],
+      ),
+    'range_encrypted_values' : IDL.Func(
+        [IDL.Principal, ByteBuf, ByteBuf, IDL.Opt(ByteBuf), IDL.Opt(IDL.Nat64)],
+        [Result_1],
+        ['query'],
+      ),
+    'remove_corrupt_entries' : IDL.Func([], [IDL.Vec(CorruptEntry)], []),
     'remove_encrypted_value' : IDL.Func(
         [IDL.Principal, ByteBuf, ByteBuf],
         [Result],
@@ -96,6 +134,11 @@ export const idlFactory = ({ IDL }) => {
         [Result_4],
         [],
       ),
+    'set_public_access' : IDL.Func(
+        [IDL.Principal, ByteBuf, IDL.Opt(AccessRights)],
+        [Result_4],
+        [],
+      ),
     'set_user_rights' : IDL.Func(
         [IDL.Principal, ByteBuf, IDL.Principal, AccessRights],
         [Result_4],
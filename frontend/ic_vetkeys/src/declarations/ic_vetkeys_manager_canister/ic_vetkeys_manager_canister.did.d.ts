@@ -4,8 +4,27 @@ import type { IDL } from '@dfinity/candid';
 
 export type AccessRights = { 'Read' : null } |
   { 'ReadWrite' : null } |
-  { 'ReadWriteManage' : null };
+  { 'ReadMetadata' : null } |
+  { 'ReadAppend' : null } |
+  { 'Delete' : null } |
+  { 'ReadWriteManage' : null } |
+  { 'ReadShare' : null };
 export interface ByteBuf { 'inner' : Uint8Array | number[] }
+export type CorruptEntry = {
+    'AccessGrant' : { 'key_id' : [Principal, ByteBuf], 'user' : Principal }
+  } |
+  {
+    'AccessGrantWithoutSharedKey' : {
+      'key_id' : [Principal, ByteBuf],
+      'user' : Principal,
+    }
+  } |
+  {
+    'SharedKeyWithoutAccessGrant' : {
+      'key_id' : [Principal, ByteBuf],
+      'user' : Principal,
+    }
+  };
 export type Result = { 'Ok' : ByteBuf } |
   { 'Err' : string };
 export type Result_1 = { 'Ok' : Array<[Principal, AccessRights]> } |
@@ -13,6 +32,7 @@ export type Result_1 = { 'Ok' : Array<[Principal, AccessRights]> } |
 export type Result_2 = { 'Ok' : [] | [AccessRights] } |
   { 'Err' : string };
 export interface _SERVICE {
+  'find_corrupt_entries' : ActorMethod<[], Array<CorruptEntry>>,
   'get_accessible_shared_key_ids' : ActorMethod<
     [],
     Array<[Principal, ByteBuf]>
@@ -24,7 +44,12 @@ export interface _SERVICE {
   >,
   'get_user_rights' : ActorMethod<[Principal, ByteBuf, Principal], Result_2>,
   'get_vetkey_verification_key' : ActorMethod<[], ByteBuf>,
+  'remove_corrupt_entries' : ActorMethod<[], Array<CorruptEntry>>,
   'remove_user' : ActorMethod<[Principal, ByteBuf, Principal], Result_2>,
+  'set_public_access' : ActorMethod<
+    [Principal, ByteBuf, [] | [AccessRights]],
+    Result_2
+  >,
   'set_user_rights' : ActorMethod<
     [Principal, ByteBuf, Principal, AccessRights],
     Result_2
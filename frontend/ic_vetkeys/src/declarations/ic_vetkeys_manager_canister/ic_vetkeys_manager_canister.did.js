@@ -1,10 +1,28 @@
 export const idlFactory = ({ IDL }) => {
   const ByteBuf = IDL.Record({ 'inner' : IDL.Vec(IDL.Nat8) });
+  const CorruptEntry = IDL.Variant({
+    'AccessGrant' : IDL.Record({
+      'key_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+      'user' : IDL.Principal,
+    }),
+    'AccessGrantWithoutSharedKey' : IDL.Record({
+      'key_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+      'user' : IDL.Principal,
+    }),
+    'SharedKeyWithoutAccessGrant' : IDL.Record({
+      'key_id' : IDL.Tuple(IDL.Principal, ByteBuf),
+      'user' : IDL.Principal,
+    }),
+  });
   const Result = IDL.Variant({ 'Ok' : ByteBuf, 'Err' : IDL.Text });
   const AccessRights = IDL.Variant({
     'Read' : IDL.Null,
     'ReadWrite' : IDL.Null,
+    'ReadMetadata' : IDL.Null,
+    'ReadAppend' : IDL.Null,
+    'Delete' : IDL.Null,
     'ReadWriteManage' : IDL.Null,
+    'ReadShare' : IDL.Null,
   });
   const Result_1 = IDL.Variant({
     'Ok' : IDL.Vec(IDL.Tuple(IDL.Principal, AccessRights)),
@@ -15,6 +33,7 @@ export const idlFactory = ({ IDL }) => {
     'Err' : IDL.Text,
   });
   return IDL.Service({
+    'find_corrupt_entries' : IDL.Func([], [IDL.Vec(CorruptEntry)], ['query']),
     'get_accessible_shared_key_ids' : IDL.Func(
         [],
         [IDL.Vec(IDL.Tuple(IDL.Principal, ByteBuf))],
@@ -36,11 +55,17 @@ export const idlFactory = ({ IDL }) => {
         ['query'],
       ),
     'get_vetkey_verification_key' : IDL.Func([], [ByteBuf], []),
+    'remove_corrupt_entries' : IDL.Func([], [IDL.Vec(CorruptEntry)], []),
     'remove_user' : IDL.Func(
         [IDL.Principal, ByteBuf, IDL.Principal],
         [Result_2],
         [],
       ),
+    'set_public_access' : IDL.Func(
+        [IDL.Principal, ByteBuf, IDL.Opt(AccessRights)],
+        [Result_2],
+        [],
+      ),
     'set_user_rights' : IDL.Func(
         [IDL.Principal, ByteBuf, IDL.Principal, AccessRights],
         [Result_2],
@@ -6,6 +6,7 @@ import {
     AccessRights,
     ByteBuf,
     EncryptedMapData,
+    RemovedMapValues,
 } from "../declarations/ic_vetkeys_encrypted_maps_canister/ic_vetkeys_encrypted_maps_canister.did";
 import { EncryptedMapsClient } from "./index";
 
@@ -89,7 +90,7 @@ export class DefaultEncryptedMapsClient implements EncryptedMapsClient {
     remove_map_values(
         mapOwner: Principal,
         mapName: ByteBuf,
-    ): Promise<{ Ok: Array<ByteBuf> } | { Err: string }> {
+    ): Promise<{ Ok: RemovedMapValues } | { Err: string }> {
         return this.actor.remove_map_values(mapOwner, mapName);
     }
 
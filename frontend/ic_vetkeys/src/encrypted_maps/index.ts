@@ -22,12 +22,14 @@ import {
     AccessRights,
     ByteBuf,
 } from "../declarations/ic_vetkeys_manager_canister/ic_vetkeys_manager_canister.did";
+import { RemovedMapValues } from "../declarations/ic_vetkeys_encrypted_maps_canister/ic_vetkeys_encrypted_maps_canister.did";
 
 export { DefaultEncryptedMapsClient } from "./encrypted_maps_canister";
 export type {
     AccessRights,
     ByteBuf,
 } from "../declarations/ic_vetkeys_manager_canister/ic_vetkeys_manager_canister.did";
+export type { RemovedMapValues } from "../declarations/ic_vetkeys_encrypted_maps_canister/ic_vetkeys_encrypted_maps_canister.did";
 
 /**
  * > [!IMPORTANT]
@@ -378,21 +380,24 @@ export class EncryptedMaps {
      *
      * @param mapOwner - The principal of the map owner
      * @param mapName - The name/identifier of the map
-     * @returns Promise resolving to an array of removed keys
+     * @returns Promise resolving to an array of removed keys, or to the number
+     * of removed values if the caller may delete but not read the map values
      * @throws Error if the operation fails
      */
     async removeMapValues(
         mapOwner: Principal,
         mapName: Uint8Array,
-    ): Promise<Array<Uint8Array>> {
+    ): Promise<Array<Uint8Array> | bigint> {
         const encryptedResult = await this.canisterClient.remove_map_values(
             mapOwner,
             arrayToByteBuf(mapName),
         );
         if ("Err" in encryptedResult) {
             throw Error(encryptedResult.Err);
+        } else if ("Count" in encryptedResult.Ok) {
+            return encryptedResult.Ok.Count;
         } else {
-            return encryptedResult.Ok.map((mapKey) =>
+            return encryptedResult.Ok.Keys.map((mapKey) =>
                 Uint8Array.from(mapKey.inner),
             );
         }
@@ -782,12 +787,12 @@ export interface EncryptedMapsClient {
      *
      * @param mapOwner - The principal of the map owner
      * @param mapName - The name/identifier of the map
-     * @returns Promise resolving to an array of removed keys, or an error if the operation fails
+     * @returns Promise resolving to the removed keys, or to the number of removed values if the caller may delete but not read the map values, or an error if the operation fails
      */
     remove_map_values(
         mapOwner: Principal,
         mapName: ByteBuf,
-    ): Promise<{ Ok: Array<ByteBuf> } | { Err: string }>;
+    ): Promise<{ Ok: RemovedMapValues } | { Err: string }>;
 
     /**
      * Grants or modifies access rights for a user.
@@ -3,8 +3,10 @@ import {
     EncryptedVetKey,
     IbeIdentity,
     IbeCiphertext,
+    IbeMultiRecipientCiphertext,
     MasterPublicKey,
     IbeSeed,
+    TimelockCiphertext,
     TransportSecretKey,
     VetKey,
     augmentedHashToG1,
@@ -14,6 +16,8 @@ import {
 } from "./utils";
 import { expect, test } from "vitest";
 import { bls12_381 } from "@noble/curves/bls12-381";
+// generated by `cargo run -p ic-vetkeys-test-utils --bin generate_test_vectors`
+import testVectors from "../../../../test_vectors/vetkeys.json";
 
 function hexToBytes(hex: string): Uint8Array {
     const bytes = new Uint8Array(hex.length / 2);
@@ -191,6 +195,121 @@ test("protocol flow with precomputed data", () => {
     );
 });
 
+test("IBE encryption with associated data", () => {
+    const identity = hexToBytes("6d657373616765");
+
+    const dpk = DerivedPublicKey.deserialize(
+        hexToBytes(
+            "972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f",
+        ),
+    );
+
+    const vetkd = VetKey.deserialize(
+        hexToBytes(
+            "987db5406ce297e729c8564a106dc896943b00216a095fe9c5d32a16a330c02eb80e6f468ede83cde5462b5145b58f65",
+        ),
+    );
+
+    const message = hexToBytes("f00f11");
+    const associatedData = hexToBytes("6c6f742031");
+    const seed = IbeSeed.fromBytes(new Uint8Array(32));
+    const ibe = IbeCiphertext.encrypt(
+        dpk,
+        IbeIdentity.fromBytes(identity),
+        message,
+        seed,
+        associatedData,
+    );
+
+    assertEqual(
+        bytesToHex(ibe.serialize()),
+        "49432049424500038606da2563b5c47a35661f1a7a7d55164b37867b923bfa3bbb8f7e0af0023242df83df9d3ef1ab5aba4e4ce3dae75fe606e677132244a2515b035fd71540b3fe49c0e8658531fa80c52637160aff2ed71dcb479f7395159703d74bcaa0ac97bf9155cd85cce2fff173bd1cb62fa867eaf95cb3a5baa28a3a948f20f59afbcec89f7f98",
+    );
+
+    const ibeRec = IbeCiphertext.deserialize(ibe.serialize());
+    assertEqual(bytesToHex(ibeRec.decrypt(vetkd, associatedData)), "f00f11");
+    expect(() => ibeRec.decrypt(vetkd, hexToBytes("6c6f742032"))).toThrow();
+    expect(() => ibeRec.decrypt(vetkd)).toThrow();
+});
+
+test("multi-recipient IBE encryption", () => {
+    const dpk = DerivedPublicKey.deserialize(
+        hexToBytes(
+            "972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f",
+        ),
+    );
+
+    const vetkd = VetKey.deserialize(
+        hexToBytes(
+            "987db5406ce297e729c8564a106dc896943b00216a095fe9c5d32a16a330c02eb80e6f468ede83cde5462b5145b58f65",
+        ),
+    );
+
+    const message = hexToBytes("f00f11");
+    const seed = IbeSeed.fromBytes(new Uint8Array(32));
+    const ibe = IbeMultiRecipientCiphertext.encrypt(
+        dpk,
+        [
+            IbeIdentity.fromBytes(hexToBytes("6f74686572")),
+            IbeIdentity.fromBytes(hexToBytes("6d657373616765")),
+            IbeIdentity.fromBytes(hexToBytes("6f74686572")),
+        ],
+        message,
+        seed,
+    );
+
+    assertEqual(ibe.recipientCount(), 2);
+    assertEqual(
+        bytesToHex(ibe.serialize()),
+        "49432049424500048e021207f692c9a992b453ef52f85735b2e2dac7d776a0cb45ef053e5afbff6677b7675ffcefeb13bb51f7ffdeb10e160c0ff16d3d8e48e88462d63138ef2c037dece64e28e06ce6fd5870297cce1dcd3a73b019ba08ab7626f27ce53f6c7598000000024526604d27b14206a8f1d68dc9c78ffdb9a4a54898710307f3f40fcc3a6b8401c60ae5e20059050e6861bc7872e14fbde5f9a114bc3312a8746690b66924edd6873e41aa3c2308fbed53a07db3badb4697b692644b1ac84c1a5f5dd6e1a228ba9f7f98",
+    );
+
+    const ibeRec = IbeMultiRecipientCiphertext.deserialize(ibe.serialize());
+    assertEqual(bytesToHex(ibeRec.decrypt(vetkd)), "f00f11");
+    expect(() => IbeCiphertext.deserialize(ibe.serialize())).toThrow();
+});
+
+test("time-lock IBE encryption", () => {
+    assertEqual(
+        bytesToHex(IbeIdentity.fromTimestamp(11n, 10n).getBytes()),
+        bytesToHex(IbeIdentity.fromTimestamp(20n, 10n).getBytes()),
+    );
+    expect(() => IbeIdentity.fromTimestamp(1n, 0n)).toThrow();
+
+    const dpk = DerivedPublicKey.deserialize(
+        hexToBytes(
+            "972c4c6cc184b56121a1d27ef1ca3a2334d1a51be93573bd18e168f78f8fe15ce44fb029ffe8e9c3ee6bea2660f4f35e0774a35a80d6236c050fd8f831475b5e145116d3e83d26c533545f64b08464e4bcc755f990a381efa89804212d4eef5f",
+        ),
+    );
+
+    const message = hexToBytes("f00f11");
+    const seed = IbeSeed.fromBytes(new Uint8Array(32));
+    const timelock = TimelockCiphertext.encrypt(
+        dpk,
+        1_700_000_000_123_456_789n,
+        1_000_000_000n,
+        message,
+        seed,
+    );
+
+    assertEqual(
+        bytesToHex(timelock.serialize()),
+        "494320544c450001000000003b9aca00000000006553f1014943204942450001a9937528bda5826cf5c7da77a5f5e46719a9748f4ea0aa491c8fba92081e5d55457ab36ec4f6335954c6d87987d0b28301bd8da166493bb537c842d20396da5a68cc9e9672fadedf1e311e0057fc906dfd37d1077ca027954c45336405e66e5efff442d0612cb08a719191919ab89a1a215971e0b4d60f88b63abe1e93739d039f7f98",
+    );
+
+    const timelockRec = TimelockCiphertext.deserialize(timelock.serialize());
+    assertEqual(timelockRec.unlockTime(), 1_700_000_001_000_000_000n);
+    assertEqual(
+        bytesToHex(timelockRec.identity().getBytes()),
+        bytesToHex(
+            IbeIdentity.fromTimestamp(
+                1_700_000_000_500_000_000n,
+                1_000_000_000n,
+            ).getBytes(),
+        ),
+    );
+});
+
 test("hash to scalar", () => {
     const dst = "QUUX-V01-CS02-with-BLS12381SCALAR_XMD:SHA-256_SSWU_RO_";
 
@@ -458,3 +577,108 @@ test("hkdf test vectors", () => {
         assertEqual(bytesToHex(kdf), expected);
     }
 });
+
+test("key derivation test vectors", () => {
+    for (const v of testVectors.key_derivation) {
+        const mpk = MasterPublicKey.deserialize(
+            hexToBytes(v.master_public_key),
+        );
+        const canisterKey = mpk.deriveKey(hexToBytes(v.canister_id));
+        assertEqual(bytesToHex(canisterKey.publicKeyBytes()), v.canister_key);
+        const derivedKey = canisterKey.deriveKey(hexToBytes(v.context));
+        assertEqual(
+            bytesToHex(derivedKey.publicKeyBytes()),
+            v.derived_public_key,
+        );
+    }
+});
+
+test("encrypted vetkey test vectors", () => {
+    for (const v of testVectors.encrypted_vetkeys) {
+        const tsk = new TransportSecretKey(hexToBytes(v.transport_secret_key));
+        assertEqual(bytesToHex(tsk.publicKeyBytes()), v.transport_public_key);
+        const dpk = DerivedPublicKey.deserialize(
+            hexToBytes(v.derived_public_key),
+        );
+        const ek = new EncryptedVetKey(hexToBytes(v.encrypted_vetkey));
+        const vetkey = ek.decryptAndVerify(tsk, dpk, hexToBytes(v.input));
+        assertEqual(bytesToHex(vetkey.signatureBytes()), v.vetkey);
+    }
+});
+
+test("IBE ciphertext test vectors", () => {
+    for (const v of testVectors.ibe_ciphertexts) {
+        const dpk = DerivedPublicKey.deserialize(
+            hexToBytes(v.derived_public_key),
+        );
+        const associatedData =
+            v.associated_data === null
+                ? undefined
+                : hexToBytes(v.associated_data);
+        const ciphertext = IbeCiphertext.encrypt(
+            dpk,
+            IbeIdentity.fromBytes(hexToBytes(v.identity)),
+            hexToBytes(v.message),
+            IbeSeed.fromBytes(hexToBytes(v.seed)),
+            associatedData,
+        );
+        assertEqual(bytesToHex(ciphertext.serialize()), v.ciphertext);
+
+        const vetkey = VetKey.deserialize(hexToBytes(v.vetkey));
+        const plaintext = IbeCiphertext.deserialize(
+            hexToBytes(v.ciphertext),
+        ).decrypt(vetkey, associatedData);
+        assertEqual(bytesToHex(plaintext), v.message);
+    }
+});
+
+test("symmetric key test vectors", () => {
+    for (const v of testVectors.symmetric_keys) {
+        const key = deriveSymmetricKey(
+            hexToBytes(v.input),
+            v.domain_separator,
+            v.output.length / 2,
+        );
+        assertEqual(bytesToHex(key), v.output);
+    }
+    for (const v of testVectors.vetkey_symmetric_keys) {
+        const vetkey = VetKey.deserialize(hexToBytes(v.vetkey));
+        const key = vetkey.deriveSymmetricKey(
+            v.domain_separator,
+            v.output.length / 2,
+        );
+        assertEqual(bytesToHex(key), v.output);
+    }
+});
+
+test("invalid test vectors are rejected", () => {
+    for (const v of testVectors.invalid_derived_public_keys) {
+        expect(
+            () => DerivedPublicKey.deserialize(hexToBytes(v.bytes)),
+            v.description,
+        ).toThrow();
+    }
+    for (const v of testVectors.invalid_encrypted_vetkeys) {
+        const tsk = new TransportSecretKey(hexToBytes(v.transport_secret_key));
+        const dpk = DerivedPublicKey.deserialize(
+            hexToBytes(v.derived_public_key),
+        );
+        expect(() => {
+            const ek = new EncryptedVetKey(hexToBytes(v.encrypted_vetkey));
+            return ek.decryptAndVerify(tsk, dpk, hexToBytes(v.input));
+        }, v.description).toThrow();
+    }
+    for (const v of testVectors.invalid_ibe_ciphertexts) {
+        const vetkey = VetKey.deserialize(hexToBytes(v.vetkey));
+        const associatedData =
+            v.associated_data === null
+                ? undefined
+                : hexToBytes(v.associated_data);
+        expect(() => {
+            const ciphertext = IbeCiphertext.deserialize(
+                hexToBytes(v.ciphertext),
+            );
+            return ciphertext.decrypt(vetkey, associatedData);
+        }, v.description).toThrow();
+    }
+});
@@ -623,23 +623,45 @@ enum IbeDomainSeparators {
     MaskSeed = "ic-vetkd-bls12-381-ibe-mask-seed",
     // Note that the messge length is appended to this
     MaskMsg = "ic-vetkd-bls12-381-ibe-mask-msg-",
+    RecipientHint = "ic-vetkd-bls12-381-ibe-recipient-hint",
 }
 
 // "IC IBE" (ASCII) plus 0x00 0x01 for future extensions/ciphersuites
 const IBE_HEADER = new Uint8Array([
     0x49, 0x43, 0x20, 0x49, 0x42, 0x45, 0x00, 0x01,
 ]);
+const IBE_HEADER_WITH_ASSOCIATED_DATA = new Uint8Array([
+    0x49, 0x43, 0x20, 0x49, 0x42, 0x45, 0x00, 0x03,
+]);
+const IBE_MULTI_RECIPIENT_HEADER = new Uint8Array([
+    0x49, 0x43, 0x20, 0x49, 0x42, 0x45, 0x00, 0x04,
+]);
 const IBE_HEADER_BYTES = 8;
 
 function hashToMask(
     header: Uint8Array,
     seed: Uint8Array,
+    associatedData: Uint8Array,
     msg: Uint8Array,
 ): bigint {
-    const ro_input = new Uint8Array([...header, ...seed, ...msg]);
+    const ro_input = isEqual(header, IBE_HEADER_WITH_ASSOCIATED_DATA)
+        ? new Uint8Array([
+              ...header,
+              ...seed,
+              ...u64ToBytesBE(associatedData.length),
+              ...associatedData,
+              ...msg,
+          ])
+        : new Uint8Array([...header, ...seed, ...msg]);
     return hashToScalar(ro_input, IbeDomainSeparators.HashToMask);
 }
 
+function u64ToBytesBE(value: number | bigint): Uint8Array {
+    const bytes = new Uint8Array(8);
+    new DataView(bytes.buffer).setBigUint64(0, BigInt(value), false);
+    return bytes;
+}
+
 function xorBuf(a: Uint8Array, b: Uint8Array): Uint8Array {
     if (a.length !== b.length) {
         throw new Error("xorBuf arguments should have the same length");
@@ -746,6 +768,35 @@ export class IbeIdentity {
         return IbeIdentity.fromBytes(principal.toUint8Array());
     }
 
+    /**
+     * Create the time-lock identity for a point in time
+     *
+     * The timestamp is rounded up to a multiple of the granularity (both in
+     * nanoseconds), so all timestamps within the same round map to the same
+     * identity. The identity is encoded canonically as a fixed prefix,
+     * followed by the granularity and the round number, each as a 64-bit
+     * big endian integer.
+     */
+    static fromTimestamp(timestamp: bigint, granularity: bigint) {
+        return IbeIdentity.fromTimelockRound(
+            timelockRound(timestamp, granularity),
+            granularity,
+        );
+    }
+
+    /**
+     * @internal create the time-lock identity for a round
+     */
+    static fromTimelockRound(round: bigint, granularity: bigint) {
+        return IbeIdentity.fromBytes(
+            new Uint8Array([
+                ...TIMELOCK_IDENTITY_PREFIX,
+                ...u64ToBytesBE(granularity),
+                ...u64ToBytesBE(round),
+            ]),
+        );
+    }
+
     /**
      * @internal getter returning the encoded
      */
@@ -754,6 +805,28 @@ export class IbeIdentity {
     }
 }
 
+const TIMELOCK_IDENTITY_PREFIX = new TextEncoder().encode(
+    "ic-vetkeys-timelock",
+);
+
+const TIMELOCK_HEADER = new Uint8Array([
+    0x49, 0x43, 0x20, 0x54, 0x4c, 0x45, 0x00, 0x01,
+]);
+
+const U64_MAX = (1n << 64n) - 1n;
+
+function timelockRound(timestamp: bigint, granularity: bigint): bigint {
+    if (timestamp < 0n || timestamp > U64_MAX) {
+        throw new Error(
+            "Time-lock timestamp must be a 64-bit unsigned integer",
+        );
+    }
+    if (granularity <= 0n || granularity > U64_MAX) {
+        throw new Error("Time-lock granularity must be positive");
+    }
+    return (timestamp + granularity - 1n) / granularity;
+}
+
 const SEED_BYTES = 32;
 
 /**
@@ -857,7 +930,10 @@ export class IbeCiphertext {
         );
         const c3 = bytes.subarray(IBE_HEADER_BYTES + G2_BYTES + SEED_BYTES);
 
-        if (!isEqual(header, IBE_HEADER)) {
+        if (
+            !isEqual(header, IBE_HEADER) &&
+            !isEqual(header, IBE_HEADER_WITH_ASSOCIATED_DATA)
+        ) {
             throw new Error("Unexpected header for IBE ciphertext");
         }
 
@@ -874,15 +950,29 @@ export class IbeCiphertext {
      * Any user who is able to retrieve the VetKey for the specified
      * derived public key and identity will be able to decrypt this
      * message.
+     *
+     * If associated data is provided, the ciphertext is bound to it and can
+     * only be decrypted if exactly the same associated data is provided to
+     * `decrypt`. The associated data itself is not encrypted and not included
+     * in the ciphertext.
      */
     static encrypt(
         dpk: DerivedPublicKey,
         identity: IbeIdentity,
         msg: Uint8Array,
         seed: IbeSeed,
+        associatedData?: Uint8Array,
     ): IbeCiphertext {
-        const header = IBE_HEADER;
-        const t = hashToMask(header, seed.getBytes(), msg);
+        const header =
+            associatedData === undefined
+                ? IBE_HEADER
+                : IBE_HEADER_WITH_ASSOCIATED_DATA;
+        const t = hashToMask(
+            header,
+            seed.getBytes(),
+            associatedData ?? new Uint8Array(),
+            msg,
+        );
         const pt = augmentedHashToG1(dpk, identity.getBytes());
         const tsig = bls12_381.fields.Fp12.pow(
             bls12_381.pairing(pt, dpk.getPoint()),
@@ -898,15 +988,26 @@ export class IbeCiphertext {
 
     /**
      * Decrypt an IBE ciphertext, returning the message
+     *
+     * The associated data must be equal to the associated data used for
+     * encryption, if any.
      */
-    decrypt(vetkd: VetKey): Uint8Array {
+    decrypt(vetkd: VetKey, associatedData?: Uint8Array): Uint8Array {
+        const ad = associatedData ?? new Uint8Array();
+        if (
+            !isEqual(this.#header, IBE_HEADER_WITH_ASSOCIATED_DATA) &&
+            ad.length > 0
+        ) {
+            throw new Error("IBE ciphertext is not bound to associated data");
+        }
+
         const k_c1 = bls12_381.pairing(vetkd.getPoint(), this.#c1);
 
         const seed = maskSeed(this.#c2, serializeGtElem(k_c1));
 
         const msg = maskMsg(this.#c3, seed);
 
-        const t = hashToMask(this.#header, seed, msg);
+        const t = hashToMask(this.#header, seed, ad, msg);
 
         const g2_t = bls12_381.G2.ProjectivePoint.BASE.multiply(t);
 
@@ -934,3 +1035,311 @@ export class IbeCiphertext {
         this.#c3 = c3;
     }
 }
+
+const RECIPIENT_HINT_BYTES = 16;
+const RECIPIENT_SLOT_BYTES = RECIPIENT_HINT_BYTES + SEED_BYTES;
+
+function recipientHint(t: Uint8Array): Uint8Array {
+    return deriveSymmetricKey(
+        t,
+        IbeDomainSeparators.RecipientHint,
+        RECIPIENT_HINT_BYTES,
+    );
+}
+
+function compareBytes(x: Uint8Array, y: Uint8Array): number {
+    for (let i = 0; i < Math.min(x.length, y.length); ++i) {
+        if (x[i] !== y[i]) {
+            return x[i] - y[i];
+        }
+    }
+    return x.length - y.length;
+}
+
+/**
+ * IBE (Identity Based Encryption) of a single message to multiple identities
+ *
+ * The message is included in the ciphertext only once, and a recipient slot
+ * is added for each identity. Recipients locate their slot using a
+ * pseudorandom hint, so the recipient identities are not revealed.
+ */
+export class IbeMultiRecipientCiphertext {
+    readonly #header: Uint8Array;
+    readonly #c1: G2Point;
+    readonly #slots: Uint8Array[];
+    readonly #c3: Uint8Array;
+
+    /**
+     * Serialize the multi-recipient IBE ciphertext to a bytestring
+     */
+    serialize(): Uint8Array {
+        const c1bytes = this.#c1.toRawBytes(true);
+        const count = new Uint8Array(4);
+        new DataView(count.buffer).setUint32(0, this.#slots.length, false);
+        return new Uint8Array([
+            ...this.#header,
+            ...c1bytes,
+            ...count,
+            ...this.#slots.flatMap((slot) => [...slot]),
+            ...this.#c3,
+        ]);
+    }
+
+    /**
+     * Deserialize a multi-recipient IBE ciphertext
+     */
+    static deserialize(bytes: Uint8Array): IbeMultiRecipientCiphertext {
+        const slotsStart = IBE_HEADER_BYTES + G2_BYTES + 4;
+        if (bytes.length < slotsStart) {
+            throw new Error("Invalid multi-recipient IBE ciphertext");
+        }
+
+        const header = bytes.subarray(0, IBE_HEADER_BYTES);
+        if (!isEqual(header, IBE_MULTI_RECIPIENT_HEADER)) {
+            throw new Error(
+                "Unexpected header for multi-recipient IBE ciphertext",
+            );
+        }
+
+        const c1 = bls12_381.G2.ProjectivePoint.fromHex(
+            bytes.subarray(IBE_HEADER_BYTES, IBE_HEADER_BYTES + G2_BYTES),
+        );
+        const count = new DataView(
+            bytes.buffer,
+            bytes.byteOffset + IBE_HEADER_BYTES + G2_BYTES,
+            4,
+        ).getUint32(0, false);
+
+        const slotsEnd = slotsStart + count * RECIPIENT_SLOT_BYTES;
+        if (bytes.length < slotsEnd) {
+            throw new Error("Invalid multi-recipient IBE ciphertext");
+        }
+
+        const slots = [];
+        for (let i = slotsStart; i < slotsEnd; i += RECIPIENT_SLOT_BYTES) {
+            slots.push(bytes.subarray(i, i + RECIPIENT_SLOT_BYTES));
+        }
+        const c3 = bytes.subarray(slotsEnd);
+
+        return new IbeMultiRecipientCiphertext(header, c1, slots, c3);
+    }
+
+    /**
+     * Return the number of recipient slots of this ciphertext
+     */
+    recipientCount(): number {
+        return this.#slots.length;
+    }
+
+    /**
+     * Encrypt a message to multiple identities using IBE, returning the ciphertext
+     *
+     * The seed parameter must be a randomly generated value of exactly 32 bytes,
+     * that was generated just for this one message. Using it for a second message,
+     * or for any other purposes, compromises the security of the IBE scheme.
+     *
+     * Any user who is able to retrieve the VetKey for the specified
+     * derived public key and any of the identities will be able to decrypt
+     * this message. Duplicate identities are encrypted to only once.
+     */
+    static encrypt(
+        dpk: DerivedPublicKey,
+        identities: IbeIdentity[],
+        msg: Uint8Array,
+        seed: IbeSeed,
+    ): IbeMultiRecipientCiphertext {
+        const header = IBE_MULTI_RECIPIENT_HEADER;
+        const t = hashToMask(header, seed.getBytes(), new Uint8Array(), msg);
+
+        const uniqueIdentities = identities
+            .map((identity) => identity.getBytes())
+            .sort(compareBytes)
+            .filter((id, i, ids) => i === 0 || !isEqual(id, ids[i - 1]));
+
+        const slots = uniqueIdentities.map((identity) => {
+            const pt = augmentedHashToG1(dpk, identity);
+            const tsig = serializeGtElem(
+                bls12_381.fields.Fp12.pow(
+                    bls12_381.pairing(pt, dpk.getPoint()),
+                    t,
+                ),
+            );
+            return new Uint8Array([
+                ...recipientHint(tsig),
+                ...maskSeed(seed.getBytes(), tsig),
+            ]);
+        });
+
+        const c1 = bls12_381.G2.ProjectivePoint.BASE.multiply(t);
+        const c3 = maskMsg(msg, seed.getBytes());
+
+        return new IbeMultiRecipientCiphertext(header, c1, slots, c3);
+    }
+
+    /**
+     * Decrypt a multi-recipient IBE ciphertext, returning the message
+     */
+    decrypt(vetkd: VetKey): Uint8Array {
+        const k_c1 = serializeGtElem(
+            bls12_381.pairing(vetkd.getPoint(), this.#c1),
+        );
+        const hint = recipientHint(k_c1);
+
+        const slot = this.#slots.find((slot) =>
+            isEqual(slot.subarray(0, RECIPIENT_HINT_BYTES), hint),
+        );
+        if (!slot) {
+            throw new Error("Decryption failed: no matching recipient");
+        }
+
+        const seed = maskSeed(slot.subarray(RECIPIENT_HINT_BYTES), k_c1);
+
+        const msg = maskMsg(this.#c3, seed);
+
+        const t = hashToMask(this.#header, seed, new Uint8Array(), msg);
+
+        const g2_t = bls12_381.G2.ProjectivePoint.BASE.multiply(t);
+
+        const valid = isEqual(g2_t.toRawBytes(true), this.#c1.toRawBytes(true));
+
+        if (valid) {
+            return msg;
+        } else {
+            throw new Error("Decryption failed");
+        }
+    }
+
+    /**
+     * Private constructor
+     */
+    private constructor(
+        header: Uint8Array,
+        c1: G2Point,
+        slots: Uint8Array[],
+        c3: Uint8Array,
+    ) {
+        this.#header = header;
+        this.#c1 = c1;
+        this.#slots = slots;
+        this.#c3 = c3;
+    }
+}
+
+/**
+ * A message time-locked to a point in time
+ *
+ * This is an IBE ciphertext for the time-lock identity of the point in time
+ * (see `IbeIdentity.fromTimestamp`) that additionally records the point in
+ * time, so that the recipient knows which key to request.
+ */
+export class TimelockCiphertext {
+    readonly #granularity: bigint;
+    readonly #round: bigint;
+    readonly #ciphertext: IbeCiphertext;
+
+    /**
+     * Serialize the time-lock ciphertext to a bytestring
+     */
+    serialize(): Uint8Array {
+        return new Uint8Array([
+            ...TIMELOCK_HEADER,
+            ...u64ToBytesBE(this.#granularity),
+            ...u64ToBytesBE(this.#round),
+            ...this.#ciphertext.serialize(),
+        ]);
+    }
+
+    /**
+     * Deserialize a time-lock ciphertext
+     */
+    static deserialize(bytes: Uint8Array): TimelockCiphertext {
+        const prefixBytes = TIMELOCK_HEADER.length + 8 + 8;
+        if (bytes.length < prefixBytes) {
+            throw new Error("Invalid time-lock ciphertext");
+        }
+
+        const header = bytes.subarray(0, TIMELOCK_HEADER.length);
+        if (!isEqual(header, TIMELOCK_HEADER)) {
+            throw new Error("Unexpected header for time-lock ciphertext");
+        }
+
+        const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.length);
+        const granularity = view.getBigUint64(TIMELOCK_HEADER.length, false);
+        const round = view.getBigUint64(TIMELOCK_HEADER.length + 8, false);
+        if (granularity === 0n) {
+            throw new Error("Invalid time-lock ciphertext granularity");
+        }
+
+        return new TimelockCiphertext(
+            granularity,
+            round,
+            IbeCiphertext.deserialize(bytes.subarray(prefixBytes)),
+        );
+    }
+
+    /**
+     * Time-lock a message until the given unlock time
+     *
+     * The unlock time is rounded up to a multiple of the granularity, see
+     * `IbeIdentity.fromTimestamp`. Otherwise the same considerations as for
+     * `IbeCiphertext.encrypt` apply.
+     */
+    static encrypt(
+        dpk: DerivedPublicKey,
+        unlockTime: bigint,
+        granularity: bigint,
+        msg: Uint8Array,
+        seed: IbeSeed,
+    ): TimelockCiphertext {
+        const round = timelockRound(unlockTime, granularity);
+        const identity = IbeIdentity.fromTimelockRound(round, granularity);
+        return new TimelockCiphertext(
+            granularity,
+            round,
+            IbeCiphertext.encrypt(dpk, identity, msg, seed),
+        );
+    }
+
+    /**
+     * Return the time after which this message can be decrypted, or
+     * undefined if it is not representable as a 64-bit timestamp
+     */
+    unlockTime(): bigint | undefined {
+        const unlockTime = this.#round * this.#granularity;
+        return unlockTime <= U64_MAX ? unlockTime : undefined;
+    }
+
+    /**
+     * Return the granularity used to round the unlock time
+     */
+    granularity(): bigint {
+        return this.#granularity;
+    }
+
+    /**
+     * Return the IBE identity whose VetKey decrypts this message
+     */
+    identity(): IbeIdentity {
+        return IbeIdentity.fromTimelockRound(this.#round, this.#granularity);
+    }
+
+    /**
+     * Decrypt a time-locked message, returning the message
+     */
+    decrypt(vetkd: VetKey): Uint8Array {
+        return this.#ciphertext.decrypt(vetkd);
+    }
+
+    /**
+     * Private constructor
+     */
+    private constructor(
+        granularity: bigint,
+        round: bigint,
+        ciphertext: IbeCiphertext,
+    ) {
+        this.#granularity = granularity;
+        this.#round = round;
+        this.#ciphertext = ciphertext;
+    }
+}